@@ -5,8 +5,11 @@ import { Extractor } from './components/Extractor';
 import { Database } from './components/Database';
 import { Settings } from './components/Settings';
 import { ErrorBoundary } from './components/ErrorBoundary';
-import { AppSettings } from './types';
-import { SETTINGS_KEY } from './constants';
+import { UnlockVault } from './components/UnlockVault';
+import { loadApiKey } from './services/settings';
+import { getStartupError } from './services/db';
+import { VAULT_SWITCHED_EVENT } from './services/vault';
+import { listen } from '@tauri-apps/api/event';
 
 const MainLayout = () => {
   return (
@@ -30,20 +33,13 @@ const App = () => {
   // Check for API key on mount
   const [apiKey, setApiKey] = useState<string | null>(null);
   const [isLoading, setIsLoading] = useState(true);
+  const [startupError, setStartupError] = useState<string | null>(null);
+  const [isLocked, setIsLocked] = useState(false);
 
   // Theme State
   const [theme, setTheme] = useState<'light' | 'dark'>('light');
 
   useEffect(() => {
-    // Load Settings
-    const savedSettings = localStorage.getItem(SETTINGS_KEY);
-    if (savedSettings) {
-      const parsed: AppSettings = JSON.parse(savedSettings);
-      if (parsed.apiKey) {
-        setApiKey(parsed.apiKey);
-      }
-    }
-
     // Load Theme
     const savedTheme = localStorage.getItem('vaulty_theme');
     if (savedTheme === 'dark' || savedTheme === 'light') {
@@ -54,9 +50,34 @@ const App = () => {
       document.documentElement.classList.add('dark');
     }
 
-    setIsLoading(false);
+    // Settings live in the database, so load them only once it is open
+    getStartupError()
+      .then(error => {
+        if (error?.kind === "vaultLocked") setIsLocked(true);
+        else if (error) setStartupError(error.message);
+        else return loadSettings();
+      })
+      .catch(e => console.error("Failed to check database status", e))
+      .finally(() => setIsLoading(false));
+
+    // Everything on screen belongs to the previous vault, so start over
+    const unlisten = listen(VAULT_SWITCHED_EVENT, () => window.location.reload());
+    return () => {
+      unlisten.then(stop => stop());
+    };
   }, []);
 
+  const loadSettings = () => loadApiKey()
+    .then(key => {
+      if (key) setApiKey(key);
+    })
+    .catch(e => console.error("Failed to load API key", e));
+
+  const handleUnlocked = () => {
+    setIsLocked(false);
+    loadSettings();
+  };
+
   const toggleTheme = () => {
     const newTheme = theme === 'light' ? 'dark' : 'light';
     setTheme(newTheme);
@@ -69,6 +90,10 @@ const App = () => {
     }
   };
 
+  if (startupError) return <div className="flex h-screen w-full items-center justify-center bg-neutral-50 dark:bg-neutral-950 text-red-600 dark:text-red-400 p-8 text-center">{startupError}</div>;
+
+  if (isLocked) return <UnlockVault onUnlocked={handleUnlocked} />;
+
   if (isLoading) return <div className="flex h-screen w-full items-center justify-center bg-neutral-50 dark:bg-neutral-950 dark:text-neutral-200">Loading Vaulty...</div>;
 
   return (
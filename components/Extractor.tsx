@@ -2,8 +2,8 @@ import React, { useState, useRef, useEffect, useContext } from 'react';
 import { Upload, Check, AlertCircle, Wand2, X } from 'lucide-react';
 import { Button } from './Button';
 import { ApiKeyContext } from '../App.tsx';
-import { analyzePageImage } from '../services/geminiService';
-import { Exercise } from '../types';
+import { analyzePageImage, cancelAnalysis } from '../services/geminiService';
+import { AppError, Exercise } from '../types';
 import { saveExercises, saveImage } from '../services/db';
 import { useNavigate } from 'react-router-dom';
 import { open } from '@tauri-apps/api/dialog';
@@ -27,6 +27,7 @@ export const Extractor = () => {
   // Canvas refs
   const imageRef = useRef<HTMLImageElement>(null);
   const canvasRef = useRef<HTMLCanvasElement>(null);
+  const analysisIdRef = useRef<string | null>(null);
 
   // Drawing state
   const [isDrawing, setIsDrawing] = useState(false);
@@ -143,15 +144,17 @@ export const Extractor = () => {
       return;
     }
 
+    const requestId = crypto.randomUUID();
+    analysisIdRef.current = requestId;
     setIsAnalyzing(true);
     try {
       let results;
       if (originalImagePath && !pdfPath) {
         // Analyzing uploaded image file (not PDF)
-        results = await analyzePageImage(null, originalImagePath, apiKey);
+        results = await analyzePageImage(null, originalImagePath, apiKey, undefined, requestId);
       } else if (currentImage) {
         // Analyzing PDF (stitched) or data URI image
-        results = await analyzePageImage(currentImage, null, apiKey);
+        results = await analyzePageImage(currentImage, null, apiKey, pdfPath ? pdfImages.length : undefined, requestId);
       } else {
         throw new Error("No image to analyze");
       }
@@ -167,12 +170,26 @@ export const Extractor = () => {
       setExtractedExercises(fullResults);
     } catch (error) {
       console.error("[ANALYZE] Analysis error:", error);
-      alert(`Analysis failed: ${error instanceof Error ? error.message : "Please check your API Key and try again."}`);
+      const appError = error as AppError;
+      if (appError?.kind === "cancelled") {
+        // User stopped the request; nothing to report
+      } else if (appError?.kind === "missingApiKey") {
+        alert("Analysis failed: Please add your Gemini API Key in Settings.");
+      } else {
+        alert(`Analysis failed: ${error instanceof Error ? error.message : appError?.message ?? "Please check your API Key and try again."}`);
+      }
     } finally {
+      analysisIdRef.current = null;
       setIsAnalyzing(false);
     }
   };
 
+  const handleCancelAnalyze = async () => {
+    if (analysisIdRef.current) {
+      await cancelAnalysis(analysisIdRef.current);
+    }
+  };
+
   // Drawing Logic - Full width, only control height
   const getMouseY = (e: React.MouseEvent) => {
     if (!canvasRef.current) return 0;
@@ -207,7 +224,7 @@ export const Extractor = () => {
       const cropUri = captureCrop(currentRect);
       setExtractedExercises(prev => prev.map(ex =>
         ex.id === selectedExerciseId
-          ? { ...ex, imageUri: cropUri, boundingBox: { y: currentRect.y, height: currentRect.h } }
+          ? { ...ex, imageUri: cropUri, boundingBoxes: [{ page: ex.sourcePage ?? 1, y: currentRect.y, height: currentRect.h }] }
           : ex
       ));
     }
@@ -277,12 +294,13 @@ export const Extractor = () => {
 
       // Draw all existing bounding boxes subtly (full width)
       extractedExercises.forEach(ex => {
-        if (ex.boundingBox && ex.id !== selectedExerciseId) {
+        const box = ex.boundingBoxes?.[0];
+        if (box && ex.id !== selectedExerciseId) {
           ctx.fillStyle = 'rgba(59, 130, 246, 0.1)'; // Light blue
-          ctx.fillRect(0, ex.boundingBox.y, canvas.width, ex.boundingBox.height);
+          ctx.fillRect(0, box.y, canvas.width, box.height);
           ctx.strokeStyle = 'rgba(59, 130, 246, 0.3)';
           ctx.lineWidth = 1;
-          ctx.strokeRect(0, ex.boundingBox.y, canvas.width, ex.boundingBox.height);
+          ctx.strokeRect(0, box.y, canvas.width, box.height);
         }
       });
 
@@ -333,7 +351,8 @@ export const Extractor = () => {
       return {
         ...ex,
         imageUri: cropPath,
-        pageImageUri: finalPageImg
+        pageImageUri: finalPageImg,
+        sourceFile: pdfPath ?? originalImagePath ?? undefined
       };
     }));
 
@@ -419,16 +438,23 @@ export const Extractor = () => {
         <div className="p-5 border-b border-neutral-200 dark:border-neutral-800">
           <div className="flex justify-between items-center mb-3">
             <h2 className="font-semibold text-lg text-neutral-900 dark:text-white">Exercises</h2>
-            {(imageUri || pdfImages.length > 0) && (
-              <Button
-                size="sm"
-                onClick={handleAnalyze}
-                disabled={isAnalyzing}
-                icon={isAnalyzing ? <div className="animate-spin w-4 h-4 border-2 border-current border-t-transparent rounded-full" /> : <Wand2 size={14} />}
-              >
-                {isAnalyzing ? 'Analyzing...' : 'AI Extract'}
-              </Button>
-            )}
+            <div className="flex gap-2">
+              {(imageUri || pdfImages.length > 0) && (
+                <Button
+                  size="sm"
+                  onClick={handleAnalyze}
+                  disabled={isAnalyzing}
+                  icon={isAnalyzing ? <div className="animate-spin w-4 h-4 border-2 border-current border-t-transparent rounded-full" /> : <Wand2 size={14} />}
+                >
+                  {isAnalyzing ? 'Analyzing...' : 'AI Extract'}
+                </Button>
+              )}
+              {isAnalyzing && (
+                <Button size="sm" variant="secondary" onClick={handleCancelAnalyze} icon={<X size={14} />}>
+                  Cancel
+                </Button>
+              )}
+            </div>
           </div>
           {(imageUri || pdfImages.length > 0) && (
             <div className="space-y-2">
@@ -1,8 +1,12 @@
 import React, { useState, useContext, useEffect } from 'react';
-import { Key, Save, AlertTriangle, ExternalLink, Moon, Sun } from 'lucide-react';
+import { Key, Save, AlertTriangle, ExternalLink, Moon, Sun, Lock, FolderOpen } from 'lucide-react';
+import { open } from '@tauri-apps/api/dialog';
 import { Button } from './Button';
 import { ApiKeyContext, ThemeContext } from '../App.tsx';
-import { SETTINGS_KEY } from '../constants';
+import { AppError, GeminiModel } from '../types';
+import { getDefaultModel, saveApiKey, setDefaultModel } from '../services/settings';
+import { changePassphrase, getVaultStatus, setVaultPassphrase } from '../services/vault';
+import { getDataDirectory, moveDataDirectory } from '../services/db';
 
 export const Settings = () => {
   const { apiKey, setApiKey } = useContext(ApiKeyContext);
@@ -14,9 +18,85 @@ export const Settings = () => {
     if (apiKey) setInputKey(apiKey);
   }, [apiKey]);
 
-  const handleSave = () => {
+  const [model, setModel] = useState<GeminiModel>('gemini-2.5-flash');
+
+  useEffect(() => {
+    getDefaultModel()
+      .then(setModel)
+      .catch(e => console.error("Failed to load default model", e));
+  }, []);
+
+  const handleModelChange = async (newModel: GeminiModel) => {
+    try {
+      await setDefaultModel(newModel);
+      setModel(newModel);
+    } catch (e) {
+      console.error("Failed to save default model", e);
+      alert("Failed to save default model: " + ((e as AppError)?.message ?? String(e)));
+    }
+  };
+
+  const [isEncrypted, setIsEncrypted] = useState(false);
+  const [currentPassphrase, setCurrentPassphrase] = useState('');
+  const [newPassphrase, setNewPassphrase] = useState('');
+
+  useEffect(() => {
+    getVaultStatus()
+      .then(status => setIsEncrypted(status.encrypted))
+      .catch(e => console.error("Failed to load vault status", e));
+  }, []);
+
+  const handlePassphrase = async () => {
+    try {
+      if (isEncrypted) {
+        await changePassphrase(currentPassphrase, newPassphrase);
+      } else {
+        await setVaultPassphrase(newPassphrase);
+        setIsEncrypted(true);
+      }
+    } catch (e) {
+      console.error("Failed to update passphrase", e);
+      alert("Failed to update passphrase: " + ((e as AppError)?.message ?? String(e)));
+      return;
+    }
+    setCurrentPassphrase('');
+    setNewPassphrase('');
+    alert("Passphrase saved. You will need it to open Vaulty from now on.");
+  };
+
+  const [dataDir, setDataDir] = useState('');
+  const [isMoving, setIsMoving] = useState(false);
+
+  useEffect(() => {
+    getDataDirectory()
+      .then(setDataDir)
+      .catch(e => console.error("Failed to load data directory", e));
+  }, []);
+
+  const handleMoveData = async () => {
+    const selected = await open({ directory: true, title: "Choose where to keep your vault" });
+    if (typeof selected !== 'string') return;
+
+    setIsMoving(true);
+    try {
+      setDataDir(await moveDataDirectory(selected));
+    } catch (e) {
+      console.error("Failed to move data directory", e);
+      alert("Failed to move your vault: " + ((e as AppError)?.message ?? String(e)));
+    } finally {
+      setIsMoving(false);
+    }
+  };
+
+  const handleSave = async () => {
+    try {
+      await saveApiKey(inputKey);
+    } catch (e) {
+      console.error("Failed to save API key", e);
+      alert("Failed to save API key: " + ((e as AppError)?.message ?? String(e)));
+      return;
+    }
     setApiKey(inputKey);
-    localStorage.setItem(SETTINGS_KEY, JSON.stringify({ apiKey: inputKey }));
     setSaved(true);
     setTimeout(() => setSaved(false), 2000);
   };
@@ -60,7 +140,7 @@ export const Settings = () => {
           <div className="flex-1">
             <h3 className="text-lg font-medium text-neutral-900 dark:text-white mb-1">Gemini API Key</h3>
             <p className="text-sm text-neutral-500 dark:text-neutral-400 mb-4">
-              Vaulty uses Google's Gemini 2.5 Flash model to analyze your documents. 
+              Vaulty uses Google's Gemini models to analyze your documents. 
               Your key is stored locally on your device and never sent to our servers.
             </p>
             
@@ -77,6 +157,20 @@ export const Settings = () => {
                 </Button>
             </div>
 
+            <div className="flex items-center gap-2 mb-4">
+                <label htmlFor="gemini-model" className="text-sm text-neutral-600 dark:text-neutral-400">Model</label>
+                <select
+                  id="gemini-model"
+                  className="flex-1 border border-neutral-300 dark:border-neutral-700 dark:bg-neutral-800 dark:text-white rounded-lg px-4 py-2 focus:ring-2 focus:ring-blue-500 focus:border-transparent outline-none transition-all text-sm"
+                  value={model}
+                  onChange={(e) => handleModelChange(e.target.value as GeminiModel)}
+                >
+                  <option value="gemini-2.5-flash">Gemini 2.5 Flash (default)</option>
+                  <option value="gemini-2.5-pro">Gemini 2.5 Pro (best for hard math, slower)</option>
+                  <option value="gemini-2.5-flash-lite">Gemini 2.5 Flash-Lite (cheapest)</option>
+                </select>
+            </div>
+
             <div className="bg-amber-50 dark:bg-amber-900/20 border border-amber-200 dark:border-amber-800 rounded-lg p-3 flex gap-3 items-start">
                 <AlertTriangle size={16} className="text-amber-600 dark:text-amber-500 mt-0.5 flex-shrink-0" />
                 <div className="text-xs text-amber-800 dark:text-amber-400">
@@ -96,6 +190,65 @@ export const Settings = () => {
         </div>
       </div>
       
+      {/* Encryption */}
+      <div className="bg-white dark:bg-neutral-900 rounded-xl shadow-sm border border-neutral-200 dark:border-neutral-800 p-6 mt-6">
+        <div className="flex items-start gap-4">
+          <div className="p-3 bg-neutral-100 dark:bg-neutral-800 rounded-lg text-neutral-900 dark:text-white">
+            <Lock size={24} />
+          </div>
+          <div className="flex-1">
+            <h3 className="text-lg font-medium text-neutral-900 dark:text-white mb-1">Vault Encryption</h3>
+            <p className="text-sm text-neutral-500 dark:text-neutral-400 mb-4">
+              {isEncrypted
+                ? "Your vault is encrypted. Vaulty asks for the passphrase on every launch."
+                : "Encrypt your vault with a passphrase. There is no way to recover a forgotten passphrase."}
+            </p>
+            <div className="flex gap-2">
+              {isEncrypted && (
+                <input
+                  type="password"
+                  className="flex-1 border border-neutral-300 dark:border-neutral-700 dark:bg-neutral-800 dark:text-white rounded-lg px-4 py-2 focus:ring-2 focus:ring-blue-500 focus:border-transparent outline-none transition-all text-sm"
+                  placeholder="Current passphrase"
+                  value={currentPassphrase}
+                  onChange={(e) => setCurrentPassphrase(e.target.value)}
+                />
+              )}
+              <input
+                type="password"
+                className="flex-1 border border-neutral-300 dark:border-neutral-700 dark:bg-neutral-800 dark:text-white rounded-lg px-4 py-2 focus:ring-2 focus:ring-blue-500 focus:border-transparent outline-none transition-all text-sm"
+                placeholder="New passphrase"
+                value={newPassphrase}
+                onChange={(e) => setNewPassphrase(e.target.value)}
+              />
+              <Button onClick={handlePassphrase} disabled={!newPassphrase || (isEncrypted && !currentPassphrase)}>
+                {isEncrypted ? 'Change' : 'Encrypt'}
+              </Button>
+            </div>
+          </div>
+        </div>
+      </div>
+
+      {/* Data Location */}
+      <div className="bg-white dark:bg-neutral-900 rounded-xl shadow-sm border border-neutral-200 dark:border-neutral-800 p-6 mt-6">
+        <div className="flex items-start gap-4">
+          <div className="p-3 bg-neutral-100 dark:bg-neutral-800 rounded-lg text-neutral-900 dark:text-white">
+            <FolderOpen size={24} />
+          </div>
+          <div className="flex-1 min-w-0">
+            <h3 className="text-lg font-medium text-neutral-900 dark:text-white mb-1">Data Location</h3>
+            <p className="text-sm text-neutral-500 dark:text-neutral-400 mb-4">
+              Where your vault and images are stored. Moving copies everything, checks the copy, then removes the old files.
+            </p>
+            <div className="flex gap-2 items-center">
+              <code className="flex-1 truncate text-xs bg-neutral-100 dark:bg-neutral-800 text-neutral-700 dark:text-neutral-300 rounded-lg px-3 py-2">{dataDir}</code>
+              <Button variant="secondary" onClick={handleMoveData} disabled={isMoving}>
+                {isMoving ? 'Moving...' : 'Move...'}
+              </Button>
+            </div>
+          </div>
+        </div>
+      </div>
+
       <div className="mt-8 border-t border-neutral-200 dark:border-neutral-800 pt-6">
         <h4 className="text-sm font-semibold text-neutral-900 dark:text-white mb-4">Data Management</h4>
         <div className="flex gap-4">
@@ -0,0 +1,48 @@
+import React, { useState } from 'react';
+import { Lock } from 'lucide-react';
+import { Button } from './Button';
+import { AppError } from '../types';
+import { unlockVault } from '../services/vault';
+
+export const UnlockVault = ({ onUnlocked }: { onUnlocked: () => void }) => {
+  const [passphrase, setPassphrase] = useState('');
+  const [error, setError] = useState<string | null>(null);
+  const [isUnlocking, setIsUnlocking] = useState(false);
+
+  const handleUnlock = async (e: React.FormEvent) => {
+    e.preventDefault();
+    setIsUnlocking(true);
+    setError(null);
+    try {
+      await unlockVault(passphrase);
+      onUnlocked();
+    } catch (err) {
+      setError((err as AppError)?.message ?? String(err));
+    } finally {
+      setIsUnlocking(false);
+    }
+  };
+
+  return (
+    <div className="flex h-screen w-full items-center justify-center bg-neutral-50 dark:bg-neutral-950">
+      <form onSubmit={handleUnlock} className="w-80 bg-white dark:bg-neutral-900 rounded-xl shadow-sm border border-neutral-200 dark:border-neutral-800 p-6 space-y-4">
+        <div className="flex items-center gap-2 text-neutral-900 dark:text-white">
+          <Lock size={18} />
+          <h1 className="font-semibold text-lg">Vault locked</h1>
+        </div>
+        <input
+          type="password"
+          autoFocus
+          value={passphrase}
+          onChange={(e) => setPassphrase(e.target.value)}
+          placeholder="Passphrase"
+          className="w-full px-3 py-2 rounded-lg border border-neutral-200 dark:border-neutral-700 bg-white dark:bg-neutral-800 text-neutral-900 dark:text-white"
+        />
+        {error && <p className="text-sm text-red-600 dark:text-red-400">{error}</p>}
+        <Button type="submit" className="w-full" disabled={isUnlocking || !passphrase}>
+          {isUnlocking ? 'Unlocking...' : 'Unlock'}
+        </Button>
+      </form>
+    </div>
+  );
+};
@@ -1,6 +1,7 @@
 import { useState, useCallback } from 'react';
 import { open } from '@tauri-apps/api/dialog';
 import { convertFileSrc, invoke } from '@tauri-apps/api/tauri';
+import { AppError } from '../types';
 
 export const usePdfProcessing = () => {
     const [imageUri, setImageUri] = useState<string | null>(null);
@@ -61,7 +62,7 @@ export const usePdfProcessing = () => {
                 setImageUri(convertFileSrc(path));
             }
         } catch (e) {
-            throw new Error(`Failed to process file: ${e}`);
+            throw new Error(`Failed to process file: ${(e as AppError)?.message ?? e}`);
         } finally {
             setIsLoading(false);
         }
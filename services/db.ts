@@ -1,4 +1,4 @@
-import { Exercise } from "../types";
+import { AnkiExportReport, AppError, Attachment, Attempt, AttemptOutcome, BoundingBox, BrokenImageRef, BulkDeleteResult, Course, CourseMetadata, CourseSummary, CreatedExercise, DatabaseStats, DeleteTagReport, Exercise, ExerciseImage, ExercisePage, ExerciseStatus, ExerciseUpdate, HistoryEntry, ImageKind, ImportMode, ImportSummary, LinkedExercise, LinkRelation, MergeCoursesReport, OptimizeReport, OrphanReport, ReviewState, SaveFormat, SearchPage, Statistics, StoppedTimer, StorageCleanupReport, TagCount, TimerSession, UndoneOperation, WeekMetadata } from "../types";
 import { convertFileSrc, invoke } from '@tauri-apps/api/tauri';
 
 const EVENT_KEY = "vaulty-db-change";
@@ -19,8 +19,60 @@ export const getImageUrl = (path: string | undefined) => {
   }
 };
 
-export const saveImage = async (base64Data: string): Promise<string> => {
-  return await invoke("save_image", { base64Data });
+// Downscaled copy of a saved image as a data URL, for grids and lists
+export const getImageThumbnail = async (relativePath: string, maxWidth: number): Promise<string> => {
+  return await invoke("get_image_thumbnail", { relativePath, maxWidth });
+};
+
+// Error from opening or upgrading the database at startup, if any
+export const getStartupError = async (): Promise<AppError | null> => {
+  return await invoke("get_startup_error");
+};
+
+// Without a format the image_format setting applies; for PNG the bytes are stored as they are
+export const saveImage = async (base64Data: string, format?: SaveFormat): Promise<string> => {
+  return await invoke("save_image", { base64Data, format });
+};
+
+// Saves the image and a new exercise using it together; a failed save leaves no image behind
+export const createExerciseWithImage = async (course: string, week: number, name: string, tags: string[], imageData: string): Promise<CreatedExercise> => {
+  const created = await invoke<CreatedExercise>("create_exercise_with_image", { course, week, name, tags, imageData });
+  triggerUpdate();
+  return created;
+};
+
+// Omitted fields are kept and an empty string clears one; a replaced solution image is deleted
+export const saveSolution = async (id: string, text?: string, imageBase64?: string): Promise<Exercise> => {
+  const exercise = await invoke<Exercise>("save_solution", { id, text, imageBase64 });
+  triggerUpdate();
+  return exercise;
+};
+
+// Attaches an image saved with saveImage or cropExerciseImage after the exercise's others.
+export const addExerciseImage = async (exerciseId: string, path: string, kind: ImageKind): Promise<ExerciseImage[]> => {
+  const images = await invoke<ExerciseImage[]>("add_exercise_image", { exerciseId, path, kind });
+  triggerUpdate();
+  return images;
+};
+
+// Detaches an image; its file is deleted once no exercise uses it.
+export const removeExerciseImage = async (exerciseId: string, path: string): Promise<ExerciseImage[]> => {
+  const images = await invoke<ExerciseImage[]>("remove_exercise_image", { exerciseId, path });
+  triggerUpdate();
+  return images;
+};
+
+// paths must list each of the exercise's images once, in the new order.
+export const reorderExerciseImages = async (exerciseId: string, paths: string[]): Promise<ExerciseImage[]> => {
+  const images = await invoke<ExerciseImage[]>("reorder_exercise_images", { exerciseId, paths });
+  triggerUpdate();
+  return images;
+};
+
+// Crops the bands of saved page images, stacked into one image, and returns its path.
+// pageImages maps each box's page number to its page image.
+export const cropExerciseImage = async (pageImages: Record<number, string>, bboxes: BoundingBox[]): Promise<string> => {
+  return await invoke("crop_exercise_image", { pageImages, bboxes });
 };
 
 export const saveExercises = async (exercises: Exercise[]): Promise<Exercise[]> => {
@@ -33,15 +85,130 @@ export const saveExercises = async (exercises: Exercise[]): Promise<Exercise[]>
 };
 
 export const getExercises = async (): Promise<Exercise[]> => {
-  return await invoke("get_all_exercises");
+  const page = await invoke<ExercisePage>("get_all_exercises");
+  return page.items;
+};
+
+// A single exercise by id, trashed or not; null if it doesn't exist
+export const getExercise = async (id: string): Promise<Exercise | null> => {
+  return await invoke("get_exercise", { id });
+};
+
+// Everything extracted from a PDF or image, by path or file name, in page order
+export const getExercisesBySource = async (file: string): Promise<Exercise[]> => {
+  return await invoke("get_exercises_by_source", { file });
+};
+
+// The parts of an exercise outside the trash, in order
+export const getExerciseChildren = async (id: string): Promise<Exercise[]> => {
+  return await invoke("get_exercise_children", { id });
+};
+
+// Everything changed after timestamp (ms), trashed exercises included
+export const getExercisesModifiedSince = async (timestamp: number): Promise<Exercise[]> => {
+  return await invoke("get_exercises_modified_since", { timestamp });
+};
+
+// Archived courses are left out unless includeArchived is set or the course is asked for by name
+// minDifficulty leaves out unrated exercises
+export const getExercisePage = async (limit: number, offset: number, course?: string, includeArchived = false, week?: number, status?: ExerciseStatus, minDifficulty?: number): Promise<ExercisePage> => {
+  return await invoke("get_all_exercises", { limit, offset, course, includeArchived, week, status, minDifficulty });
+};
+
+export const searchExercises = async (query: string, limit?: number, offset?: number, minDifficulty?: number): Promise<SearchPage> => {
+  return await invoke("search_exercises", { query, limit, offset, minDifficulty });
+};
+
+// Tags in use outside the trash with their usage counts, most used first
+export const fetchAllTags = async (): Promise<TagCount[]> => {
+  return await invoke("get_all_tags");
+};
+
+// Exercises with any of the tags, or every one of them when matchAll is set
+export const filterByTags = async (tags: string[], matchAll = false): Promise<Exercise[]> => {
+  return await invoke("filter_by_tags", { tags, matchAll });
+};
+
+// Returns the number of exercises whose tags changed; newTag is stored lowercased like saved tags
+export const renameTag = async (oldTag: string, newTag: string): Promise<number> => {
+  const touched = await invoke<number>("rename_tag", { old: oldTag, new: newTag });
+  triggerUpdate();
+  return touched;
+};
+
+// Folds synonym tags into one; returns the ids of the exercises that changed
+export const mergeTags = async (sources: string[], target: string): Promise<string[]> => {
+  const changed = await invoke<string[]>("merge_tags", { sources, target });
+  triggerUpdate();
+  return changed;
+};
+
+// Adds and removes tags on many exercises at once; returns each one's new tags by id
+export const bulkEditTags = async (ids: string[], add: string[], remove: string[]): Promise<Record<string, string[]>> => {
+  const updated = await invoke<Record<string, string[]>>("bulk_edit_tags", { ids, add, remove });
+  triggerUpdate();
+  return updated;
+};
+
+export const deleteTag = async (tag: string): Promise<DeleteTagReport> => {
+  const report = await invoke<DeleteTagReport>("delete_tag", { tag });
+  triggerUpdate();
+  return report;
 };
 
-export const deleteExercise = async (id: string): Promise<Exercise[]> => {
-  await invoke("delete_exercise", { id });
+// Exercises with parts need cascade to trash the parts along with them
+export const deleteExercise = async (id: string, cascade = false): Promise<Exercise[]> => {
+  await invoke("delete_exercise", { id, cascade });
   triggerUpdate();
   return await getExercises();
 };
 
+// Trashes several exercises at once; ids that don't exist are reported, not fatal.
+// Exercises with parts that aren't listed too need cascade, as with deleteExercise.
+export const bulkDeleteExercises = async (ids: string[], cascade = false): Promise<BulkDeleteResult[]> => {
+  const results = await invoke<BulkDeleteResult[]>("bulk_delete_exercises", { ids, cascade });
+  triggerUpdate();
+  return results;
+};
+
+// Reverses the most recent delete; null when there is nothing left to undo
+export const undoLastOperation = async (): Promise<UndoneOperation | null> => {
+  const undone = await invoke<UndoneOperation | null>("undo_last_operation");
+  if (undone) triggerUpdate();
+  return undone;
+};
+
+// Exercises are purged automatically once they have been in the trash for 30 days
+export const listTrash = async (): Promise<Exercise[]> => {
+  return await invoke("list_trash");
+};
+
+export const restoreExercise = async (id: string): Promise<Exercise[]> => {
+  await invoke("restore_exercise", { id });
+  triggerUpdate();
+  return await getExercises();
+};
+
+// Permanently removes trashed exercises and their unused images; returns how many were purged
+export const purgeTrash = async (): Promise<number> => {
+  return await invoke("purge_trash");
+};
+
+// Previous versions of an exercise, newest first
+export const getExerciseHistory = async (exerciseId: string): Promise<HistoryEntry[]> => {
+  return await invoke("get_exercise_history", { exerciseId });
+};
+
+export const revertExercise = async (exerciseId: string, historyId: number): Promise<Exercise[]> => {
+  await invoke("revert_exercise", { exerciseId, historyId });
+  triggerUpdate();
+  return await getExercises();
+};
+
+export const setHistoryLimit = async (limit: number): Promise<void> => {
+  await invoke("set_history_limit", { limit });
+};
+
 export const deleteCourse = async (course: string): Promise<Exercise[]> => {
   await invoke("delete_course", { course });
   triggerUpdate();
@@ -54,12 +221,296 @@ export const updateExercise = async (exercise: Exercise): Promise<Exercise[]> =>
   return await getExercises();
 };
 
+// Course names with counts only, cheap enough for the sidebar
+export const listCourses = async (): Promise<CourseSummary[]> => {
+  return await invoke("list_courses");
+};
+
+// Every course outside the trash with its metadata and weeks, archived ones included
+export const getCourses = async (): Promise<Course[]> => {
+  return await invoke("get_courses");
+};
+
+export const updateCourseMetadata = async (course: string, metadata: CourseMetadata): Promise<void> => {
+  await invoke("update_course_metadata", { course, metadata });
+  triggerUpdate();
+};
+
+export const updateWeekMetadata = async (course: string, week: number, metadata: WeekMetadata): Promise<void> => {
+  await invoke("update_week_metadata", { course, week, metadata });
+  triggerUpdate();
+};
+
+// Writes only the given fields, leaving the rest of the stored exercise as it is
+export const updateExerciseFields = async (id: string, changes: ExerciseUpdate): Promise<void> => {
+  await invoke("update_exercise", { id, changes });
+  triggerUpdate();
+};
+
+export const setExerciseStatus = async (id: string, status: ExerciseStatus): Promise<void> => {
+  await invoke("set_exercise_status", { id, status });
+  triggerUpdate();
+};
+
+// Returns whether the exercise is starred now
+export const toggleFavorite = async (id: string): Promise<boolean> => {
+  const starred = await invoke<boolean>("toggle_favorite", { id });
+  triggerUpdate();
+  return starred;
+};
+
+// Starred exercises outside the trash, optionally of one course
+export const getFavorites = async (course?: string): Promise<Exercise[]> => {
+  return await invoke("get_favorites", { course });
+};
+
+// dueAt is in ms
+export const setDueDate = async (id: string, dueAt: number): Promise<void> => {
+  await invoke("set_due_date", { id, dueAt });
+  triggerUpdate();
+};
+
+export const clearDueDate = async (id: string): Promise<void> => {
+  await invoke("clear_due_date", { id });
+  triggerUpdate();
+};
+
+// Exercises not done yet and due before the time (ms), soonest first; pass Date.now() for overdue ones
+export const getDueExercises = async (before: number): Promise<Exercise[]> => {
+  return await invoke("get_due_exercises", { before });
+};
+
+// grade runs from 0 (blackout) to 5 (perfect); returns the next review's schedule
+export const recordReview = async (id: string, grade: number): Promise<ReviewState> => {
+  const state = await invoke<ReviewState>("record_review", { id, grade });
+  triggerUpdate();
+  return state;
+};
+
+// Exercises due for review, most overdue first
+export const getReviewQueue = async (limit?: number): Promise<Exercise[]> => {
+  return await invoke("get_review_queue", { limit });
+};
+
+// null clears the rating
+export const setExerciseDifficulty = async (id: string, difficulty: number | null): Promise<void> => {
+  await invoke("set_exercise_difficulty", { id, difficulty });
+  triggerUpdate();
+};
+
+// Completion per course and week, trash excluded
+// attemptedAt defaults to now
+export const logAttempt = async (exerciseId: string, outcome: AttemptOutcome, minutesSpent?: number, note?: string, attemptedAt?: number): Promise<Attempt> => {
+  const attempt = await invoke<Attempt>("log_attempt", { exerciseId, outcome, minutesSpent, note, attemptedAt });
+  triggerUpdate();
+  return attempt;
+};
+
+// Newest first
+export const getAttempts = async (exerciseId: string): Promise<Attempt[]> => {
+  return await invoke("get_attempts", { exerciseId });
+};
+
+export const deleteAttempt = async (attemptId: number): Promise<void> => {
+  await invoke("delete_attempt", { attemptId });
+  triggerUpdate();
+};
+
+// Copies the file into the vault and attaches it to the exercise.
+export const addAttachment = async (exerciseId: string, sourcePath: string): Promise<Attachment> => {
+  const attachment = await invoke<Attachment>("add_attachment", { exerciseId, sourcePath });
+  triggerUpdate();
+  return attachment;
+};
+
+export const listAttachments = async (exerciseId: string): Promise<Attachment[]> => {
+  return await invoke("list_attachments", { exerciseId });
+};
+
+// Also deletes the attached copy.
+export const removeAttachment = async (attachmentId: string): Promise<void> => {
+  await invoke("remove_attachment", { attachmentId });
+  triggerUpdate();
+};
+
+// Path of the attached copy, checked to still exist, for handing to the system viewer.
+export const openAttachment = async (attachmentId: string): Promise<string> => {
+  return await invoke("open_attachment", { attachmentId });
+};
+
+// Linking the same pair in the same direction again changes the relation.
+export const linkExercises = async (fromId: string, toId: string, relation: LinkRelation): Promise<void> => {
+  await invoke("link_exercises", { fromId, toId, relation });
+  triggerUpdate();
+};
+
+// Removes the links between the two exercises in both directions.
+export const unlinkExercises = async (fromId: string, toId: string): Promise<void> => {
+  await invoke("unlink_exercises", { fromId, toId });
+  triggerUpdate();
+};
+
+// Links from the exercise first; trashed exercises are left out.
+export const getLinkedExercises = async (id: string): Promise<LinkedExercise[]> => {
+  return await invoke("get_linked_exercises", { id });
+};
+
+// Stops any other exercise's timer first; returns the running session
+export const startTimer = async (exerciseId: string): Promise<TimerSession> => {
+  const session = await invoke<TimerSession>("start_timer", { exerciseId });
+  triggerUpdate();
+  return session;
+};
+
+export const stopTimer = async (exerciseId: string): Promise<StoppedTimer> => {
+  const stopped = await invoke<StoppedTimer>("stop_timer", { exerciseId });
+  triggerUpdate();
+  return stopped;
+};
+
+export const getRunningTimer = async (): Promise<TimerSession | null> => {
+  return await invoke("get_running_timer");
+};
+
+export const getStatistics = async (): Promise<Statistics> => {
+  return await invoke("get_statistics");
+};
+
+// Vault overview for the dashboard; counts leave out the trash
+export const getDatabaseStats = async (): Promise<DatabaseStats> => {
+  return await invoke("get_database_stats");
+};
+
+// Files exercises under another course/week, appended in the given order; returns how many moved
+export const moveExercises = async (ids: string[], course: string, week: number): Promise<number> => {
+  const moved = await invoke<number>("move_exercises", { ids, course, week });
+  triggerUpdate();
+  return moved;
+};
+
+// Renumbers a week; rejects with kind "invalidInput" if newNumber exists, unless merge is set.
+// Returns how many exercises moved.
+export const renumberWeek = async (courseName: string, oldNumber: number, newNumber: number, merge = false): Promise<number> => {
+  const moved = await invoke<number>("renumber_week", { courseName, oldNumber, newNumber, merge });
+  triggerUpdate();
+  return moved;
+};
+
+// Moves all of source's exercises into target, combining weeks both have
+export const mergeCourses = async (source: string, target: string): Promise<MergeCoursesReport> => {
+  const report = await invoke<MergeCoursesReport>("merge_courses", { source, target });
+  triggerUpdate();
+  return report;
+};
+
+// Copies an exercise, images included, into its own week or the given one and returns the copy
+export const duplicateExercise = async (exerciseId: string, targetCourse?: string, targetWeek?: number): Promise<Exercise> => {
+  const copy = await invoke<Exercise>("duplicate_exercise", { exerciseId, targetCourse, targetWeek });
+  triggerUpdate();
+  return copy;
+};
+
+// Emitted with { copied, total, failed } while duplicateCourse copies exercises; copied reaches
+// total once the copy is saved, and failed is set if it was rolled back instead
+export const DUPLICATE_COURSE_PROGRESS_EVENT = "duplicate-course-progress";
+
+// Copies a course's live exercises and images under newName; returns how many were copied.
+// resetContent leaves the problem statements out of the copies.
+export const duplicateCourse = async (source: string, newName: string, includeNotes: boolean, resetContent = false): Promise<number> => {
+  const copied = await invoke<number>("duplicate_course", { source, newName, includeNotes, resetContent });
+  triggerUpdate();
+  return copied;
+};
+
+// orderedIds are the week's exercises in their new order; ones left out keep their order after them.
+export const reorderExercises = async (course: string, week: number, orderedIds: string[]): Promise<void> => {
+  await invoke("reorder_exercises", { course, week, orderedIds });
+  triggerUpdate();
+};
+
+// Rejects a name another course already has; mergeCourses combines two courses.
 export const renameCourse = async (oldName: string, newName: string): Promise<Exercise[]> => {
   await invoke("rename_course", { oldName, newName });
   triggerUpdate();
   return await getExercises();
 };
 
+// Writes the whole vault to a JSON file; images are inlined when includeImages is set
+export const exportVaultJson = async (destination: string, includeImages: boolean): Promise<void> => {
+  await invoke("export_vault_json", { destination, includeImages });
+};
+
+// One course as a self-contained JSON string with images inlined, for sharing
+export const exportCourse = async (courseName: string): Promise<string> => {
+  return await invoke("export_course", { courseName });
+};
+
+// One course as markdown; images are images/<file> links, or inlined when selfContained is set
+export const exportCourseMarkdown = async (courseName: string, selfContained = false): Promise<string> => {
+  return await invoke("export_course_markdown", { courseName, selfContained });
+};
+
+// Zip of notes.txt plus media/ for Anki: copy media/ into collection.media, then File > Import notes.txt
+export const exportToAnki = async (courseName: string, destination: string): Promise<AnkiExportReport> => {
+  return await invoke("export_to_anki", { courseName, destination });
+};
+
+export const importVaultJson = async (source: string, mode: ImportMode): Promise<ImportSummary> => {
+  const summary = await invoke<ImportSummary>("import_vault_json", { source, mode });
+  triggerUpdate();
+  return summary;
+};
+
+// Zips the database and images into one archive at destination
+export const createBackup = async (destination: string): Promise<void> => {
+  await invoke("create_backup", { destination });
+};
+
+// Replaces all data with a createBackup archive; the previous data is kept in restore.bak
+export const restoreBackup = async (source: string): Promise<void> => {
+  await invoke("restore_backup", { source });
+  triggerUpdate();
+};
+
+// Lists image files no exercise uses; deletes them only when confirm is true
+export const cleanupOrphanedImages = async (confirm = false): Promise<OrphanReport> => {
+  return await invoke("cleanup_orphaned_images", { confirm });
+};
+
+// Read-only check, e.g. at startup, for images of live exercises that are missing or don't decode.
+export const verifyImages = async (): Promise<BrokenImageRef[]> => {
+  return await invoke("verify_images");
+};
+
+// Compacts the database file; rejects with kind "busy" if another operation holds it
+export const optimizeDatabase = async (): Promise<OptimizeReport> => {
+  return await invoke("optimize_database");
+};
+
+// Directory holding the database and images
+export const getDataDirectory = async (): Promise<string> => {
+  return await invoke("get_data_directory");
+};
+
+// Moves the database and images to newPath and returns the resolved directory
+export const moveDataDirectory = async (newPath: string): Promise<string> => {
+  const dir = await invoke<string>("move_data_directory", { newPath });
+  triggerUpdate();
+  return dir;
+};
+
+// Deletes orphaned images without a dry run, then optimizes the database
+export const cleanupStorage = async (): Promise<StorageCleanupReport> => {
+  return await invoke("cleanup_storage");
+};
+
+// Imports an exportCourse document, merging into an existing course of the same name unless renameTo is given
+export const importCourse = async (json: string, renameTo?: string): Promise<ImportSummary> => {
+  const summary = await invoke<ImportSummary>("import_course", { json, renameTo });
+  triggerUpdate();
+  return summary;
+};
+
 // Helper to get course names
 export const getCourseNames = (exercises: Exercise[]): string[] => {
   const courses = new Set(exercises.map(ex => ex.course));
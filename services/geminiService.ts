@@ -1,14 +1,30 @@
-import { Exercise } from "../types";
+import { CostEstimate, Exercise, GeminiModel } from "../types";
 import { invoke } from '@tauri-apps/api/tauri';
 
-export const analyzePageImage = async (base64Image: string | null, imagePath: string | null, apiKey: string): Promise<Partial<Exercise>[]> => {
-  if (!apiKey) throw new Error("API Key is missing");
+// Emitted with { current, total } pages while pdf_to_images renders a PDF
+export const PDF_PROGRESS_EVENT = "pdf-progress";
+
+// Emitted with { current, total, requestId? } when analyzePageImage sends its request
+// (current 0) and when the results are in (current = total, the page count).
+// analyzePages also reports each chunk as it finishes, current counting its pages.
+export const ANALYSIS_PROGRESS_EVENT = "analysis-progress";
+
+// pageCount is the number of PDF pages stitched into the image, used to attribute exercises to pages.
+// requestId lets cancelAnalysis abort the call while it is running; it must not be in use by another one.
+// model defaults to the one chosen in settings.
+// useLocalOcr reads the text with Tesseract instead of Gemini; no key is needed and results have no tags.
+export const analyzePageImage = async (base64Image: string | null, imagePath: string | null, apiKey: string, pageCount?: number, requestId?: string, model?: GeminiModel, useLocalOcr = false): Promise<Partial<Exercise>[]> => {
+  if (!apiKey && !useLocalOcr) throw new Error("API Key is missing");
 
   try {
     const results = await invoke<Partial<Exercise>[]>("analyze_page_image", {
       base64Image,
       imagePath,
-      apiKey
+      apiKey,
+      pageCount,
+      requestId,
+      model,
+      useLocalOcr
     });
 
     return results;
@@ -17,3 +33,28 @@ export const analyzePageImage = async (base64Image: string | null, imagePath: st
     throw error;
   }
 };
+
+// Analyzes rendered PDF pages (as from pdf_to_images) in chunks of chunkSize pages, a few
+// requests at a time, for documents too long for one request. chunkSize defaults to the
+// analysis_chunk_pages setting. Results are in page order, sourcePage counted from the first path.
+export const analyzePages = async (imagePaths: string[], apiKey: string, chunkSize?: number, requestId?: string, model?: GeminiModel): Promise<Partial<Exercise>[]> => {
+  if (!apiKey) throw new Error("API Key is missing");
+
+  try {
+    return await invoke<Partial<Exercise>[]>("analyze_pages", { imagePaths, apiKey, chunkSize, requestId, model });
+  } catch (error) {
+    console.error("Gemini Analysis Failed", error);
+    throw error;
+  }
+};
+
+// Returns false if the request had already finished
+export const cancelAnalysis = async (requestId: string): Promise<boolean> => {
+  return await invoke("cancel_analysis", { requestId });
+};
+
+// Rough cost of analyzing every page of a PDF, computed locally without calling Gemini.
+// dpi defaults to the one pdf_to_images renders at.
+export const estimateAnalysisCost = async (path: string, dpi?: number): Promise<CostEstimate> => {
+  return await invoke("estimate_analysis_cost", { path, dpi });
+};
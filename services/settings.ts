@@ -0,0 +1,57 @@
+import { invoke } from '@tauri-apps/api/tauri';
+import { ApiKeyStatus, AppSettings, GeminiModel, StoredSettings } from "../types";
+import { SETTINGS_KEY } from "../constants";
+
+export const saveApiKey = async (apiKey: string): Promise<void> => {
+  await invoke("save_api_key", { apiKey });
+};
+
+// Checks the key with Gemini without saving it, for feedback in the settings screen
+export const validateApiKey = async (apiKey: string): Promise<ApiKeyStatus> => {
+  return await invoke("validate_api_key", { apiKey });
+};
+
+// The proxy Gemini requests go through; null means the HTTPS_PROXY environment variable decides
+export const getProxy = async (): Promise<string | null> => {
+  return await invoke("get_proxy");
+};
+
+// An http://, https://, socks5:// or socks5h:// URL, optionally with user:password@; null or "" clears it
+export const setProxy = async (proxy: string | null): Promise<void> => {
+  await invoke("set_proxy", { proxy });
+};
+
+// Loads the API key from the OS keyring, moving any key still kept in localStorage over first
+export const loadApiKey = async (): Promise<string | null> => {
+  const stored = await invoke<string | null>("get_api_key");
+
+  const legacySettings = localStorage.getItem(SETTINGS_KEY);
+  if (legacySettings) {
+    const parsed: AppSettings = JSON.parse(legacySettings);
+    localStorage.removeItem(SETTINGS_KEY);
+    if (!stored && parsed.apiKey) {
+      await saveApiKey(parsed.apiKey);
+      return parsed.apiKey;
+    }
+  }
+
+  return stored;
+};
+
+export const getDefaultModel = async (): Promise<GeminiModel> => {
+  return await invoke("get_default_model");
+};
+
+export const setDefaultModel = async (model: GeminiModel): Promise<void> => {
+  await invoke("set_default_model", { model });
+};
+
+// Returns the default for settings that were never set
+export const getSetting = async <K extends keyof StoredSettings>(key: K): Promise<StoredSettings[K]> => {
+  return await invoke("get_setting", { key });
+};
+
+// Rejects values out of range
+export const setSetting = async <K extends keyof StoredSettings>(key: K, value: StoredSettings[K]): Promise<void> => {
+  await invoke("set_setting", { key, value });
+};
@@ -0,0 +1,40 @@
+import { invoke } from '@tauri-apps/api/tauri';
+import { VaultProfile, VaultStatus } from "../types";
+
+export const getVaultStatus = async (): Promise<VaultStatus> => {
+  return await invoke("get_vault_status");
+};
+
+// Encrypts a plaintext vault; it must be unlocked with this passphrase on every launch
+export const setVaultPassphrase = async (passphrase: string): Promise<void> => {
+  await invoke("set_vault_passphrase", { passphrase });
+};
+
+export const unlockVault = async (passphrase: string): Promise<void> => {
+  await invoke("unlock_vault", { passphrase });
+};
+
+export const changePassphrase = async (currentPassphrase: string, newPassphrase: string): Promise<void> => {
+  await invoke("change_passphrase", { currentPassphrase, newPassphrase });
+};
+
+// Emitted by the backend with the new vault's name after switchVault
+export const VAULT_SWITCHED_EVENT = "vault-switched";
+
+export const listVaults = async (): Promise<VaultProfile[]> => {
+  return await invoke("list_vaults");
+};
+
+// Registers a vault without opening it; path defaults to a directory under the app data dir
+export const createVault = async (name: string, path?: string): Promise<VaultProfile> => {
+  return await invoke("create_vault", { name, path });
+};
+
+export const switchVault = async (name: string): Promise<void> => {
+  await invoke("switch_vault", { name });
+};
+
+// Permanently deletes a vault's database and images; the open vault can't be deleted
+export const deleteVault = async (name: string, confirm: boolean): Promise<void> => {
+  await invoke("delete_vault", { name, confirm });
+};
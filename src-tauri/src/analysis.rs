@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{command, State};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::error::AppError;
+
+/// In-flight analysis requests, keyed by the id the frontend passed in.
+#[derive(Default)]
+pub struct AnalysisRegistry(Mutex<HashMap<String, CancellationToken>>);
+
+/// Keeps a request registered for as long as it is alive and removes it on drop,
+/// however the command returns.
+pub struct Registration<'a> {
+    registry: &'a AnalysisRegistry,
+    id: String,
+    pub token: CancellationToken,
+}
+
+impl AnalysisRegistry {
+    /// Fails if a request with the same id is still running, since the two would
+    /// share one entry and whichever finished first would unregister the other.
+    pub fn register(&self, id: String) -> Result<Registration<'_>, AppError> {
+        let token = CancellationToken::new();
+        let mut requests = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if requests.contains_key(&id) {
+            return Err(AppError::InvalidInput(format!("Request {} is already running", id)));
+        }
+        requests.insert(id.clone(), token.clone());
+        Ok(Registration {
+            registry: self,
+            id,
+            token,
+        })
+    }
+
+    /// Returns false if no request with that id is running.
+    fn cancel(&self, id: &str) -> bool {
+        match self.0.lock().unwrap_or_else(|e| e.into_inner()).get(id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.registry
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
+    }
+}
+
+/// Aborts the analysis started with `request_id`. Returns false if it already finished.
+#[command]
+pub fn cancel_analysis(registry: State<'_, AnalysisRegistry>, request_id: String) -> bool {
+    let cancelled = registry.cancel(&request_id);
+    if cancelled {
+        info!("Cancelled request {}", request_id);
+    }
+    cancelled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_ids_are_rejected_while_the_first_runs() {
+        let registry = AnalysisRegistry::default();
+        let first = registry.register("a".to_string()).unwrap();
+        assert!(matches!(registry.register("a".to_string()), Err(AppError::InvalidInput(_))));
+
+        // The rejected attempt must not have unregistered the running request
+        assert!(registry.cancel("a"));
+        assert!(first.token.is_cancelled());
+
+        drop(first);
+        assert!(!registry.cancel("a"));
+        assert!(registry.register("a".to_string()).is_ok());
+    }
+}
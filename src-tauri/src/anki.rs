@@ -0,0 +1,157 @@
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use tauri::{command, AppHandle, Runtime, State};
+use tracing::info;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::backup::write_atomically;
+use crate::db::{get_connection, get_images_dir, DbPool};
+use crate::error::AppError;
+use crate::models::{Exercise, ImageKind, EXERCISE_COLUMNS};
+use crate::sort::SortBy;
+
+const NOTES_ENTRY: &str = "notes.txt";
+const MEDIA_DIR: &str = "media";
+
+#[derive(Debug, Serialize)]
+pub struct AnkiExportReport {
+    pub cards: usize,
+    pub images: usize,
+}
+
+/// Escapes text for an HTML field of Anki's text importer. Quotes are escaped too
+/// so the importer never sees a quoted field, and tabs/newlines can't split one.
+fn html_field(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\t', " ")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+/// Anki tags are separated by spaces, so spaces inside a tag become underscores.
+fn anki_tag(tag: &str) -> String {
+    tag.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// Adds an exercise image to the archive's `media/` once, and returns the `<img>`
+/// tag showing it, or `None` when the exercise has no such image on disk.
+fn add_media(
+    zip: &mut ZipWriter<File>,
+    media: &mut HashSet<String>,
+    images_dir: &Path,
+    image_uri: Option<&str>,
+) -> Result<Option<String>, AppError> {
+    // Stored paths may spell the images dir differently; the file name is what counts
+    let image = image_uri
+        .and_then(|path| Path::new(path).file_name())
+        .map(|name| images_dir.join(name))
+        .filter(|path| path.is_file());
+    let Some(image) = image else {
+        return Ok(None);
+    };
+
+    let name = image.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    if media.insert(name.clone()) {
+        // Images are already compressed, deflating them again only costs time
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        zip.start_file(format!("{}/{}", MEDIA_DIR, name), stored)?;
+        io::copy(&mut File::open(&image)?, zip)?;
+    }
+    Ok(Some(format!("<img src=\"{}\">", html_field(&name))))
+}
+
+/// Writes a course as a zip holding `notes.txt`, a tab-separated file for Anki's
+/// File > Import, and `media/` with the exercises' images. The front of each card
+/// is the exercise name and cropped images, the back its notes, content and
+/// solution; tags carry over. Trashed exercises are left out.
+///
+/// To import: unzip the archive, copy the files in `media/` into the profile's
+/// `collection.media` folder (Anki: Tools > Check Media > View Files shows it),
+/// then File > Import `notes.txt`. The file header sets the deck, separator and
+/// tag column, so the import dialog needs no changes.
+#[command]
+pub fn export_to_anki<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    course_name: String,
+    destination: String,
+) -> Result<AnkiExportReport, AppError> {
+    let conn = get_connection(&pool)?;
+    let images_dir = get_images_dir(&app)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM exercises WHERE course = ?1 AND deleted_at IS NULL ORDER BY {}",
+        EXERCISE_COLUMNS,
+        SortBy::CourseThenWeek.order_by()
+    ))?;
+    let exercises = stmt
+        .query_map(params![course_name], Exercise::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    if exercises.is_empty() {
+        return Err(AppError::NotFound(format!("course {}", course_name)));
+    }
+
+    let mut report = AnkiExportReport { cards: 0, images: 0 };
+    write_atomically(Path::new(&destination), |temp| {
+        let mut zip = ZipWriter::new(File::create(temp)?);
+        let mut notes = format!(
+            "#separator:tab\n#html:true\n#deck:{}\n#tags column:3\n",
+            course_name.replace(['\n', '\r'], " ")
+        );
+        let mut media: HashSet<String> = HashSet::new();
+
+        for exercise in &exercises {
+            let mut front = html_field(&exercise.name);
+            for image in exercise.images.iter().filter(|image| image.kind == ImageKind::Crop) {
+                if let Some(img) = add_media(&mut zip, &mut media, &images_dir, Some(&image.path))? {
+                    front.push_str(&format!("<br>{}", img));
+                }
+            }
+
+            let mut back: Vec<String> = [
+                exercise.notes.as_deref(),
+                exercise.content.as_deref(),
+                exercise.solution_text.as_deref(),
+            ]
+            .into_iter()
+            .flatten()
+            .filter(|text| !text.trim().is_empty())
+            .map(html_field)
+            .collect();
+            for image in exercise.images.iter().filter(|image| image.kind == ImageKind::Solution) {
+                if let Some(img) = add_media(&mut zip, &mut media, &images_dir, Some(&image.path))? {
+                    back.push(img);
+                }
+            }
+            let back = back.join("<hr>");
+            let mut tags: Vec<String> = exercise.tags.iter().map(|tag| anki_tag(tag)).collect();
+            tags.push(format!("week_{}", exercise.week));
+
+            notes.push_str(&format!("{}\t{}\t{}\n", front, back, tags.join(" ")));
+        }
+
+        zip.start_file(
+            NOTES_ENTRY,
+            FileOptions::default().compression_method(CompressionMethod::Deflated),
+        )?;
+        zip.write_all(notes.as_bytes())?;
+        zip.finish()?;
+
+        report = AnkiExportReport {
+            cards: exercises.len(),
+            images: media.len(),
+        };
+        Ok(())
+    })?;
+
+    info!("Exported {} cards from {} to {}", report.cards, course_name, destination);
+    Ok(report)
+}
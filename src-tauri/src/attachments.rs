@@ -0,0 +1,177 @@
+use rusqlite::{params, OptionalExtension, Row};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use tauri::{command, AppHandle, Runtime, State};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::db::{get_attachments_dir, get_connection, DbPool};
+use crate::error::AppError;
+
+/// A file attached to an exercise, kept as a copy in the `attachments` dir.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub id: String,
+    pub exercise_id: String,
+    /// Name of the file that was attached, for display.
+    pub file_name: String,
+    /// Where the copy lives.
+    pub path: String,
+    pub mime_type: String,
+    pub size_bytes: i64,
+    /// Unix milliseconds.
+    pub created_at: i64,
+}
+
+const ATTACHMENT_COLUMNS: &str = "id, exercise_id, file_name, path, mime_type, size_bytes, created_at";
+
+impl Attachment {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Attachment {
+            id: row.get(0)?,
+            exercise_id: row.get(1)?,
+            file_name: row.get(2)?,
+            path: row.get(3)?,
+            mime_type: row.get(4)?,
+            size_bytes: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    }
+}
+
+/// Guessed from the extension; what isn't listed is stored as plain bytes.
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("pdf") => "application/pdf",
+        Some("ipynb") => "application/x-ipynb+json",
+        Some("json") => "application/json",
+        Some("zip") => "application/zip",
+        Some("txt") => "text/plain",
+        Some("md") => "text/markdown",
+        Some("csv") => "text/csv",
+        Some("tex") => "application/x-tex",
+        Some("py") => "text/x-python",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Copies the file at `source_path` into the attachments dir under a new name
+/// and attaches it to an exercise outside the trash.
+#[command]
+pub fn add_attachment<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    exercise_id: String,
+    source_path: String,
+) -> Result<Attachment, AppError> {
+    let source = Path::new(&source_path);
+    if !source.is_file() {
+        return Err(AppError::InvalidInput(format!("No file at {}", source_path)));
+    }
+    let file_name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| AppError::InvalidInput(format!("No file name in {}", source_path)))?;
+
+    let conn = get_connection(&pool)?;
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM exercises WHERE id = ?1 AND deleted_at IS NULL)",
+        params![exercise_id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::NotFound(format!("exercise {}", exercise_id)));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let copy = match source.extension() {
+        Some(extension) => get_attachments_dir(&app)?.join(format!("{}.{}", id, extension.to_string_lossy())),
+        None => get_attachments_dir(&app)?.join(&id),
+    };
+    let size_bytes = fs::copy(source, &copy)? as i64;
+
+    let attachment = Attachment {
+        id,
+        exercise_id,
+        file_name,
+        path: copy.to_string_lossy().into_owned(),
+        mime_type: mime_type(source).to_string(),
+        size_bytes,
+        created_at: chrono::Utc::now().timestamp_millis(),
+    };
+    let inserted = conn.execute(
+        &format!("INSERT INTO attachments ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)", ATTACHMENT_COLUMNS),
+        params![
+            attachment.id,
+            attachment.exercise_id,
+            attachment.file_name,
+            attachment.path,
+            attachment.mime_type,
+            attachment.size_bytes,
+            attachment.created_at
+        ],
+    );
+    if let Err(e) = inserted {
+        let _ = fs::remove_file(&copy);
+        return Err(e.into());
+    }
+
+    info!("Attached {} to exercise {}", attachment.file_name, attachment.exercise_id);
+    Ok(attachment)
+}
+
+/// An exercise's attachments, oldest first.
+#[command]
+pub fn list_attachments(pool: State<'_, DbPool>, exercise_id: String) -> Result<Vec<Attachment>, AppError> {
+    let conn = get_connection(&pool)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM attachments WHERE exercise_id = ?1 ORDER BY created_at, id",
+        ATTACHMENT_COLUMNS
+    ))?;
+    let attachments = stmt
+        .query_map(params![exercise_id], Attachment::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(attachments)
+}
+
+/// Detaches an attachment and deletes its copy.
+#[command]
+pub fn remove_attachment(pool: State<'_, DbPool>, attachment_id: String) -> Result<(), AppError> {
+    let conn = get_connection(&pool)?;
+    let path: String = conn
+        .query_row(
+            "DELETE FROM attachments WHERE id = ?1 RETURNING path",
+            params![attachment_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("attachment {}", attachment_id)))?;
+
+    let _ = fs::remove_file(path);
+    Ok(())
+}
+
+/// The path of an attachment's copy, for opening it with the system's default app.
+#[command]
+pub fn open_attachment(pool: State<'_, DbPool>, attachment_id: String) -> Result<String, AppError> {
+    let conn = get_connection(&pool)?;
+    let path: String = conn
+        .query_row(
+            "SELECT path FROM attachments WHERE id = ?1",
+            params![attachment_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("attachment {}", attachment_id)))?;
+
+    if !Path::new(&path).is_file() {
+        return Err(AppError::NotFound(format!("attachment file {}", path)));
+    }
+    Ok(path)
+}
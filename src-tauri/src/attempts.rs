@@ -0,0 +1,133 @@
+use rusqlite::{params, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+use crate::db::{get_connection, DbPool};
+use crate::error::AppError;
+
+/// How an attempt at an exercise went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttemptOutcome {
+    Solved,
+    Partial,
+    Failed,
+}
+
+impl AttemptOutcome {
+    /// The value stored in the `outcome` column.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AttemptOutcome::Solved => "solved",
+            AttemptOutcome::Partial => "partial",
+            AttemptOutcome::Failed => "failed",
+        }
+    }
+
+    fn from_column(value: &str) -> Self {
+        match value {
+            "solved" => AttemptOutcome::Solved,
+            "partial" => AttemptOutcome::Partial,
+            _ => AttemptOutcome::Failed,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attempt {
+    pub id: i64,
+    pub exercise_id: String,
+    /// Unix milliseconds.
+    pub attempted_at: i64,
+    pub outcome: AttemptOutcome,
+    pub minutes_spent: Option<i64>,
+    pub note: Option<String>,
+}
+
+const ATTEMPT_COLUMNS: &str = "id, exercise_id, attempted_at, outcome, minutes_spent, note";
+
+impl Attempt {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Attempt {
+            id: row.get(0)?,
+            exercise_id: row.get(1)?,
+            attempted_at: row.get(2)?,
+            outcome: AttemptOutcome::from_column(&row.get::<_, String>(3)?),
+            minutes_spent: row.get(4)?,
+            note: row.get(5)?,
+        })
+    }
+}
+
+/// Records an attempt at an exercise outside the trash, at `attempted_at` or
+/// else now. A blank note is stored as unset.
+#[command]
+pub fn log_attempt(
+    pool: State<'_, DbPool>,
+    exercise_id: String,
+    outcome: AttemptOutcome,
+    minutes_spent: Option<i64>,
+    note: Option<String>,
+    attempted_at: Option<i64>,
+) -> Result<Attempt, AppError> {
+    if minutes_spent.is_some_and(|minutes| minutes < 0) {
+        return Err(AppError::InvalidInput("Minutes spent can't be negative".to_string()));
+    }
+    let note = note.map(|note| note.trim().to_string()).filter(|note| !note.is_empty());
+
+    let conn = get_connection(&pool)?;
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM exercises WHERE id = ?1 AND deleted_at IS NULL)",
+        params![exercise_id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::NotFound(format!("exercise {}", exercise_id)));
+    }
+
+    let attempt = conn.query_row(
+        &format!(
+            "INSERT INTO attempts (exercise_id, attempted_at, outcome, minutes_spent, note)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             RETURNING {}",
+            ATTEMPT_COLUMNS
+        ),
+        params![
+            exercise_id,
+            attempted_at.unwrap_or_else(|| chrono::Utc::now().timestamp_millis()),
+            outcome.as_str(),
+            minutes_spent,
+            note
+        ],
+        Attempt::from_row,
+    )?;
+
+    Ok(attempt)
+}
+
+/// Attempts at an exercise, newest first.
+#[command]
+pub fn get_attempts(pool: State<'_, DbPool>, exercise_id: String) -> Result<Vec<Attempt>, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM attempts WHERE exercise_id = ?1 ORDER BY attempted_at DESC, id DESC",
+        ATTEMPT_COLUMNS
+    ))?;
+    let attempts = stmt
+        .query_map(params![exercise_id], Attempt::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(attempts)
+}
+
+#[command]
+pub fn delete_attempt(pool: State<'_, DbPool>, attempt_id: i64) -> Result<(), AppError> {
+    let conn = get_connection(&pool)?;
+    let deleted = conn.execute("DELETE FROM attempts WHERE id = ?1", params![attempt_id])?;
+    if deleted == 0 {
+        return Err(AppError::NotFound(format!("attempt {}", attempt_id)));
+    }
+    Ok(())
+}
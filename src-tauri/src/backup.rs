@@ -0,0 +1,1185 @@
+use base64::{engine::general_purpose, Engine as _};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use tauri::{command, AppHandle, Runtime, State};
+use tracing::{info, warn};
+use uuid::Uuid;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::db::{get_attachments_dir, get_connection, get_data_dir, get_images_dir, replace_database_file, DbPool};
+use crate::migrations;
+use crate::error::AppError;
+use crate::gemini::HttpClient;
+use crate::images::thumbnail_path;
+use crate::links::{insert_link, links_between, ExerciseLink};
+use crate::models::{Exercise, ImageKind, EXERCISE_COLUMNS};
+use crate::sort::SortBy;
+
+/// Bump whenever the export layout changes so importers can reject what they don't understand.
+pub const VAULT_EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VaultExport {
+    pub version: u32,
+    #[serde(rename = "exportedAt")]
+    pub exported_at: i64,
+    pub courses: Vec<CourseExport>,
+    /// Links between exported exercises, by their ids in this document.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<ExerciseLink>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CourseExport {
+    pub name: String,
+    pub weeks: Vec<WeekExport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeekExport {
+    pub week: i64,
+    pub exercises: Vec<ExerciseExport>,
+}
+
+/// An exercise with image paths made relative to the data dir
+/// (`images/<file>`) and, optionally, the image bytes inlined as data URLs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExerciseExport {
+    #[serde(flatten)]
+    pub exercise: Exercise,
+    #[serde(rename = "imageData", default, skip_serializing_if = "Option::is_none")]
+    pub image_data: Option<String>,
+    #[serde(rename = "pageImageData", default, skip_serializing_if = "Option::is_none")]
+    pub page_image_data: Option<String>,
+    #[serde(rename = "solutionImageData", default, skip_serializing_if = "Option::is_none")]
+    pub solution_image_data: Option<String>,
+    /// The inlined bytes of `images`, in the same order.
+    #[serde(rename = "imagesData", default, skip_serializing_if = "Vec::is_empty")]
+    pub images_data: Vec<Option<String>>,
+}
+
+fn relative_image_path(images_dir: &Path, path: &str) -> String {
+    match Path::new(path).strip_prefix(images_dir) {
+        Ok(file) => format!("images/{}", file.to_string_lossy()),
+        Err(_) => path.to_string(),
+    }
+}
+
+pub fn image_mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        _ => "image/png",
+    }
+}
+
+/// Reads an image into a data URL, or `None` if the file is gone.
+pub fn image_data_url(path: &str) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    Some(format!(
+        "data:{};base64,{}",
+        image_mime_type(Path::new(path)),
+        general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+/// Runs `write` against a sibling temp file and only moves it over `destination`
+/// once it succeeded, so a crash never leaves a truncated file behind.
+pub fn write_atomically(
+    destination: &Path,
+    write: impl FnOnce(&Path) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    let mut temp_name = destination.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = PathBuf::from(temp_name);
+
+    let result = write(&temp_path).and_then(|_| Ok(fs::rename(&temp_path, destination)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn to_export(images_dir: &Path, mut exercise: Exercise, include_images: bool) -> ExerciseExport {
+    let (mut image_data, mut page_image_data, mut solution_image_data) = (None, None, None);
+    let mut images_data = Vec::new();
+    if include_images {
+        image_data = exercise.image_uri.as_deref().and_then(image_data_url);
+        page_image_data = exercise.page_image_uri.as_deref().and_then(image_data_url);
+        solution_image_data = exercise.solution_image_uri.as_deref().and_then(image_data_url);
+        images_data = exercise.images.iter().map(|image| image_data_url(&image.path)).collect();
+    }
+    for image in &mut exercise.images {
+        image.path = relative_image_path(images_dir, &image.path);
+    }
+
+    exercise.image_uri = exercise.image_uri.map(|p| relative_image_path(images_dir, &p));
+    exercise.page_image_uri = exercise.page_image_uri.map(|p| relative_image_path(images_dir, &p));
+    exercise.solution_image_uri = exercise.solution_image_uri.map(|p| relative_image_path(images_dir, &p));
+
+    ExerciseExport {
+        exercise,
+        image_data,
+        page_image_data,
+        solution_image_data,
+        images_data,
+    }
+}
+
+/// Groups exercises (already sorted by course, then week) into the nested export layout.
+fn group_by_course(exercises: Vec<ExerciseExport>) -> Vec<CourseExport> {
+    let mut courses: Vec<CourseExport> = Vec::new();
+
+    for exported in exercises {
+        let course_name = exported.exercise.course.clone();
+        let week_number = exported.exercise.week;
+
+        if courses.last().map(|c| &c.name) != Some(&course_name) {
+            courses.push(CourseExport {
+                name: course_name,
+                weeks: Vec::new(),
+            });
+        }
+        let course = courses.last_mut().expect("course was just pushed");
+
+        if course.weeks.last().map(|w| w.week) != Some(week_number) {
+            course.weeks.push(WeekExport {
+                week: week_number,
+                exercises: Vec::new(),
+            });
+        }
+        course.weeks.last_mut().expect("week was just pushed").exercises.push(exported);
+    }
+
+    courses
+}
+
+/// Builds the export document for the whole vault, trash included, or for the
+/// live exercises of a single course.
+fn build_export(
+    conn: &Connection,
+    images_dir: &Path,
+    course: Option<&str>,
+    include_images: bool,
+) -> Result<VaultExport, AppError> {
+    let where_clause = if course.is_some() {
+        "WHERE course = ?1 AND deleted_at IS NULL"
+    } else {
+        ""
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM exercises {} ORDER BY {}",
+        EXERCISE_COLUMNS,
+        where_clause,
+        SortBy::CourseThenWeek.order_by()
+    ))?;
+    let exercises = stmt
+        .query_map(params_from_iter(course), Exercise::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let ids: HashSet<String> = exercises.iter().map(|exercise| exercise.id.clone()).collect();
+    let exported: Vec<ExerciseExport> = exercises
+        .into_iter()
+        .map(|exercise| to_export(images_dir, exercise, include_images))
+        .collect();
+
+    Ok(VaultExport {
+        version: VAULT_EXPORT_VERSION,
+        exported_at: chrono::Utc::now().timestamp_millis(),
+        courses: group_by_course(exported),
+        links: links_between(conn, &ids)?,
+    })
+}
+
+/// Writes every course, week and exercise to `destination` as a single JSON document.
+#[command]
+pub fn export_vault_json<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    destination: String,
+    include_images: bool,
+) -> Result<(), AppError> {
+    let conn = get_connection(&pool)?;
+    let images_dir = get_images_dir(&app)?;
+
+    let export = build_export(&conn, &images_dir, None, include_images)?;
+    let json = serde_json::to_vec_pretty(&export)?;
+    write_atomically(Path::new(&destination), |temp| Ok(fs::write(temp, &json)?))?;
+
+    info!("Exported vault to {}", destination);
+    Ok(())
+}
+
+/// Returns one course as a self-contained JSON document, in the same format as
+/// `export_vault_json` with every image inlined, for sharing.
+#[command]
+pub fn export_course<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    course_name: String,
+) -> Result<String, AppError> {
+    let conn = get_connection(&pool)?;
+    let images_dir = get_images_dir(&app)?;
+
+    let export = build_export(&conn, &images_dir, Some(&course_name), true)?;
+    if export.courses.is_empty() {
+        return Err(AppError::NotFound(format!("course {}", course_name)));
+    }
+
+    Ok(serde_json::to_string(&export)?)
+}
+
+/// Backslash-escapes characters that would otherwise turn text into markdown.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_{}[]<>()#+-!|~".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Obsidian-style `#tag`: tags can't contain spaces, so those become dashes.
+fn markdown_tag(tag: &str) -> String {
+    format!("#{}", tag.split_whitespace().collect::<Vec<_>>().join("-"))
+}
+
+/// Renders one course as markdown for note-taking apps: a section per week, a
+/// heading per exercise with its tags as `#tag`s, the cropped image, any notes and
+/// the solution, if there is one.
+/// Images are referenced as `images/<file>` relative to the data dir, or inlined
+/// as data URLs with `self_contained`.
+#[command]
+pub fn export_course_markdown<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    course_name: String,
+    self_contained: Option<bool>,
+) -> Result<String, AppError> {
+    let self_contained = self_contained.unwrap_or(false);
+    let conn = get_connection(&pool)?;
+    let images_dir = get_images_dir(&app)?;
+
+    let export = build_export(&conn, &images_dir, Some(&course_name), self_contained)?;
+    let course = export
+        .courses
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::NotFound(format!("course {}", course_name)))?;
+
+    let mut markdown = format!("# {}\n", escape_markdown(&course.name));
+    for week in course.weeks {
+        let title: Option<String> = conn
+            .query_row(
+                "SELECT title FROM week_metadata WHERE course = ?1 AND week = ?2",
+                params![course_name, week.week],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        markdown.push_str(&format!("\n## Week {}", week.week));
+        if let Some(title) = title {
+            markdown.push_str(&format!(" — {}", escape_markdown(&title)));
+        }
+        markdown.push('\n');
+
+        for exported in week.exercises {
+            let exercise = exported.exercise;
+            markdown.push_str(&format!("\n### {}\n", escape_markdown(&exercise.name)));
+            if !exercise.tags.is_empty() {
+                let tags: Vec<String> = exercise.tags.iter().map(|tag| markdown_tag(tag)).collect();
+                markdown.push_str(&format!("\n{}\n", tags.join(" ")));
+            }
+            let images: Vec<(ImageKind, String)> = exercise
+                .images
+                .iter()
+                .enumerate()
+                .filter_map(|(index, image)| {
+                    let source = if self_contained {
+                        exported.images_data.get(index).cloned().flatten()
+                    } else {
+                        Some(image.path.clone())
+                    };
+                    source.map(|source| (image.kind, source))
+                })
+                .collect();
+            for (_, image) in images.iter().filter(|(kind, _)| *kind == ImageKind::Crop) {
+                // Angle brackets let the destination contain spaces
+                markdown.push_str(&format!("\n![{}](<{}>)\n", escape_markdown(&exercise.name), image));
+            }
+            if let Some(notes) = exercise.notes.filter(|notes| !notes.trim().is_empty()) {
+                markdown.push_str(&format!("\n{}\n", notes.trim()));
+            }
+
+            let solution_text = exercise.solution_text.filter(|text| !text.trim().is_empty());
+            let solution_images: Vec<&String> = images
+                .iter()
+                .filter(|(kind, _)| *kind == ImageKind::Solution)
+                .map(|(_, image)| image)
+                .collect();
+            if solution_text.is_some() || !solution_images.is_empty() {
+                markdown.push_str("\n#### Solution\n");
+            }
+            if let Some(text) = solution_text {
+                markdown.push_str(&format!("\n{}\n", text.trim()));
+            }
+            for image in solution_images {
+                markdown.push_str(&format!(
+                    "\n![Solution to {}](<{}>)\n",
+                    escape_markdown(&exercise.name),
+                    image
+                ));
+            }
+        }
+    }
+
+    Ok(markdown)
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Add to the current vault, resolving collisions per exercise.
+    Merge,
+    /// Wipe the current vault and load the backup in its place.
+    Replace,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    #[serde(rename = "coursesAdded")]
+    pub courses_added: usize,
+    #[serde(rename = "exercisesAdded")]
+    pub exercises_added: usize,
+    #[serde(rename = "exercisesUpdated")]
+    pub exercises_updated: usize,
+    #[serde(rename = "conflictsSkipped")]
+    pub conflicts_skipped: usize,
+}
+
+enum MergeAction {
+    Insert,
+    Update(String),
+    /// Keep the existing exercise with this id.
+    Skip(String),
+}
+
+/// Writes an inlined data URL into the images dir and returns the new file's path.
+fn restore_image(images_dir: &Path, data_url: &str, written: &mut Vec<PathBuf>) -> Result<String, AppError> {
+    let (header, data) = data_url
+        .split_once(',')
+        .ok_or_else(|| AppError::InvalidInput("Backup contains malformed image data".to_string()))?;
+    let extension = match header {
+        h if h.contains("image/jpeg") => "jpg",
+        h if h.contains("image/webp") => "webp",
+        _ => "png",
+    };
+
+    let bytes = general_purpose::STANDARD.decode(data)?;
+    let path = images_dir.join(format!("{}.{}", Uuid::new_v4(), extension));
+    fs::write(&path, bytes)?;
+    written.push(path.clone());
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Maps an exported `images/<file>` path to that file in `images_dir`. Anything
+/// else (absolute paths, `..`, subdirectories, or a symlink leading out of the
+/// images dir) is refused, so an import can only point exercises at files inside
+/// the vault; images from elsewhere have to be inlined.
+fn resolve_image_path(images_dir: &Path, path: &str) -> Option<String> {
+    let file = Path::new(path.strip_prefix("images/")?);
+    let mut components = file.components();
+    if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+        return None;
+    }
+
+    let local = images_dir.join(file);
+    if let Ok(resolved) = local.canonicalize() {
+        if !resolved.starts_with(images_dir.canonicalize().ok()?) {
+            return None;
+        }
+    }
+    Some(local.to_string_lossy().into_owned())
+}
+
+/// Points an exported exercise back at local image files, restoring inlined images.
+fn restore_exercise(
+    images_dir: &Path,
+    exported: ExerciseExport,
+    written: &mut Vec<PathBuf>,
+) -> Result<Exercise, AppError> {
+    let mut exercise = exported.exercise;
+    // By exported path, so an image listed both in `images` and a column is written once
+    let mut restored: HashMap<String, String> = HashMap::new();
+    let mut restore = |path: Option<String>, data: Option<String>| -> Result<Option<String>, AppError> {
+        let Some(path) = path else {
+            return data.map(|data| restore_image(images_dir, &data, written)).transpose();
+        };
+        if let Some(local) = restored.get(&path) {
+            return Ok(Some(local.clone()));
+        }
+        let local = match data {
+            Some(data) => restore_image(images_dir, &data, written)?,
+            None => match resolve_image_path(images_dir, &path) {
+                Some(local) => local,
+                None => {
+                    warn!("Dropping image outside the images dir: {}", path);
+                    return Ok(None);
+                }
+            },
+        };
+        restored.insert(path, local.clone());
+        Ok(Some(local))
+    };
+
+    let mut images_data = exported.images_data.into_iter();
+    let mut images = Vec::with_capacity(exercise.images.len());
+    for mut image in std::mem::take(&mut exercise.images) {
+        let data = images_data.next().flatten();
+        if let Some(path) = restore(Some(image.path.clone()), data)? {
+            image.path = path;
+            images.push(image);
+        }
+    }
+    exercise.images = images;
+    exercise.image_uri = restore(exercise.image_uri.take(), exported.image_data)?;
+    exercise.page_image_uri = restore(exercise.page_image_uri.take(), exported.page_image_data)?;
+    exercise.solution_image_uri = restore(exercise.solution_image_uri.take(), exported.solution_image_data)?;
+    Ok(exercise)
+}
+
+/// An exercise with the same name in the same course/week is treated as the same
+/// exercise, and the more recently edited copy wins. Anything else is added, under a fresh id if
+/// its id is already taken.
+fn merge_action(tx: &Transaction, exercise: &mut Exercise) -> Result<MergeAction, AppError> {
+    // Exports from before updated_at existed only have created_at to go on
+    let existing: Option<(String, i64)> = tx
+        .query_row(
+            "SELECT id, COALESCE(updated_at, created_at) FROM exercises
+             WHERE name = ?1 AND course = ?2 AND week = ?3 LIMIT 1",
+            params![exercise.name, exercise.course, exercise.week],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    if let Some((id, updated_at)) = existing {
+        return Ok(if exercise.updated_at.unwrap_or(exercise.created_at) > updated_at {
+            MergeAction::Update(id)
+        } else {
+            MergeAction::Skip(id)
+        });
+    }
+
+    let id_taken = tx
+        .query_row("SELECT 1 FROM exercises WHERE id = ?1", [&exercise.id], |_| Ok(()))
+        .optional()?
+        .is_some();
+    if id_taken {
+        exercise.id = Uuid::new_v4().to_string();
+    }
+    Ok(MergeAction::Insert)
+}
+
+fn import_into(
+    tx: &Transaction,
+    images_dir: &Path,
+    export: VaultExport,
+    mode: ImportMode,
+    written: &mut Vec<PathBuf>,
+) -> Result<ImportSummary, AppError> {
+    if let ImportMode::Replace = mode {
+        tx.execute_batch(
+            "DELETE FROM exercises;
+             DELETE FROM course_metadata;
+             DELETE FROM week_metadata;
+             DELETE FROM undo_operations;",
+        )?;
+    }
+
+    let existing_courses: HashSet<String> = tx
+        .prepare("SELECT DISTINCT course FROM exercises")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+
+    let mut summary = ImportSummary::default();
+    // Export ids to the ids the exercises ended up with, for the links
+    let mut imported_ids: HashMap<String, String> = HashMap::new();
+    // Restored parts with the export id of their parent
+    let mut parents: Vec<(String, String)> = Vec::new();
+
+    for course in export.courses {
+        if !existing_courses.contains(&course.name) {
+            summary.courses_added += 1;
+        }
+
+        for week in course.weeks {
+            for exported in week.exercises {
+                let exported_id = exported.exercise.id.clone();
+                let mut exercise = exported.exercise.clone();
+                // The nesting is authoritative over the per-exercise copies
+                exercise.course = course.name.clone();
+                exercise.week = week.week;
+
+                let action = match mode {
+                    ImportMode::Replace => MergeAction::Insert,
+                    ImportMode::Merge => merge_action(tx, &mut exercise)?,
+                };
+                if let MergeAction::Skip(id) = action {
+                    imported_ids.insert(exported_id, id);
+                    summary.conflicts_skipped += 1;
+                    continue;
+                }
+
+                let mut restored = restore_exercise(
+                    images_dir,
+                    ExerciseExport { exercise, ..exported },
+                    written,
+                )?;
+
+                match action {
+                    MergeAction::Update(id) => {
+                        restored.id = id;
+                        restored.order_index = None;
+                        summary.exercises_updated += 1;
+                    }
+                    _ => {
+                        if let ImportMode::Merge = mode {
+                            restored.order_index = None;
+                        }
+                        summary.exercises_added += 1;
+                    }
+                }
+                // Set once every exercise has its final id
+                let exported_parent = restored.parent_id.take();
+                restored.upsert(tx)?;
+                if let Some(exported_parent) = exported_parent {
+                    parents.push((restored.id.clone(), exported_parent));
+                }
+                imported_ids.insert(exported_id, restored.id);
+            }
+        }
+    }
+
+    for (id, exported_parent) in parents {
+        // A part whose parent wasn't in the export, or merged into it, stands on its own
+        let parent = imported_ids.get(&exported_parent).filter(|parent| **parent != id);
+        tx.execute("UPDATE exercises SET parent_id = ?2 WHERE id = ?1", params![id, parent])?;
+    }
+
+    for link in export.links {
+        if let (Some(from_id), Some(to_id)) = (imported_ids.get(&link.from_id), imported_ids.get(&link.to_id)) {
+            // Two exported exercises can merge into the same one
+            if from_id != to_id {
+                insert_link(
+                    tx,
+                    &ExerciseLink {
+                        from_id: from_id.clone(),
+                        to_id: to_id.clone(),
+                        relation: link.relation,
+                    },
+                )?;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Export versions this build can import.
+const SUPPORTED_EXPORT_VERSIONS: std::ops::RangeInclusive<u32> = 1..=VAULT_EXPORT_VERSION;
+
+fn import_in_transaction(
+    conn: &mut Connection,
+    images_dir: &Path,
+    export: VaultExport,
+    mode: ImportMode,
+    written: &mut Vec<PathBuf>,
+) -> Result<ImportSummary, AppError> {
+    let tx = conn.transaction()?;
+    let summary = import_into(&tx, images_dir, export, mode, written)?;
+    tx.commit()?;
+    Ok(summary)
+}
+
+/// Checks the export version, then imports it in a single transaction.
+fn import_vault(
+    conn: &mut Connection,
+    images_dir: &Path,
+    export: VaultExport,
+    mode: ImportMode,
+) -> Result<ImportSummary, AppError> {
+    if !SUPPORTED_EXPORT_VERSIONS.contains(&export.version) {
+        let supported: Vec<String> = SUPPORTED_EXPORT_VERSIONS.map(|v| v.to_string()).collect();
+        return Err(AppError::InvalidInput(format!(
+            "Unsupported export version {} (supported versions: {})",
+            export.version,
+            supported.join(", ")
+        )));
+    }
+
+    // Exports don't carry attachments, so a replace drops every one of them
+    let (replaced, attachments) = match mode {
+        ImportMode::Replace => (referenced_image_names(conn)?, attachment_paths(conn)?),
+        ImportMode::Merge => (HashSet::new(), Vec::new()),
+    };
+
+    let mut written = Vec::new();
+    let result = import_in_transaction(conn, images_dir, export, mode, &mut written);
+    match &result {
+        Err(_) => {
+            // Nothing was committed, so drop the images restored along the way
+            for path in written {
+                let _ = fs::remove_file(path);
+            }
+        }
+        Ok(_) => {
+            for path in attachments {
+                let _ = fs::remove_file(path);
+            }
+            if !replaced.is_empty() {
+                remove_replaced_images(conn, images_dir, replaced);
+            }
+        }
+    }
+    result
+}
+
+fn attachment_paths(conn: &Connection) -> Result<Vec<String>, AppError> {
+    let paths = conn
+        .prepare("SELECT path FROM attachments")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(paths)
+}
+
+/// File names of the images exercises refer to. Stored paths may spell the images
+/// dir differently than we do now, but the names are unique.
+fn referenced_image_names(conn: &Connection) -> Result<HashSet<String>, AppError> {
+    let paths: Vec<String> = conn
+        .prepare("SELECT DISTINCT path FROM exercise_images")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(paths
+        .iter()
+        .filter_map(|path| Path::new(path).file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect())
+}
+
+/// Deletes the images of a replaced vault that the imported one doesn't use, with
+/// their thumbnails. The import is already committed, so failures are only logged.
+fn remove_replaced_images(conn: &Connection, images_dir: &Path, replaced: HashSet<String>) {
+    let still_referenced = match referenced_image_names(conn) {
+        Ok(names) => names,
+        Err(e) => {
+            warn!("Could not check which replaced images are still in use: {}", e);
+            return;
+        }
+    };
+
+    let mut removed = 0;
+    for name in replaced.difference(&still_referenced) {
+        let path = images_dir.join(name);
+        for file in [Some(path.clone()), thumbnail_path(&path)].into_iter().flatten() {
+            match fs::remove_file(&file) {
+                Ok(()) => removed += 1,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => warn!("Could not remove replaced image {}: {}", file.display(), e),
+            }
+        }
+    }
+    info!("Removed {} image files of the replaced vault", removed);
+}
+
+/// Loads a file written by `export_vault_json`, either merging it into the current
+/// vault or replacing it. Runs in a single transaction.
+#[command]
+pub fn import_vault_json<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    source: String,
+    mode: ImportMode,
+) -> Result<ImportSummary, AppError> {
+    let export: VaultExport = serde_json::from_slice(&fs::read(&source)?)?;
+
+    let mut conn = get_connection(&pool)?;
+    let images_dir = get_images_dir(&app)?;
+    let summary = import_vault(&mut conn, &images_dir, export, mode)?;
+
+    info!("Imported vault from {}: {:?}", source, summary);
+    Ok(summary)
+}
+
+/// Imports a document produced by `export_course`. A course that already exists
+/// is merged week by week, unless `rename_to` files the import under a new name.
+#[command]
+pub fn import_course<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    json: String,
+    rename_to: Option<String>,
+) -> Result<ImportSummary, AppError> {
+    let mut export: VaultExport = serde_json::from_str(&json)?;
+
+    if let Some(new_name) = rename_to.map(|n| n.trim().to_string()) {
+        if new_name.is_empty() {
+            return Err(AppError::InvalidInput("Course name cannot be empty".to_string()));
+        }
+        let [course] = export.courses.as_mut_slice() else {
+            return Err(AppError::InvalidInput(format!(
+                "Can only rename a single-course export, this one has {} courses",
+                export.courses.len()
+            )));
+        };
+        course.name = new_name;
+    }
+
+    let mut conn = get_connection(&pool)?;
+    let images_dir = get_images_dir(&app)?;
+    let summary = import_vault(&mut conn, &images_dir, export, ImportMode::Merge)?;
+
+    info!("Imported course: {:?}", summary);
+    Ok(summary)
+}
+
+const ARCHIVE_DB_ENTRY: &str = "vaulty.db";
+const ARCHIVE_IMAGES_DIR: &str = "images";
+const ARCHIVE_ATTACHMENTS_DIR: &str = "attachments";
+
+fn write_backup_archive(
+    archive_path: &Path,
+    db_snapshot: &Path,
+    images_dir: &Path,
+    attachments_dir: &Path,
+) -> Result<(), AppError> {
+    let mut zip = ZipWriter::new(File::create(archive_path)?);
+
+    zip.start_file(
+        ARCHIVE_DB_ENTRY,
+        FileOptions::default().compression_method(CompressionMethod::Deflated),
+    )?;
+    io::copy(&mut File::open(db_snapshot)?, &mut zip)?;
+
+    // Images are already compressed, deflating them again only costs time
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    for entry in fs::read_dir(images_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = format!("{}/{}", ARCHIVE_IMAGES_DIR, entry.file_name().to_string_lossy());
+        zip.start_file(name, stored)?;
+        io::copy(&mut File::open(entry.path())?, &mut zip)?;
+    }
+
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    for entry in fs::read_dir(attachments_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = format!("{}/{}", ARCHIVE_ATTACHMENTS_DIR, entry.file_name().to_string_lossy());
+        zip.start_file(name, deflated)?;
+        io::copy(&mut File::open(entry.path())?, &mut zip)?;
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+/// Writes a consistent copy of the live database to `path`, even with other writers in WAL mode.
+fn snapshot_database(conn: &Connection, path: &Path) -> Result<(), AppError> {
+    let _ = fs::remove_file(path);
+    conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+    Ok(())
+}
+
+/// Zips the database and the images and attachments directories into a single
+/// file at `destination`.
+#[command]
+pub fn create_backup<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    destination: String,
+) -> Result<(), AppError> {
+    let data_dir = get_data_dir(&app)?;
+    let images_dir = get_images_dir(&app)?;
+    let attachments_dir = get_attachments_dir(&app)?;
+
+    let conn = get_connection(&pool)?;
+    let snapshot = data_dir.join("vaulty.snapshot.db");
+    snapshot_database(&conn, &snapshot)?;
+
+    let result = write_atomically(Path::new(&destination), |temp| {
+        write_backup_archive(temp, &snapshot, &images_dir, &attachments_dir)
+    });
+    let _ = fs::remove_file(&snapshot);
+    result?;
+
+    info!("Created backup archive at {}", destination);
+    Ok(())
+}
+
+/// Unpacks the database, images and attachments from an archive into `staging`,
+/// ignoring any other entries and any path that would escape the directory.
+fn extract_backup_archive(archive: &mut ZipArchive<File>, staging: &Path) -> Result<(), AppError> {
+    fs::create_dir_all(staging.join(ARCHIVE_IMAGES_DIR))?;
+    // Backups from before attachments restore to none
+    fs::create_dir_all(staging.join(ARCHIVE_ATTACHMENTS_DIR))?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let Some(name) = entry.enclosed_name().map(Path::to_path_buf) else {
+            continue;
+        };
+
+        let is_db = name == Path::new(ARCHIVE_DB_ENTRY);
+        let is_image = name.parent() == Some(Path::new(ARCHIVE_IMAGES_DIR));
+        let is_attachment = name.parent() == Some(Path::new(ARCHIVE_ATTACHMENTS_DIR));
+        if !is_db && !is_image && !is_attachment {
+            continue;
+        }
+
+        io::copy(&mut entry, &mut File::create(staging.join(&name))?)?;
+    }
+    Ok(())
+}
+
+pub fn copy_dir_files(from: &Path, to: &Path) -> Result<(), AppError> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Image paths are stored absolute, so a backup made on another machine (or under
+/// another user) points at the wrong directory. Repoint any missing file at the
+/// restored copy with the same name.
+fn relink_images(conn: &Connection, images_dir: &Path) -> Result<(), AppError> {
+    point_images_at(conn, images_dir, true)
+}
+
+/// Repoints every image path at the file with the same name in `images_dir`, for
+/// when the whole directory has moved.
+pub fn repoint_images(conn: &Connection, images_dir: &Path) -> Result<(), AppError> {
+    point_images_at(conn, images_dir, false)
+}
+
+/// Attachment paths are stored absolute too; this repoints them like
+/// `repoint_images`, or with `only_missing` like `relink_images`.
+pub fn point_attachments_at(conn: &Connection, attachments_dir: &Path, only_missing: bool) -> Result<(), AppError> {
+    let rows: Vec<(String, String)> = conn
+        .prepare("SELECT id, path FROM attachments")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+
+    for (id, path) in rows {
+        if only_missing && Path::new(&path).exists() {
+            continue;
+        }
+        let Some(name) = Path::new(&path).file_name() else {
+            continue;
+        };
+        let local = attachments_dir.join(name);
+        if local.exists() {
+            conn.execute(
+                "UPDATE attachments SET path = ?1 WHERE id = ?2",
+                params![local.to_string_lossy(), id],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn point_images_at(conn: &Connection, images_dir: &Path, only_missing: bool) -> Result<(), AppError> {
+    let relink = |path: Option<String>| -> Option<String> {
+        let path = path?;
+        if only_missing && Path::new(&path).exists() {
+            return None;
+        }
+        let local = images_dir.join(Path::new(&path).file_name()?);
+        local.exists().then(|| local.to_string_lossy().into_owned())
+    };
+
+    // The image list first, so the column updates below find their images already moved
+    let paths: Vec<String> = conn
+        .prepare("SELECT DISTINCT path FROM exercise_images")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    for old in paths {
+        if let Some(path) = relink(Some(old.clone())) {
+            conn.execute(
+                "UPDATE OR IGNORE exercise_images SET path = ?1 WHERE path = ?2",
+                params![path, old],
+            )?;
+        }
+    }
+
+    for column in ["image_path", "page_image_path", "solution_image_path"] {
+        let rows: Vec<(String, Option<String>)> = conn
+            .prepare(&format!("SELECT id, {} FROM exercises", column))?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        for (id, path) in rows {
+            if let Some(path) = relink(path) {
+                conn.execute(&format!("UPDATE exercises SET {} = ?1 WHERE id = ?2", column), params![path, id])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn restore_from_staging(
+    pool: &DbPool,
+    data_dir: &Path,
+    images_dir: &Path,
+    attachments_dir: &Path,
+    staging: &Path,
+) -> Result<(), AppError> {
+    let staged_path = staging.join(ARCHIVE_DB_ENTRY);
+    let check = pool
+        .open_unpooled(&staged_path)?
+        .query_row("PRAGMA quick_check", [], |row| row.get::<_, String>(0))
+        .map_err(|e| match e.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::NotADatabase) => AppError::InvalidInput(
+                "Backup database can't be read; it may be encrypted with a different passphrase".to_string(),
+            ),
+            _ => AppError::from(e),
+        })?;
+    if check != "ok" {
+        return Err(AppError::InvalidInput(format!("Backup database is corrupt: {}", check)));
+    }
+
+    // Keep the current data around so a bad restore can be undone by hand
+    let bak_dir = data_dir.join("restore.bak");
+    let _ = fs::remove_dir_all(&bak_dir);
+    fs::create_dir_all(&bak_dir)?;
+    {
+        let conn = get_connection(pool)?;
+        snapshot_database(&conn, &bak_dir.join(ARCHIVE_DB_ENTRY))?;
+    }
+    copy_dir_files(images_dir, &bak_dir.join(ARCHIVE_IMAGES_DIR))?;
+    copy_dir_files(attachments_dir, &bak_dir.join(ARCHIVE_ATTACHMENTS_DIR))?;
+
+    pool.reopen(None, |db_path| replace_database_file(db_path, &staged_path))?;
+    let mut conn = get_connection(pool)?;
+    migrations::run(&mut conn)?;
+
+    fs::remove_dir_all(images_dir)?;
+    fs::rename(staging.join(ARCHIVE_IMAGES_DIR), images_dir)?;
+    relink_images(&conn, images_dir)?;
+    fs::remove_dir_all(attachments_dir)?;
+    fs::rename(staging.join(ARCHIVE_ATTACHMENTS_DIR), attachments_dir)?;
+    point_attachments_at(&conn, attachments_dir, true)?;
+
+    Ok(())
+}
+
+/// Replaces the database, images and attachments with the contents of a
+/// `create_backup` archive.
+/// The previous data is kept in `restore.bak` inside the data dir.
+#[command]
+pub fn restore_backup<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    http: State<'_, HttpClient>,
+    source: String,
+) -> Result<(), AppError> {
+    let data_dir = get_data_dir(&app)?;
+    let images_dir = get_images_dir(&app)?;
+    let attachments_dir = get_attachments_dir(&app)?;
+
+    let mut archive = ZipArchive::new(File::open(&source)?)?;
+    if archive.by_name(ARCHIVE_DB_ENTRY).is_err() {
+        return Err(AppError::InvalidInput(format!(
+            "Not a Vaulty backup: {} is missing from the archive",
+            ARCHIVE_DB_ENTRY
+        )));
+    }
+
+    // Unpack fully before touching live data so a truncated archive fails early
+    let staging = data_dir.join("restore.tmp");
+    let _ = fs::remove_dir_all(&staging);
+    let result = extract_backup_archive(&mut archive, &staging)
+        .and_then(|_| restore_from_staging(&pool, &data_dir, &images_dir, &attachments_dir, &staging));
+    let _ = fs::remove_dir_all(&staging);
+    // The restored database may hold a different proxy setting
+    http.reset();
+    result?;
+
+    info!("Restored backup from {}", source);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_connection;
+
+    #[test]
+    fn resolve_image_path_stays_inside_images_dir() {
+        let images_dir = Path::new("/vault/images");
+        assert_eq!(
+            resolve_image_path(images_dir, "images/a.png").as_deref(),
+            Some(images_dir.join("a.png").to_str().unwrap())
+        );
+        for path in ["/home/user/notes.txt", "images/../db.sqlite", "images/sub/a.png", "images/", "a.png"] {
+            assert_eq!(resolve_image_path(images_dir, path), None, "{}", path);
+        }
+    }
+
+    /// A one-exercise export of "Ex 1" in Algebra week 1.
+    fn export_of(content: &str, created_at: i64, updated_at: i64) -> VaultExport {
+        serde_json::from_value(serde_json::json!({
+            "version": VAULT_EXPORT_VERSION,
+            "exportedAt": 0,
+            "courses": [{
+                "name": "Algebra",
+                "weeks": [{
+                    "week": 1,
+                    "exercises": [{
+                        "id": "exported",
+                        "name": "Ex 1",
+                        "tags": [],
+                        "course": "Algebra",
+                        "week": 1,
+                        "content": content,
+                        "createdAt": created_at,
+                        "updatedAt": updated_at,
+                        "isFavorite": false,
+                        "totalSeconds": 0
+                    }]
+                }]
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn content(conn: &Connection) -> String {
+        conn.query_row("SELECT content FROM exercises WHERE id = 'local'", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn merge_keeps_the_more_recently_edited_copy() {
+        let mut conn = test_connection();
+        conn.execute(
+            "INSERT INTO exercises (id, name, tags, course, week, content, created_at, updated_at)
+             VALUES ('local', 'Ex 1', '[]', 'Algebra', 1, 'local', 100, 200)",
+            [],
+        )
+        .unwrap();
+        let images_dir = Path::new("/nonexistent/images");
+
+        // Created later, but edited before the local copy was
+        let summary = import_vault(&mut conn, images_dir, export_of("stale", 150, 150), ImportMode::Merge).unwrap();
+        assert_eq!((summary.conflicts_skipped, summary.exercises_updated), (1, 0));
+        assert_eq!(content(&conn), "local");
+
+        let summary = import_vault(&mut conn, images_dir, export_of("edited", 50, 300), ImportMode::Merge).unwrap();
+        assert_eq!((summary.conflicts_skipped, summary.exercises_updated), (0, 1));
+        assert_eq!(content(&conn), "edited");
+    }
+
+    #[test]
+    fn replace_clears_course_and_week_metadata() {
+        let mut conn = test_connection();
+        conn.execute_batch(
+            "INSERT INTO exercises (id, name, tags, course, week, created_at)
+             VALUES ('local', 'Old', '[]', 'History', 2, 100);
+             INSERT INTO course_metadata (course, color) VALUES ('History', '#ff0000');
+             INSERT INTO week_metadata (course, week, title) VALUES ('History', 2, 'Rome');",
+        )
+        .unwrap();
+
+        let export = export_of("imported", 1, 1);
+        import_vault(&mut conn, Path::new("/nonexistent/images"), export, ImportMode::Replace).unwrap();
+
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM exercises WHERE course = 'History'"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM course_metadata"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM week_metadata"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM exercises WHERE course = 'Algebra'"), 1);
+    }
+
+    #[test]
+    fn replace_removes_images_only_the_old_vault_used() {
+        let images_dir = std::env::temp_dir().join(format!("vaulty-replace-{}", Uuid::new_v4()));
+        fs::create_dir_all(&images_dir).unwrap();
+        for name in ["dropped.png", "dropped.thumb.png", "kept.png"] {
+            fs::write(images_dir.join(name), b"png").unwrap();
+        }
+
+        let mut conn = test_connection();
+        conn.execute(
+            "INSERT INTO exercises (id, name, tags, course, week, created_at) VALUES ('local', 'Old', '[]', 'History', 2, 100)",
+            [],
+        )
+        .unwrap();
+        for name in ["dropped.png", "kept.png"] {
+            conn.execute(
+                "INSERT INTO exercise_images (exercise_id, path, kind, position) VALUES ('local', ?1, 'crop', 0)",
+                [images_dir.join(name).to_string_lossy()],
+            )
+            .unwrap();
+        }
+
+        let mut export = export_of("imported", 1, 1);
+        export.courses[0].weeks[0].exercises[0].exercise.image_uri = Some("images/kept.png".to_string());
+        import_vault(&mut conn, &images_dir, export, ImportMode::Replace).unwrap();
+
+        assert!(!images_dir.join("dropped.png").exists());
+        assert!(!images_dir.join("dropped.thumb.png").exists());
+        assert!(images_dir.join("kept.png").exists());
+        fs::remove_dir_all(&images_dir).unwrap();
+    }
+
+    #[test]
+    fn replace_removes_attachments_and_undo_history() {
+        let dir = std::env::temp_dir().join(format!("vaulty-replace-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let attachment = dir.join("notes.pdf");
+        fs::write(&attachment, b"%PDF").unwrap();
+
+        let mut conn = test_connection();
+        conn.execute(
+            "INSERT INTO exercises (id, name, tags, course, week, created_at) VALUES ('local', 'Old', '[]', 'History', 2, 100)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO attachments (id, exercise_id, file_name, path, mime_type, size_bytes, created_at)
+             VALUES ('att', 'local', 'notes.pdf', ?1, 'application/pdf', 4, 100)",
+            [attachment.to_string_lossy()],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO undo_operations (kind, target, deleted_at, exercise_ids) VALUES ('course', 'History', 100, '[\"local\"]')",
+            [],
+        )
+        .unwrap();
+
+        import_vault(&mut conn, &dir, export_of("imported", 1, 1), ImportMode::Replace).unwrap();
+
+        assert!(!attachment.exists());
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM attachments"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM undo_operations"), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use serde::Serialize;
+use tauri::{command, State};
+use tracing::debug;
+
+use crate::db::{get_connection, DbPool};
+use crate::error::AppError;
+use crate::settings::Settings;
+
+/// US Letter in points, for pages without a readable MediaBox.
+const DEFAULT_PAGE_SIZE: (f64, f64) = (612.0, 792.0);
+
+/// Rendered pages are mostly white paper; as PNG they come out at roughly this
+/// many bytes per pixel. Scans and photo-heavy pages can be several times larger.
+const PNG_BYTES_PER_PIXEL: f64 = 0.15;
+
+/// Gemini counts an image with both sides at most 384px as one tile, and splits
+/// anything larger into 768x768 tiles; each tile costs 258 tokens.
+const SMALL_IMAGE_SIDE: u64 = 384;
+const TILE_SIDE: u64 = 768;
+const TOKENS_PER_TILE: u64 = 258;
+
+/// The analysis prompt and response schema sent along with every page.
+const PROMPT_TOKENS: u64 = 450;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostEstimate {
+    pub pages: u32,
+    pub estimated_image_bytes: u64,
+    pub estimated_input_tokens: u64,
+}
+
+/// A page's MediaBox as (width, height) in points, inherited from its parents
+/// in the page tree when the page doesn't set one.
+fn page_size(doc: &Document, page_id: ObjectId) -> Option<(f64, f64)> {
+    let mut dict: &Dictionary = doc.get_dictionary(page_id).ok()?;
+    // Bounded so a malformed tree with a cycle can't loop forever
+    for _ in 0..32 {
+        if let Ok(media_box) = dict.get_deref(b"MediaBox", doc).and_then(Object::as_array) {
+            let coords = media_box
+                .iter()
+                .map(|value| doc.dereference(value).and_then(|(_, value)| value.as_float()))
+                .collect::<Result<Vec<_>, _>>()
+                .ok()?;
+            if let [x0, y0, x1, y1] = coords[..] {
+                return Some(((x1 - x0).abs() as f64, (y1 - y0).abs() as f64));
+            }
+            return None;
+        }
+        dict = dict.get_deref(b"Parent", doc).and_then(Object::as_dict).ok()?;
+    }
+    None
+}
+
+fn image_tokens(width: u64, height: u64) -> u64 {
+    if width <= SMALL_IMAGE_SIDE && height <= SMALL_IMAGE_SIDE {
+        return TOKENS_PER_TILE;
+    }
+    width.div_ceil(TILE_SIDE) * height.div_ceil(TILE_SIDE) * TOKENS_PER_TILE
+}
+
+/// Estimates what analyzing every page of a PDF will cost, without rendering it
+/// or calling Gemini: each page is sized from its MediaBox at `dpi` (by default the
+/// `pdf_dpi` setting, as for `pdf_to_images`) and counted with Gemini's image
+/// token rules, plus the prompt sent with it.
+///
+/// This is an estimate. The billed amount also includes output tokens and can
+/// differ when Gemini resizes images or changes its token accounting.
+#[command]
+pub fn estimate_analysis_cost(
+    pool: State<'_, DbPool>,
+    path: String,
+    dpi: Option<u32>,
+) -> Result<CostEstimate, AppError> {
+    let dpi = match dpi {
+        Some(dpi) => dpi,
+        None => {
+            let conn = get_connection(&pool)?;
+            Settings::load(&conn)?.pdf_dpi
+        }
+    };
+    if dpi == 0 {
+        return Err(AppError::InvalidInput("DPI must be greater than 0".to_string()));
+    }
+
+    let doc = Document::load(&path).map_err(|e| AppError::PdfConversion(format!("Failed to open PDF: {}", e)))?;
+    let pages = doc.get_pages();
+
+    let mut estimate = CostEstimate {
+        pages: pages.len() as u32,
+        estimated_image_bytes: 0,
+        estimated_input_tokens: 0,
+    };
+    // PDF user space is 72 units per inch
+    let scale = dpi as f64 / 72.0;
+    for page_id in pages.into_values() {
+        let (width, height) = page_size(&doc, page_id).unwrap_or(DEFAULT_PAGE_SIZE);
+        let (width, height) = ((width * scale).round() as u64, (height * scale).round() as u64);
+        estimate.estimated_image_bytes += ((width * height) as f64 * PNG_BYTES_PER_PIXEL) as u64;
+        estimate.estimated_input_tokens += image_tokens(width, height) + PROMPT_TOKENS;
+    }
+
+    debug!(
+        "Estimated {} pages at {} DPI: {} bytes, {} tokens",
+        estimate.pages, dpi, estimate.estimated_image_bytes, estimate.estimated_input_tokens
+    );
+    Ok(estimate)
+}
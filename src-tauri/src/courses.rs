@@ -0,0 +1,279 @@
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{command, State};
+
+use crate::db::{get_connection, DbPool};
+use crate::error::AppError;
+
+/// User-set details of a course. Courses without a `course_metadata` row get the
+/// defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CourseMetadata {
+    /// `#rrggbb` or `#rgb`.
+    pub color: Option<String>,
+    /// Free-form term label, e.g. "Fall 2025".
+    pub semester: Option<String>,
+    #[serde(default)]
+    pub archived: bool,
+    pub description: Option<String>,
+}
+
+/// User-set details of one week of a course.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeekMetadata {
+    /// e.g. "Eigenvalues"
+    pub title: Option<String>,
+    /// `YYYY-MM-DD`
+    pub start_date: Option<String>,
+    /// `YYYY-MM-DD`, on or after `start_date`.
+    pub end_date: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Week {
+    pub week: i64,
+    pub exercise_count: i64,
+    #[serde(flatten)]
+    pub metadata: WeekMetadata,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Course {
+    pub name: String,
+    pub exercise_count: i64,
+    #[serde(flatten)]
+    pub metadata: CourseMetadata,
+    /// Weeks with exercises outside the trash, in order.
+    pub weeks: Vec<Week>,
+}
+
+impl Course {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Course {
+            name: row.get(0)?,
+            exercise_count: row.get(1)?,
+            metadata: CourseMetadata {
+                color: row.get(2)?,
+                semester: row.get(3)?,
+                archived: row.get::<_, Option<bool>>(4)?.unwrap_or(false),
+                description: row.get(5)?,
+            },
+            weeks: Vec::new(),
+        })
+    }
+}
+
+/// Filters exercises down to those whose course isn't archived.
+pub const NOT_ARCHIVED: &str =
+    "course NOT IN (SELECT course FROM course_metadata WHERE archived = 1)";
+
+fn check_color(color: &str) -> Result<(), AppError> {
+    let hex = color.strip_prefix('#').unwrap_or("");
+    if !matches!(hex.len(), 3 | 6) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(AppError::InvalidInput(format!(
+            "Course color must be a hex color like #3b82f6, not {}",
+            color
+        )));
+    }
+    Ok(())
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| AppError::InvalidInput(format!("Expected a date like 2025-10-13, not {}", date)))
+}
+
+/// Every course with exercises outside the trash, archived ones included, in
+/// natural order, with its weeks.
+#[command]
+pub fn get_courses(pool: State<'_, DbPool>) -> Result<Vec<Course>, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT e.course, COUNT(*), m.color, m.semester, m.archived, m.description
+         FROM exercises e LEFT JOIN course_metadata m ON m.course = e.course
+         WHERE e.deleted_at IS NULL
+         GROUP BY e.course
+         ORDER BY e.course COLLATE NATURAL_ORDER",
+    )?;
+    let mut courses = stmt
+        .query_map([], Course::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let positions: HashMap<String, usize> = courses
+        .iter()
+        .enumerate()
+        .map(|(position, course)| (course.name.clone(), position))
+        .collect();
+    let mut stmt = conn.prepare(
+        "SELECT e.course, e.week, COUNT(*), w.title, w.start_date, w.end_date
+         FROM exercises e LEFT JOIN week_metadata w ON w.course = e.course AND w.week = e.week
+         WHERE e.deleted_at IS NULL
+         GROUP BY e.course, e.week
+         ORDER BY e.week",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let course: String = row.get(0)?;
+        if let Some(&position) = positions.get(&course) {
+            courses[position].weeks.push(Week {
+                week: row.get(1)?,
+                exercise_count: row.get(2)?,
+                metadata: WeekMetadata {
+                    title: row.get(3)?,
+                    start_date: row.get(4)?,
+                    end_date: row.get(5)?,
+                },
+            });
+        }
+    }
+
+    Ok(courses)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CourseSummary {
+    pub name: String,
+    pub exercise_count: i64,
+    pub week_count: i64,
+    /// Latest `updatedAt` of the course's exercises outside the trash.
+    pub last_modified: Option<i64>,
+    pub archived: bool,
+}
+
+/// Course names with counts, for the sidebar, in one grouped query. Courses that
+/// have metadata are listed even when all their exercises are in the trash.
+#[command]
+pub fn list_courses(pool: State<'_, DbPool>) -> Result<Vec<CourseSummary>, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT name, SUM(live), COUNT(DISTINCT CASE WHEN live THEN week END),
+                MAX(CASE WHEN live THEN updated_at END), MAX(archived)
+         FROM (
+             SELECT course AS name, week, deleted_at IS NULL AS live, updated_at, 0 AS archived, 0 AS known
+             FROM exercises
+             UNION ALL
+             SELECT course, NULL, 0, NULL, archived, 1 FROM course_metadata
+         )
+         GROUP BY name
+         HAVING SUM(live) > 0 OR MAX(known) = 1
+         ORDER BY name COLLATE NATURAL_ORDER",
+    )?;
+    let courses = stmt
+        .query_map([], |row| {
+            Ok(CourseSummary {
+                name: row.get(0)?,
+                exercise_count: row.get(1)?,
+                week_count: row.get(2)?,
+                last_modified: row.get(3)?,
+                archived: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(courses)
+}
+
+/// Replaces the metadata of `course`. Empty strings are stored as unset.
+#[command]
+pub fn update_course_metadata(
+    pool: State<'_, DbPool>,
+    course: String,
+    metadata: CourseMetadata,
+) -> Result<(), AppError> {
+    let color = non_empty(metadata.color);
+    if let Some(color) = &color {
+        check_color(color)?;
+    }
+
+    let conn = get_connection(&pool)?;
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM exercises WHERE course = ?1)",
+        params![course],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::NotFound(format!("course {}", course)));
+    }
+
+    conn.execute(
+        "INSERT OR REPLACE INTO course_metadata (course, color, semester, archived, description)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            course,
+            color,
+            non_empty(metadata.semester),
+            metadata.archived,
+            non_empty(metadata.description)
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Replaces the metadata of one week of `course`. Only the metadata row is
+/// written, so the week's exercises are untouched. Empty strings are stored as unset.
+#[command]
+pub fn update_week_metadata(
+    pool: State<'_, DbPool>,
+    course: String,
+    week: i64,
+    metadata: WeekMetadata,
+) -> Result<(), AppError> {
+    let start_date = non_empty(metadata.start_date);
+    let end_date = non_empty(metadata.end_date);
+    let start = start_date.as_deref().map(parse_date).transpose()?;
+    let end = end_date.as_deref().map(parse_date).transpose()?;
+    if let (Some(start), Some(end)) = (start, end) {
+        if end < start {
+            return Err(AppError::InvalidInput("A week can't end before it starts".to_string()));
+        }
+    }
+
+    let conn = get_connection(&pool)?;
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM exercises WHERE course = ?1 AND week = ?2)",
+        params![course, week],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::NotFound(format!("{} week {}", course, week)));
+    }
+
+    conn.execute(
+        "INSERT INTO week_metadata (course, week, title, start_date, end_date)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (course, week) DO UPDATE SET
+             title = excluded.title, start_date = excluded.start_date, end_date = excluded.end_date",
+        params![course, week, non_empty(metadata.title), start_date, end_date],
+    )?;
+
+    Ok(())
+}
+
+/// Moves the course and week metadata of `old` to `new` when a course is renamed
+/// or merged. Where `new` already has metadata it is kept and `old`'s is dropped.
+pub fn carry_metadata(conn: &Connection, old: &str, new: &str) -> Result<(), AppError> {
+    if old == new {
+        return Ok(());
+    }
+    for table in ["course_metadata", "week_metadata"] {
+        conn.execute(
+            &format!("UPDATE OR IGNORE {} SET course = ?2 WHERE course = ?1", table),
+            params![old, new],
+        )?;
+        conn.execute(&format!("DELETE FROM {} WHERE course = ?1", table), params![old])?;
+    }
+    Ok(())
+}
@@ -0,0 +1,452 @@
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+use tauri::{AppHandle, Runtime};
+use tracing::{info, warn};
+
+use crate::error::AppError;
+use crate::migrations;
+use crate::sort::natural_cmp;
+use crate::timer;
+use crate::trash;
+
+pub type DbConnection = PooledConnection<SqliteConnectionManager>;
+
+pub fn get_app_data_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, AppError> {
+    let path = app
+        .path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| AppError::Io("Failed to get app data directory".to_string()))?;
+    fs::create_dir_all(&path)
+        .map_err(|e| AppError::Io(format!("Failed to create app data dir: {}", e)))?;
+    Ok(path)
+}
+
+/// Names the directory holding the database and images when it isn't the app data
+/// dir. It lives outside the vault so it can be read before the database is opened.
+const DATA_DIR_CONFIG: &str = "data_dir";
+
+pub fn configured_data_dir<R: Runtime>(app: &AppHandle<R>) -> Result<Option<PathBuf>, AppError> {
+    match fs::read_to_string(get_app_data_dir(app)?.join(DATA_DIR_CONFIG)) {
+        Ok(contents) if !contents.trim().is_empty() => Ok(Some(PathBuf::from(contents.trim()))),
+        Ok(_) => Ok(None),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Points the app at `dir` for its data, or back at the app data dir with `None`.
+pub fn set_data_dir<R: Runtime>(app: &AppHandle<R>, dir: Option<&Path>) -> Result<(), AppError> {
+    let config = get_app_data_dir(app)?.join(DATA_DIR_CONFIG);
+    match dir {
+        Some(dir) => fs::write(config, dir.to_string_lossy().as_bytes())?,
+        None => match fs::remove_file(config) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        },
+    }
+    Ok(())
+}
+
+/// Directory holding the database and images. A configured directory that has gone
+/// missing (an unplugged drive, say) is an error rather than a reason to start an
+/// empty vault somewhere else.
+pub fn get_data_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, AppError> {
+    match configured_data_dir(app)? {
+        Some(dir) if dir.is_dir() => Ok(dir),
+        Some(dir) => Err(AppError::Io(format!(
+            "The data directory {} is not available. Reconnect the drive it is on and restart Vaulty.",
+            dir.display()
+        ))),
+        None => get_app_data_dir(app),
+    }
+}
+
+pub fn get_db_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, AppError> {
+    Ok(get_data_dir(app)?.join("vaulty.db"))
+}
+
+pub fn get_images_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, AppError> {
+    let path = get_data_dir(app)?.join("images");
+    fs::create_dir_all(&path)
+        .map_err(|e| AppError::Io(format!("Failed to create images dir: {}", e)))?;
+    Ok(path)
+}
+
+pub fn get_attachments_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, AppError> {
+    let path = get_data_dir(app)?.join("attachments");
+    fs::create_dir_all(&path)
+        .map_err(|e| AppError::Io(format!("Failed to create attachments dir: {}", e)))?;
+    Ok(path)
+}
+
+/// Per-connection settings, applied to every connection the pool opens. With
+/// SQLCipher the key has to be set before anything else touches the file.
+fn configure_connection(conn: &mut Connection, key: Option<&str>) -> rusqlite::Result<()> {
+    if let Some(key) = key {
+        conn.pragma_update(None, "key", key)?;
+    }
+    // Wait for competing writers instead of failing immediately with SQLITE_BUSY
+    conn.busy_timeout(Duration::from_secs(5))?;
+    // WAL lets reads proceed while an import is writing; foreign keys are off by default in SQLite
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA synchronous = NORMAL;
+         PRAGMA foreign_keys = ON;",
+    )?;
+    conn.create_collation("NATURAL_ORDER", natural_cmp)
+}
+
+/// An in-memory database set up like the pool's connections and migrated to the
+/// current schema.
+#[cfg(test)]
+pub(crate) fn test_connection() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    configure_connection(&mut conn, None).unwrap();
+    crate::migrations::run(&mut conn).unwrap();
+    conn
+}
+
+/// Plaintext SQLite files start with this header; SQLCipher files look like random bytes.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// True if the file exists and isn't a plaintext SQLite database.
+fn is_encrypted(db_path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    match fs::File::open(db_path).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(()) => &header != SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+fn build_pool(db_path: &Path, key: Option<String>) -> Result<Pool<SqliteConnectionManager>, AppError> {
+    let manager = SqliteConnectionManager::file(db_path)
+        .with_init(move |conn| configure_connection(conn, key.as_deref()));
+
+    Pool::builder()
+        .max_size(8)
+        .build(manager)
+        .map_err(|e| AppError::Db(format!("Failed to create connection pool: {}", e)))
+}
+
+struct PoolState {
+    db_path: PathBuf,
+    pool: Option<Pool<SqliteConnectionManager>>,
+    encrypted: bool,
+    key: Option<String>,
+}
+
+/// The shared connection pool, created once in `setup` and handed to commands as
+/// managed state. It sits behind a lock so it can be closed while the database
+/// file is replaced or moved, and stays closed while an encrypted vault is locked
+/// or the data directory is unavailable.
+pub struct DbPool {
+    state: RwLock<PoolState>,
+}
+
+impl DbPool {
+    /// Opens the pool, or leaves it closed if the database is encrypted and needs a passphrase.
+    pub fn open(db_path: PathBuf) -> Result<Self, AppError> {
+        let encrypted = is_encrypted(&db_path);
+        let pool = if encrypted {
+            None
+        } else {
+            Some(build_pool(&db_path, None)?)
+        };
+
+        Ok(DbPool {
+            state: RwLock::new(PoolState {
+                db_path,
+                pool,
+                encrypted,
+                key: None,
+            }),
+        })
+    }
+
+    /// A pool that never opens, for when there is no database to open.
+    pub fn closed() -> Self {
+        DbPool {
+            state: RwLock::new(PoolState {
+                db_path: PathBuf::new(),
+                pool: None,
+                encrypted: false,
+                key: None,
+            }),
+        }
+    }
+
+    pub fn db_path(&self) -> PathBuf {
+        self.state.read().unwrap_or_else(|e| e.into_inner()).db_path.clone()
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.state.read().unwrap_or_else(|e| e.into_inner()).encrypted
+    }
+
+    pub fn is_locked(&self) -> bool {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        state.pool.is_none() && state.encrypted
+    }
+
+    fn get(&self) -> Result<DbConnection, AppError> {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        let pool = match &state.pool {
+            Some(pool) => pool.clone(),
+            None if state.encrypted => return Err(AppError::VaultLocked),
+            None => return Err(AppError::Db("The database is not open".to_string())),
+        };
+        drop(state);
+        Ok(pool.get()?)
+    }
+
+    /// Opens a standalone connection to `path` with the vault's current key, for
+    /// reading databases that belong to this vault outside the pool.
+    pub fn open_unpooled(&self, path: &Path) -> Result<Connection, AppError> {
+        let conn = Connection::open(path)?;
+        if let Some(key) = &self.state.read().unwrap_or_else(|e| e.into_inner()).key {
+            conn.pragma_update(None, "key", key)?;
+        }
+        Ok(conn)
+    }
+
+    /// Fails with `InvalidInput` unless `key` decrypts the database file.
+    pub fn verify_key(&self, key: &str) -> Result<(), AppError> {
+        let conn = Connection::open(self.db_path())?;
+        conn.pragma_update(None, "key", key)?;
+        match conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)) {
+            Ok(_) => Ok(()),
+            Err(e) if e.sqlite_error_code() == Some(rusqlite::ErrorCode::NotADatabase) => {
+                Err(AppError::InvalidInput("Incorrect passphrase".to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Closes the pool, runs `replace` (which may swap out the database file) and
+    /// reopens it, switching to `new_key` if one is given. Callers must drop their
+    /// own connections first.
+    pub fn reopen(
+        &self,
+        new_key: Option<String>,
+        replace: impl FnOnce(&Path) -> Result<(), AppError>,
+    ) -> Result<(), AppError> {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        // Dropping the pool closes its idle connections
+        state.pool = None;
+
+        let result = replace(&state.db_path);
+        if result.is_ok() && new_key.is_some() {
+            state.key = new_key;
+        }
+
+        state.encrypted = is_encrypted(&state.db_path);
+        state.pool = Some(build_pool(&state.db_path, state.key.clone())?);
+        result
+    }
+
+    /// Closes the pool, runs `copy` with the current database path and key, and
+    /// reopens the pool on `new_db_path` if it succeeded or on the old file if not.
+    /// Callers must drop their own connections first.
+    pub fn relocate(
+        &self,
+        new_db_path: PathBuf,
+        copy: impl FnOnce(&Path, Option<&str>) -> Result<(), AppError>,
+    ) -> Result<(), AppError> {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.pool = None;
+
+        let result = copy(&state.db_path, state.key.as_deref());
+        if result.is_ok() {
+            state.db_path = new_db_path;
+        }
+
+        state.encrypted = is_encrypted(&state.db_path);
+        state.pool = Some(build_pool(&state.db_path, state.key.clone())?);
+        result
+    }
+
+    /// Closes the pool and opens the database at `db_path` in its place, for
+    /// switching vaults. The current key is forgotten, so an encrypted database
+    /// stays locked until it is unlocked with its own passphrase.
+    pub fn switch(&self, db_path: PathBuf) -> Result<(), AppError> {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.pool = None;
+        state.key = None;
+        state.encrypted = is_encrypted(&db_path);
+        state.db_path = db_path;
+
+        if !state.encrypted {
+            state.pool = Some(build_pool(&state.db_path, None)?);
+        }
+        Ok(())
+    }
+}
+
+/// Moves `new_file` over the database, discarding the old file's WAL so it can't
+/// be replayed into the new one. Only call this from a `DbPool::reopen` callback.
+pub fn replace_database_file(db_path: &Path, new_file: &Path) -> Result<(), AppError> {
+    for suffix in ["-wal", "-shm"] {
+        let mut path = db_path.as_os_str().to_owned();
+        path.push(suffix);
+        let _ = fs::remove_file(PathBuf::from(path));
+    }
+    fs::rename(new_file, db_path)?;
+    Ok(())
+}
+
+pub fn get_connection(pool: &DbPool) -> Result<DbConnection, AppError> {
+    pool.get()
+}
+
+pub fn init_db(pool: &DbPool) -> Result<(), AppError> {
+    let mut conn = get_connection(pool)?;
+    migrations::run(&mut conn)?;
+    // Housekeeping failing shouldn't keep the vault from opening
+    if let Err(e) = timer::close_dangling_sessions(&conn) {
+        warn!("Failed to close open timer sessions: {}", e);
+    }
+    if let Err(e) = trash::purge_expired(&mut conn) {
+        warn!("Failed to purge old trash: {}", e);
+    }
+
+    info!("Database initialized successfully");
+    Ok(())
+}
+
+/// Error from `init_db` at startup, kept so the frontend can report it instead of the app aborting.
+/// Cleared once another vault opens successfully.
+pub struct StartupError(pub Mutex<Option<AppError>>);
+
+pub fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>, AppError> {
+    let value = conn
+        .query_row("SELECT value FROM app_settings WHERE key = ?1", params![key], |row| row.get(0))
+        .optional()?;
+    Ok(value)
+}
+
+pub fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+pub fn delete_setting(conn: &Connection, key: &str) -> Result<(), AppError> {
+    conn.execute("DELETE FROM app_settings WHERE key = ?1", params![key])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn purging_an_exercise_leaves_no_orphans() {
+        let conn = test_connection();
+        conn.execute_batch(
+            "INSERT INTO exercises (id, name, tags, course, week, created_at, image_path, deleted_at, parent_id) VALUES
+                 ('parent', 'Ex 1', '[\"graphs\"]', 'Algebra', 1, 0, '/img/p.png', 5, NULL),
+                 ('part', 'Ex 1a', '[\"bfs\"]', 'Algebra', 1, 0, '/img/c.png', 5, 'parent'),
+                 ('other', 'Ex 2', '[\"graphs\"]', 'Algebra', 1, 0, '/img/o.png', NULL, NULL);
+             INSERT INTO attempts (exercise_id, attempted_at, outcome) VALUES
+                 ('parent', 1, 'solved'), ('part', 1, 'failed'), ('other', 1, 'partial');
+             INSERT INTO timer_sessions (exercise_id, started_at, ended_at) VALUES
+                 ('parent', 1, 2), ('part', 1, 2), ('other', 1, 2);
+             INSERT INTO attachments (id, exercise_id, file_name, path, mime_type, size_bytes, created_at) VALUES
+                 ('a1', 'parent', 'p.pdf', '/att/p.pdf', 'application/pdf', 1, 0),
+                 ('a2', 'other', 'o.pdf', '/att/o.pdf', 'application/pdf', 1, 0);
+             INSERT INTO exercise_history (exercise_id, name, reason, recorded_at) VALUES
+                 ('parent', 'Ex 1', 'edit', 1), ('other', 'Ex 2', 'edit', 1);
+             INSERT INTO exercise_links (from_id, to_id, relation, linked_at) VALUES
+                 ('parent', 'other', 'related', 0), ('other', 'part', 'prerequisite', 0);",
+        )
+        .unwrap();
+
+        conn.execute("DELETE FROM exercises WHERE id = 'parent'", []).unwrap();
+
+        let ids: Vec<String> = conn
+            .prepare("SELECT id FROM exercises ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(ids, ["other"], "the trashed part goes with its parent");
+
+        // Every column that points at an exercise, in whatever table it lives
+        let references: Vec<(String, String)> = conn
+            .prepare(
+                "SELECT m.name, c.name FROM sqlite_master m JOIN pragma_table_info(m.name) c
+                 WHERE m.type = 'table' AND c.name IN ('exercise_id', 'from_id', 'to_id', 'parent_id')",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(references.len() >= 8, "{:?}", references);
+        for (table, column) in references {
+            let orphans: i64 = conn
+                .query_row(
+                    &format!(
+                        "SELECT COUNT(*) FROM {table} WHERE {column} IS NOT NULL
+                         AND {column} NOT IN (SELECT id FROM exercises)"
+                    ),
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(orphans, 0, "{}.{} points at a purged exercise", table, column);
+        }
+
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM exercises_fts WHERE id != 'other'"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM attempts WHERE exercise_id = 'other'"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM exercise_images WHERE exercise_id = 'other'"), 1);
+    }
+
+    #[test]
+    fn pooled_connections_use_wal_and_cascade_deletes() {
+        let dir = std::env::temp_dir().join(format!("vaulty-pool-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let pool = DbPool::open(dir.join("vaulty.db")).unwrap();
+        init_db(&pool).unwrap();
+
+        // Every connection the pool hands out, not just the first
+        let connections: Vec<DbConnection> = (0..3).map(|_| get_connection(&pool).unwrap()).collect();
+        for conn in &connections {
+            let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+            let foreign_keys: bool = conn.query_row("PRAGMA foreign_keys", [], |row| row.get(0)).unwrap();
+            let synchronous: i64 = conn.query_row("PRAGMA synchronous", [], |row| row.get(0)).unwrap();
+            assert_eq!((journal_mode.as_str(), foreign_keys, synchronous), ("wal", true, 1));
+        }
+
+        let conn = &connections[0];
+        conn.execute_batch(
+            "INSERT INTO exercises (id, name, tags, course, week, created_at) VALUES ('e', 'Ex 1', '[]', 'Algebra', 1, 0);
+             INSERT INTO attempts (exercise_id, attempted_at, outcome) VALUES ('e', 1, 'solved');
+             INSERT INTO timer_sessions (exercise_id, started_at, ended_at) VALUES ('e', 1, 2);",
+        )
+        .unwrap();
+        connections[1].execute("DELETE FROM exercises WHERE id = 'e'", []).unwrap();
+        let left: i64 = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM attempts) + (SELECT COUNT(*) FROM timer_sessions)",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(left, 0);
+
+        drop(connections);
+        drop(pool);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
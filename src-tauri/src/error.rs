@@ -0,0 +1,139 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// Error returned by every command. Serialized as `{ kind, message }` so the
+/// frontend can branch on `error.kind` instead of parsing message strings.
+#[derive(Debug, Clone)]
+pub enum AppError {
+    Db(String),
+    Busy(String),
+    VaultLocked,
+    Migration(String),
+    Io(String),
+    Gemini(String),
+    RateLimited(String),
+    MissingApiKey,
+    Cancelled,
+    Keyring(String),
+    PdfConversion(String),
+    Base64(String),
+    Image(String),
+    Json(String),
+    Archive(String),
+    InvalidInput(String),
+    NotFound(String),
+}
+
+impl AppError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::Db(_) => "db",
+            AppError::Busy(_) => "busy",
+            AppError::VaultLocked => "vaultLocked",
+            AppError::Migration(_) => "migration",
+            AppError::Io(_) => "io",
+            AppError::Gemini(_) => "gemini",
+            AppError::RateLimited(_) => "rateLimited",
+            AppError::MissingApiKey => "missingApiKey",
+            AppError::Cancelled => "cancelled",
+            AppError::Keyring(_) => "keyring",
+            AppError::PdfConversion(_) => "pdfConversion",
+            AppError::Base64(_) => "base64",
+            AppError::Image(_) => "image",
+            AppError::Json(_) => "json",
+            AppError::Archive(_) => "archive",
+            AppError::InvalidInput(_) => "invalidInput",
+            AppError::NotFound(_) => "notFound",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Db(msg) => write!(f, "Database error: {}", msg),
+            AppError::Busy(msg) => write!(f, "Database is busy: {}", msg),
+            AppError::VaultLocked => write!(f, "Vault is locked"),
+            AppError::Migration(msg) => write!(f, "Database upgrade failed: {}", msg),
+            AppError::Io(msg) => write!(f, "File error: {}", msg),
+            AppError::Gemini(msg) => write!(f, "Gemini error: {}", msg),
+            AppError::RateLimited(msg) => write!(f, "Gemini rate limit exhausted: {}", msg),
+            AppError::MissingApiKey => write!(f, "API key not configured"),
+            AppError::Cancelled => write!(f, "Analysis cancelled"),
+            AppError::Keyring(msg) => write!(f, "Keyring error: {}", msg),
+            AppError::PdfConversion(msg) => write!(f, "PDF conversion failed: {}", msg),
+            AppError::Base64(msg) => write!(f, "Invalid base64 data: {}", msg),
+            AppError::Image(msg) => write!(f, "Image error: {}", msg),
+            AppError::Json(msg) => write!(f, "JSON error: {}", msg),
+            AppError::Archive(msg) => write!(f, "Backup archive error: {}", msg),
+            AppError::InvalidInput(msg) => write!(f, "{}", msg),
+            AppError::NotFound(msg) => write!(f, "Not found: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        AppError::Db(e.to_string())
+    }
+}
+
+impl From<r2d2::Error> for AppError {
+    fn from(e: r2d2::Error) -> Self {
+        AppError::Db(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Io(e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        AppError::Gemini(e.to_string())
+    }
+}
+
+impl From<keyring::Error> for AppError {
+    fn from(e: keyring::Error) -> Self {
+        AppError::Keyring(e.to_string())
+    }
+}
+
+impl From<base64::DecodeError> for AppError {
+    fn from(e: base64::DecodeError) -> Self {
+        AppError::Base64(e.to_string())
+    }
+}
+
+impl From<image::ImageError> for AppError {
+    fn from(e: image::ImageError) -> Self {
+        AppError::Image(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::Json(e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for AppError {
+    fn from(e: zip::result::ZipError) -> Self {
+        AppError::Archive(e.to_string())
+    }
+}
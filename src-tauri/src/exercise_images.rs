@@ -0,0 +1,179 @@
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tauri::{command, State};
+
+use crate::db::{get_connection, DbPool};
+use crate::error::AppError;
+use crate::images::thumbnail_path;
+use crate::models::{ExerciseImage, ImageKind};
+
+/// The first image of each kind, written back into the exercise's image columns.
+fn first_of_kind(kind: ImageKind) -> String {
+    format!(
+        "(SELECT path FROM exercise_images WHERE exercise_id = exercises.id AND kind = '{}'
+          ORDER BY position, path LIMIT 1)",
+        kind.as_str()
+    )
+}
+
+/// Points `image_path`, `page_image_path` and `solution_image_path` at the first
+/// image of their kind again after the image list changed.
+pub fn sync_image_columns(conn: &Connection, exercise_id: &str) -> Result<(), AppError> {
+    conn.execute(
+        &format!(
+            "UPDATE exercises SET image_path = {}, page_image_path = {}, solution_image_path = {}
+             WHERE id = ?1",
+            first_of_kind(ImageKind::Crop),
+            first_of_kind(ImageKind::Page),
+            first_of_kind(ImageKind::Solution)
+        ),
+        params![exercise_id],
+    )?;
+    Ok(())
+}
+
+/// Stores the image list of a newly inserted exercise, in place of the entries
+/// its image columns made.
+pub fn insert_images(conn: &Connection, exercise_id: &str, images: &[ExerciseImage]) -> Result<(), AppError> {
+    conn.execute("DELETE FROM exercise_images WHERE exercise_id = ?1", params![exercise_id])?;
+    let mut stmt = conn.prepare(
+        "INSERT OR IGNORE INTO exercise_images (exercise_id, path, kind, position) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    for image in images {
+        stmt.execute(params![exercise_id, image.path, image.kind.as_str(), image.position])?;
+    }
+    sync_image_columns(conn, exercise_id)
+}
+
+pub fn load_images(conn: &Connection, exercise_id: &str) -> Result<Vec<ExerciseImage>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT path, kind, position FROM exercise_images WHERE exercise_id = ?1 ORDER BY position, path",
+    )?;
+    let images = stmt
+        .query_map(params![exercise_id], |row| {
+            Ok(ExerciseImage {
+                path: row.get(0)?,
+                kind: ImageKind::from_column(&row.get::<_, String>(1)?),
+                position: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(images)
+}
+
+fn check_exercise(conn: &Connection, exercise_id: &str) -> Result<(), AppError> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM exercises WHERE id = ?1 AND deleted_at IS NULL)",
+        params![exercise_id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::NotFound(format!("exercise {}", exercise_id)));
+    }
+    Ok(())
+}
+
+/// Attaches an image file, saved beforehand with `save_image` or
+/// `crop_exercise_image`, after the exercise's other images. Returns the images.
+#[command]
+pub fn add_exercise_image(
+    pool: State<'_, DbPool>,
+    exercise_id: String,
+    path: String,
+    kind: ImageKind,
+) -> Result<Vec<ExerciseImage>, AppError> {
+    if !Path::new(&path).is_file() {
+        return Err(AppError::InvalidInput(format!("No image file at {}", path)));
+    }
+
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+    check_exercise(&tx, &exercise_id)?;
+
+    let added = tx.execute(
+        "INSERT OR IGNORE INTO exercise_images (exercise_id, path, kind, position)
+         SELECT ?1, ?2, ?3, COALESCE(MAX(position) + 1, 0) FROM exercise_images WHERE exercise_id = ?1",
+        params![exercise_id, path, kind.as_str()],
+    )?;
+    if added == 0 {
+        return Err(AppError::InvalidInput(format!("{} is already attached to this exercise", path)));
+    }
+    sync_image_columns(&tx, &exercise_id)?;
+    let images = load_images(&tx, &exercise_id)?;
+    tx.commit()?;
+
+    Ok(images)
+}
+
+/// Detaches an image from an exercise and deletes the file once no exercise, in
+/// the trash or not, uses it any more. Returns the remaining images.
+#[command]
+pub fn remove_exercise_image(
+    pool: State<'_, DbPool>,
+    exercise_id: String,
+    path: String,
+) -> Result<Vec<ExerciseImage>, AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+    check_exercise(&tx, &exercise_id)?;
+
+    let removed = tx.execute(
+        "DELETE FROM exercise_images WHERE exercise_id = ?1 AND path = ?2",
+        params![exercise_id, path],
+    )?;
+    if removed == 0 {
+        return Err(AppError::NotFound(format!("image {} of exercise {}", path, exercise_id)));
+    }
+    sync_image_columns(&tx, &exercise_id)?;
+    let images = load_images(&tx, &exercise_id)?;
+    let still_used: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM exercise_images WHERE path = ?1)",
+        params![path],
+        |row| row.get(0),
+    )?;
+    tx.commit()?;
+
+    if !still_used {
+        if let Some(thumbnail) = thumbnail_path(Path::new(&path)) {
+            let _ = fs::remove_file(thumbnail);
+        }
+        let _ = fs::remove_file(&path);
+    }
+    Ok(images)
+}
+
+/// Puts an exercise's images in the order of `paths`, which must list each of
+/// them once. The first image of each kind becomes its `imageUri`,
+/// `pageImageUri` or `solutionImageUri`. Returns the images.
+#[command]
+pub fn reorder_exercise_images(
+    pool: State<'_, DbPool>,
+    exercise_id: String,
+    paths: Vec<String>,
+) -> Result<Vec<ExerciseImage>, AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+    check_exercise(&tx, &exercise_id)?;
+
+    let current: HashSet<String> = load_images(&tx, &exercise_id)?.into_iter().map(|image| image.path).collect();
+    let requested: HashSet<&String> = paths.iter().collect();
+    if requested.len() != paths.len() || requested.len() != current.len() || !paths.iter().all(|path| current.contains(path)) {
+        return Err(AppError::InvalidInput(
+            "The new order must list each of the exercise's images exactly once".to_string(),
+        ));
+    }
+
+    for (position, path) in paths.iter().enumerate() {
+        tx.execute(
+            "UPDATE exercise_images SET position = ?3 WHERE exercise_id = ?1 AND path = ?2",
+            params![exercise_id, path, position as i64],
+        )?;
+    }
+    sync_image_columns(&tx, &exercise_id)?;
+    let images = load_images(&tx, &exercise_id)?;
+    tx.commit()?;
+
+    Ok(images)
+}
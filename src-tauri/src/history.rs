@@ -0,0 +1,108 @@
+use rusqlite::{params, Row};
+use serde::Serialize;
+use tauri::{command, State};
+
+use crate::db::{get_connection, set_setting, DbPool};
+use crate::error::AppError;
+
+/// Read by the `exercise_history_prune` trigger; see migrations.
+const HISTORY_LIMIT_SETTING: &str = "history_limit";
+
+/// A previous version of an exercise's text, recorded when it was edited or trashed.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    id: i64,
+    exercise_id: String,
+    name: String,
+    tags: Vec<String>,
+    notes: Option<String>,
+    content: String,
+    reason: String,
+    recorded_at: i64,
+}
+
+impl HistoryEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let tags_str: Option<String> = row.get(3)?;
+        Ok(HistoryEntry {
+            id: row.get(0)?,
+            exercise_id: row.get(1)?,
+            name: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            tags: tags_str
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+            notes: row.get(4)?,
+            content: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+            reason: row.get(6)?,
+            recorded_at: row.get(7)?,
+        })
+    }
+}
+
+/// Recorded versions of an exercise, newest first.
+#[command]
+pub fn get_exercise_history(
+    pool: State<'_, DbPool>,
+    exercise_id: String,
+) -> Result<Vec<HistoryEntry>, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, exercise_id, name, tags, notes, content, reason, recorded_at
+         FROM exercise_history WHERE exercise_id = ?1 ORDER BY id DESC",
+    )?;
+    let entries = stmt
+        .query_map(params![exercise_id], HistoryEntry::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(entries)
+}
+
+/// Restores the name, tags, notes and content recorded in a history entry. The
+/// version being replaced is itself recorded, so a revert can be undone.
+#[command]
+pub fn revert_exercise(
+    pool: State<'_, DbPool>,
+    exercise_id: String,
+    history_id: i64,
+) -> Result<(), AppError> {
+    let conn = get_connection(&pool)?;
+
+    let updated = conn.execute(
+        "UPDATE exercises SET (name, tags, notes, content) = (
+            SELECT name, tags, notes, content FROM exercise_history WHERE id = ?2 AND exercise_id = ?1
+        )
+        WHERE id = ?1 AND EXISTS (SELECT 1 FROM exercise_history WHERE id = ?2 AND exercise_id = ?1)",
+        params![exercise_id, history_id],
+    )?;
+    if updated == 0 {
+        return Err(AppError::NotFound(format!(
+            "history entry {} for exercise {}",
+            history_id, exercise_id
+        )));
+    }
+
+    Ok(())
+}
+
+/// Sets how many versions are kept per exercise and trims existing history to fit.
+#[command]
+pub fn set_history_limit(pool: State<'_, DbPool>, limit: u32) -> Result<(), AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    set_setting(&tx, HISTORY_LIMIT_SETTING, &limit.to_string())?;
+    tx.execute(
+        "DELETE FROM exercise_history WHERE id IN (
+            SELECT id FROM (
+                SELECT id, ROW_NUMBER() OVER (PARTITION BY exercise_id ORDER BY id DESC) AS position
+                FROM exercise_history
+            ) WHERE position > ?1
+        )",
+        params![limit],
+    )?;
+
+    tx.commit()?;
+    Ok(())
+}
@@ -0,0 +1,295 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::DynamicImage;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{command, AppHandle, Runtime, State};
+use tracing::info;
+
+use crate::backup::{image_data_url, write_atomically};
+use crate::db::{get_connection, get_images_dir, DbPool};
+use crate::error::AppError;
+use crate::models::ImageKind;
+
+/// Images are written before the exercise that refers to them is saved, so files
+/// this young may belong to a save still in progress.
+const MIN_ORPHAN_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// MIME type of PNG, JPEG or WebP bytes, told from their signature rather than
+/// any name or data URL prefix they came with. `None` for anything else.
+pub fn sniff_mime_type(bytes: &[u8]) -> Option<&'static str> {
+    match image::guess_format(bytes).ok()? {
+        image::ImageFormat::Png => Some("image/png"),
+        image::ImageFormat::Jpeg => Some("image/jpeg"),
+        image::ImageFormat::WebP => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Encoding for images saved with `save_image`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SaveFormat {
+    #[default]
+    Png,
+    Webp,
+    Jpeg,
+}
+
+impl SaveFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SaveFormat::Png => "png",
+            SaveFormat::Webp => "webp",
+            SaveFormat::Jpeg => "jpg",
+        }
+    }
+
+    /// Decodes `data` and re-encodes it in this format, JPEG at `jpeg_quality`
+    /// (1–100). The WebP and JPEG encoders only take 8-bit pixels, and JPEG has no
+    /// alpha channel, so transparency is dropped for it.
+    pub fn encode(self, data: &[u8], jpeg_quality: u8) -> Result<Vec<u8>, AppError> {
+        let decoded = image::load_from_memory(data)?;
+        let mut encoded = Cursor::new(Vec::new());
+        match self {
+            SaveFormat::Png => decoded.write_to(&mut encoded, image::ImageFormat::Png)?,
+            SaveFormat::Webp => {
+                DynamicImage::from(decoded.to_rgba8()).write_to(&mut encoded, image::ImageFormat::WebP)?
+            }
+            SaveFormat::Jpeg => {
+                JpegEncoder::new_with_quality(&mut encoded, jpeg_quality).encode_image(&decoded.to_rgb8())?
+            }
+        }
+        Ok(encoded.into_inner())
+    }
+}
+
+/// Where the cached thumbnail of `image` lives: `<name>.thumb.png` next to it.
+pub fn thumbnail_path(image: &Path) -> Option<PathBuf> {
+    let stem = image.file_stem()?.to_string_lossy();
+    Some(image.with_file_name(format!("{}.thumb.png", stem)))
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrphanReport {
+    /// Image files no exercise refers to.
+    pub files: Vec<String>,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: u64,
+    /// Whether the files were removed or this was a dry run.
+    pub deleted: bool,
+}
+
+/// Image files no exercise (trashed ones included) refers to. Thumbnails count as
+/// referenced while their source image is.
+pub fn find_orphaned_images(conn: &Connection, images_dir: &Path) -> Result<OrphanReport, AppError> {
+    // Match on file name alone: names are unique UUIDs, and stored paths may spell
+    // the images dir differently than we do now
+    let referenced: HashSet<OsString> = conn
+        .prepare(
+            "SELECT DISTINCT path FROM exercise_images",
+        )?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .flat_map(|path| {
+            let path = Path::new(path);
+            [Some(path.to_path_buf()), thumbnail_path(path)]
+        })
+        .flatten()
+        .filter_map(|path| path.file_name().map(|name| name.to_owned()))
+        .collect();
+
+    let mut report = OrphanReport {
+        files: Vec::new(),
+        total_bytes: 0,
+        deleted: false,
+    };
+    for entry in fs::read_dir(images_dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let recent = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age < MIN_ORPHAN_AGE);
+        if metadata.is_file() && !recent && !referenced.contains(&entry.file_name()) {
+            report.files.push(entry.path().to_string_lossy().into_owned());
+            report.total_bytes += metadata.len();
+        }
+    }
+    report.files.sort();
+
+    Ok(report)
+}
+
+pub fn delete_orphaned_images(report: &mut OrphanReport) -> Result<(), AppError> {
+    for file in &report.files {
+        fs::remove_file(file)?;
+    }
+    report.deleted = true;
+    info!(
+        "Removed {} orphaned images ({} bytes)",
+        report.files.len(),
+        report.total_bytes
+    );
+    Ok(())
+}
+
+/// Finds orphaned image files and deletes them when `confirm` is set. Without it
+/// nothing is touched, so the caller can show the list first.
+#[command]
+pub fn cleanup_orphaned_images<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    confirm: Option<bool>,
+) -> Result<OrphanReport, AppError> {
+    let conn = get_connection(&pool)?;
+    let mut report = find_orphaned_images(&conn, &get_images_dir(&app)?)?;
+
+    if confirm.unwrap_or(false) {
+        delete_orphaned_images(&mut report)?;
+    }
+
+    Ok(report)
+}
+
+/// What is wrong with an image an exercise refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageProblem {
+    Missing,
+    /// The file is there but doesn't decode as an image.
+    Corrupt,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokenImageRef {
+    pub exercise_id: String,
+    pub exercise_name: String,
+    pub path: String,
+    pub kind: ImageKind,
+    pub problem: ImageProblem,
+}
+
+/// Checks that every image of the exercises outside the trash exists and decodes,
+/// and lists the ones that don't, by course and week. Nothing is changed; clearing
+/// or replacing a reference is up to the caller. Each file is decoded once, however
+/// many exercises share it.
+#[command]
+pub fn verify_images(pool: State<'_, DbPool>) -> Result<Vec<BrokenImageRef>, AppError> {
+    let conn = get_connection(&pool)?;
+    let mut stmt = conn.prepare(
+        "SELECT e.id, e.name, i.path, i.kind FROM exercise_images i JOIN exercises e ON e.id = i.exercise_id
+         WHERE e.deleted_at IS NULL
+         ORDER BY e.course COLLATE NATURAL_ORDER, e.course, e.week, e.order_index, e.created_at, i.position",
+    )?;
+    let mut rows = stmt.query([])?;
+
+    let mut checked: HashMap<String, Option<ImageProblem>> = HashMap::new();
+    let mut broken = Vec::new();
+    while let Some(row) = rows.next()? {
+        let path: String = row.get(2)?;
+        let problem = *checked.entry(path.clone()).or_insert_with(|| {
+            let file = Path::new(&path);
+            if !file.is_file() {
+                Some(ImageProblem::Missing)
+            } else if image::open(file).is_err() {
+                Some(ImageProblem::Corrupt)
+            } else {
+                None
+            }
+        });
+        let Some(problem) = problem else {
+            continue;
+        };
+        broken.push(BrokenImageRef {
+            exercise_id: row.get(0)?,
+            exercise_name: row.get(1)?,
+            path,
+            kind: ImageKind::from_column(&row.get::<_, String>(3)?),
+            problem,
+        });
+    }
+
+    if !broken.is_empty() {
+        info!("Found {} broken image references", broken.len());
+    }
+    Ok(broken)
+}
+
+/// Returns a data URL of `relative_path` scaled down to at most `max_width` pixels
+/// wide. The thumbnail is cached next to the original and regenerated when the
+/// original is newer or was cached at a different width. Images already narrow
+/// enough are returned as they are.
+#[command]
+pub fn get_image_thumbnail<R: Runtime>(
+    app: AppHandle<R>,
+    relative_path: String,
+    max_width: u32,
+) -> Result<String, AppError> {
+    if max_width == 0 {
+        return Err(AppError::InvalidInput("Thumbnail width must be positive".to_string()));
+    }
+
+    // Only the file name is used, so stored absolute paths work as well and nothing
+    // outside the images dir can be read
+    let file_name = Path::new(&relative_path)
+        .file_name()
+        .ok_or_else(|| AppError::InvalidInput(format!("Not an image path: {}", relative_path)))?;
+    let source = get_images_dir(&app)?.join(file_name);
+    let not_found = || AppError::NotFound(format!("image {}", relative_path));
+    if !source.is_file() {
+        return Err(not_found());
+    }
+
+    let (width, _) = image::image_dimensions(&source)?;
+    if width <= max_width {
+        return image_data_url(&source.to_string_lossy()).ok_or_else(not_found);
+    }
+
+    let thumbnail = thumbnail_path(&source).ok_or_else(not_found)?;
+    let source_modified = fs::metadata(&source)?.modified()?;
+    let fresh = fs::metadata(&thumbnail)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| modified >= source_modified)
+        && image::image_dimensions(&thumbnail).is_ok_and(|(cached_width, _)| cached_width == max_width);
+
+    if !fresh {
+        let scaled = image::open(&source)?.thumbnail(max_width, u32::MAX);
+        write_atomically(&thumbnail, |temp_path| {
+            Ok(scaled.save_with_format(temp_path, image::ImageFormat::Png)?)
+        })?;
+    }
+
+    image_data_url(&thumbnail.to_string_lossy()).ok_or_else(not_found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniff_mime_type_reads_the_signature() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let jpeg = b"\xff\xd8\xff\xe0\0\x10JFIF\0";
+        let webp = b"RIFF\x24\0\0\0WEBPVP8 ";
+        assert_eq!(sniff_mime_type(png), Some("image/png"));
+        assert_eq!(sniff_mime_type(jpeg), Some("image/jpeg"));
+        assert_eq!(sniff_mime_type(webp), Some("image/webp"));
+    }
+
+    #[test]
+    fn sniff_mime_type_rejects_anything_else() {
+        assert_eq!(sniff_mime_type(b""), None);
+        assert_eq!(sniff_mime_type(b"not an image at all"), None);
+        assert_eq!(sniff_mime_type(b"%PDF-1.7\n"), None);
+        assert_eq!(sniff_mime_type(b"GIF89a\x01\0\x01\0"), None);
+    }
+}
@@ -0,0 +1,163 @@
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+use crate::db::{get_connection, DbPool};
+use crate::error::AppError;
+use crate::models::{Exercise, EXERCISE_COLUMNS};
+
+/// How the exercise a link starts from relates to the one it points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkRelation {
+    Related,
+    /// The target has to be done first.
+    Prerequisite,
+    /// The source is the same exercise as the target, e.g. from another sheet.
+    DuplicateOf,
+}
+
+impl LinkRelation {
+    /// The value stored in the `relation` column.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LinkRelation::Related => "related",
+            LinkRelation::Prerequisite => "prerequisite",
+            LinkRelation::DuplicateOf => "duplicate-of",
+        }
+    }
+
+    fn from_column(value: &str) -> Self {
+        match value {
+            "prerequisite" => LinkRelation::Prerequisite,
+            "duplicate-of" => LinkRelation::DuplicateOf,
+            _ => LinkRelation::Related,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExerciseLink {
+    pub from_id: String,
+    pub to_id: String,
+    pub relation: LinkRelation,
+}
+
+/// Which end of the link the exercise asked about is.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkDirection {
+    /// The link starts at the exercise asked about.
+    Outgoing,
+    Incoming,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LinkedExercise {
+    pub exercise: Exercise,
+    pub relation: LinkRelation,
+    pub direction: LinkDirection,
+}
+
+/// Links between the given exercises only, for exports.
+pub fn links_between(conn: &Connection, ids: &HashSet<String>) -> Result<Vec<ExerciseLink>, AppError> {
+    let mut stmt = conn.prepare("SELECT from_id, to_id, relation FROM exercise_links ORDER BY linked_at, from_id, to_id")?;
+    let links = stmt
+        .query_map([], |row| {
+            Ok(ExerciseLink {
+                from_id: row.get(0)?,
+                to_id: row.get(1)?,
+                relation: LinkRelation::from_column(&row.get::<_, String>(2)?),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(links
+        .into_iter()
+        .filter(|link| ids.contains(&link.from_id) && ids.contains(&link.to_id))
+        .collect())
+}
+
+/// Stores a link, replacing the relation of an existing one between the same
+/// exercises in the same direction. Self-links are rejected.
+pub fn insert_link(conn: &Connection, link: &ExerciseLink) -> Result<(), AppError> {
+    if link.from_id == link.to_id {
+        return Err(AppError::InvalidInput("An exercise can't be linked to itself".to_string()));
+    }
+    conn.execute(
+        "INSERT INTO exercise_links (from_id, to_id, relation, linked_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (from_id, to_id) DO UPDATE SET relation = excluded.relation",
+        params![link.from_id, link.to_id, link.relation.as_str(), chrono::Utc::now().timestamp_millis()],
+    )?;
+    Ok(())
+}
+
+/// Links `from_id` to `to_id`, both outside the trash. Linking the same pair in
+/// the same direction again changes the relation.
+#[command]
+pub fn link_exercises(
+    pool: State<'_, DbPool>,
+    from_id: String,
+    to_id: String,
+    relation: LinkRelation,
+) -> Result<(), AppError> {
+    let conn = get_connection(&pool)?;
+    for id in [&from_id, &to_id] {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM exercises WHERE id = ?1 AND deleted_at IS NULL)",
+            params![id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Err(AppError::NotFound(format!("exercise {}", id)));
+        }
+    }
+
+    insert_link(&conn, &ExerciseLink { from_id, to_id, relation })
+}
+
+/// Removes the links between two exercises, in either direction.
+#[command]
+pub fn unlink_exercises(pool: State<'_, DbPool>, from_id: String, to_id: String) -> Result<(), AppError> {
+    let conn = get_connection(&pool)?;
+    let removed = conn.execute(
+        "DELETE FROM exercise_links WHERE (from_id = ?1 AND to_id = ?2) OR (from_id = ?2 AND to_id = ?1)",
+        params![from_id, to_id],
+    )?;
+    if removed == 0 {
+        return Err(AppError::NotFound(format!("link between {} and {}", from_id, to_id)));
+    }
+    Ok(())
+}
+
+/// The exercises linked to `id` in either direction, leaving out those in the
+/// trash. Links from `id` come first.
+#[command]
+pub fn get_linked_exercises(pool: State<'_, DbPool>, id: String) -> Result<Vec<LinkedExercise>, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {columns}, l.relation, l.from_id = ?1 AS outgoing
+         FROM exercise_links l JOIN exercises ON exercises.id = CASE WHEN l.from_id = ?1 THEN l.to_id ELSE l.from_id END
+         WHERE (l.from_id = ?1 OR l.to_id = ?1) AND exercises.deleted_at IS NULL
+         ORDER BY outgoing DESC, l.linked_at, exercises.id",
+        columns = EXERCISE_COLUMNS
+    ))?;
+    let linked = stmt
+        .query_map(params![id], |row| {
+            Ok(LinkedExercise {
+                exercise: Exercise::from_row(row)?,
+                relation: LinkRelation::from_column(&row.get::<_, String>("relation")?),
+                direction: if row.get("outgoing")? {
+                    LinkDirection::Outgoing
+                } else {
+                    LinkDirection::Incoming
+                },
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(linked)
+}
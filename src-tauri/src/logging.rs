@@ -0,0 +1,53 @@
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+/// Daily log files kept in the logs dir; older ones are removed at startup.
+const KEEP_LOG_FILES: usize = 7;
+
+/// Used when `RUST_LOG` is unset. Release builds stay silent unless asked.
+const DEFAULT_FILTER: &str = if cfg!(debug_assertions) { "vaulty=debug" } else { "off" };
+
+/// Opens today's log file in `log_dir` and prunes old ones. Logs roll over per
+/// launch date rather than at midnight, which is plenty for a desktop app.
+fn open_log_file(log_dir: &Path) -> std::io::Result<File> {
+    fs::create_dir_all(log_dir)?;
+    let file_name = format!("vaulty-{}.log", chrono::Local::now().format("%Y-%m-%d"));
+    let file = OpenOptions::new().create(true).append(true).open(log_dir.join(&file_name))?;
+
+    let mut logs: Vec<_> = fs::read_dir(log_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("vaulty-") && name.ends_with(".log"))
+        .collect();
+    // Dates sort lexically, newest last
+    logs.sort();
+    for old in logs.iter().rev().skip(KEEP_LOG_FILES) {
+        let _ = fs::remove_file(log_dir.join(old));
+    }
+
+    Ok(file)
+}
+
+/// Sends logs to stderr and, if it can be opened, a daily file in `log_dir`.
+/// Verbosity comes from `RUST_LOG`, e.g. `RUST_LOG=vaulty=debug`.
+pub fn init(log_dir: &Path) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+
+    let file_layer = match open_log_file(log_dir) {
+        Ok(file) => Some(fmt::layer().with_ansi(false).with_writer(Mutex::new(file))),
+        Err(e) => {
+            eprintln!("Failed to open log file in {:?}: {}", log_dir, e);
+            None
+        }
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .with(file_layer)
+        .init();
+}
@@ -4,209 +4,563 @@
 )]
 
 use base64::{engine::general_purpose, Engine as _};
-use rusqlite::{params, Connection};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, OptionalExtension, Transaction};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use tauri::{command, AppHandle, Runtime};
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle, Manager, Runtime, State};
 use uuid::Uuid;
 use image::{DynamicImage, ImageBuffer, Rgba};
 use lopdf::Document;
+use pdfium_render::prelude::{PdfRenderConfig, Pdfium};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, instrument, warn};
+
+mod analysis;
+mod anki;
+mod attachments;
+mod attempts;
+mod backup;
+mod cost;
+mod courses;
+mod db;
+mod error;
+mod exercise_images;
+mod gemini;
+mod history;
+mod images;
+mod links;
+mod logging;
+mod maintenance;
+mod migrations;
+mod models;
+mod ocr;
+mod profiles;
+mod review;
+mod search;
+mod settings;
+mod sort;
+mod statistics;
+mod storage;
+mod tags;
+mod timer;
+mod trash;
+mod undo;
+mod vault;
+
+use analysis::AnalysisRegistry;
+use gemini::HttpClient;
+use db::{get_connection, get_images_dir, DbPool, StartupError};
+use error::AppError;
+use images::{thumbnail_path, SaveFormat};
+use models::{bounding_boxes_column, check_difficulty, BoundingBox, Exercise, ExercisePage, ExerciseStatus, ExerciseUpdate, ImageKind, EXERCISE_COLUMNS};
+use settings::Settings;
+use sort::SortBy;
+
+/// Writes a base64 image (a data URL or bare base64) into `images_dir` under a new
+/// name and returns its path. Without a `format` the image settings decide: PNG
+/// bytes are written as they are, other formats are re-encoded.
+fn write_image(
+    images_dir: &Path,
+    base64_data: &str,
+    format: Option<SaveFormat>,
+    settings: &Settings,
+) -> Result<PathBuf, AppError> {
+    // PNG is what the frontend sends, so only other formats need re-encoding
+    let format = format.or(Some(settings.image_format).filter(|format| *format != SaveFormat::Png));
+    let file_name = format!("{}.{}", Uuid::new_v4(), format.unwrap_or_default().extension());
+    let file_path = images_dir.join(&file_name);
 
-#[derive(Debug, Serialize, Deserialize)]
-struct BoundingBox {
-    y: f64,
-    height: f64,
+    // Handle data:image/png;base64, prefix if present
+    let base64_clean = if let Some(idx) = base64_data.find(',') {
+        debug!("Stripping data URI prefix");
+        &base64_data[idx + 1..]
+    } else {
+        base64_data
+    };
+
+    debug!("Clean base64 length: {}", base64_clean.len());
+
+    let data = general_purpose::STANDARD
+        .decode(base64_clean)
+        .map_err(|e| {
+            error!("Failed to decode base64: {}", e);
+            AppError::from(e)
+        })?;
+
+    debug!("Decoded {} bytes", data.len());
+
+    let data = match format {
+        Some(format) => format.encode(&data, settings.jpeg_quality)?,
+        None => data,
+    };
+
+    fs::write(&file_path, data).map_err(|e| {
+        error!("Failed to write file: {}", e);
+        AppError::from(e)
+    })?;
+
+    debug!("Image saved successfully");
+    Ok(file_path)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Exercise {
+/// Saves a base64 image into the images dir and returns its path. The bytes are
+/// written as they are unless `format`, or the `image_format` setting when it is
+/// left out, asks for them to be re-encoded; either way the file gets the
+/// extension of that format.
+#[command]
+#[instrument(skip_all, fields(format = ?format))]
+fn save_image<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    base64_data: String,
+    format: Option<SaveFormat>,
+) -> Result<String, AppError> {
+    let conn = get_connection(&pool)?;
+    let settings = Settings::load(&conn)?;
+    let file_path = write_image(&get_images_dir(&app)?, &base64_data, format, &settings)?;
+    Ok(file_path.to_string_lossy().into_owned())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatedExercise {
     id: String,
-    name: String,
-    tags: Vec<String>,
+    /// As stored in the exercise's `imageUri`.
+    image_path: String,
+}
+
+/// Saves an image and a new exercise using it in one step, appended to the end of
+/// its week. If the exercise can't be saved the image is deleted again, so a
+/// failure leaves neither behind.
+#[command]
+#[instrument(skip_all, fields(course = %course, week))]
+fn create_exercise_with_image<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
     course: String,
     week: i64,
-    content: Option<String>,
-    notes: Option<String>,
-    #[serde(rename = "imageUri")]
-    image_uri: Option<String>,
-    #[serde(rename = "pageImageUri")]
-    page_image_uri: Option<String>,
-    #[serde(rename = "boundingBox")]
-    bounding_box: Option<BoundingBox>,
-    #[serde(rename = "createdAt")]
-    created_at: i64,
+    name: String,
+    tags: Vec<String>,
+    image_data: String,
+) -> Result<CreatedExercise, AppError> {
+    if name.trim().is_empty() {
+        return Err(AppError::InvalidInput("Exercise name must not be empty".to_string()));
+    }
+    if week < 0 {
+        return Err(AppError::InvalidInput("Week numbers can't be negative".to_string()));
+    }
+
+    let conn = get_connection(&pool)?;
+    let image_path = write_image(&get_images_dir(&app)?, &image_data, None, &Settings::load(&conn)?)?;
+    let exercise = Exercise {
+        id: Uuid::new_v4().to_string(),
+        name,
+        tags: tags::normalize_tags(tags),
+        course,
+        week,
+        content: None,
+        notes: None,
+        image_uri: Some(image_path.to_string_lossy().into_owned()),
+        page_image_uri: None,
+        bounding_boxes: Vec::new(),
+        created_at: chrono::Utc::now().timestamp_millis(),
+        order_index: None,
+        source_page: None,
+        source_file: None,
+        deleted_at: None,
+        updated_at: None,
+        status: ExerciseStatus::default(),
+        difficulty: None,
+        is_favorite: false,
+        due_at: None,
+        review: None,
+        total_seconds: 0,
+        solution_text: None,
+        solution_image_uri: None,
+        images: Vec::new(),
+        parent_id: None,
+    };
+
+    if let Err(e) = exercise.upsert(&conn) {
+        error!("Failed to save exercise, removing its image: {}", e);
+        let _ = fs::remove_file(&image_path);
+        return Err(e);
+    }
+
+    Ok(CreatedExercise {
+        id: exercise.id,
+        image_path: image_path.to_string_lossy().into_owned(),
+    })
 }
 
-fn get_db_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, String> {
-    let path = app
-        .path_resolver()
-        .app_data_dir()
-        .ok_or_else(|| "Failed to get app data directory".to_string())?;
-    fs::create_dir_all(&path).map_err(|e| format!("Failed to create app data dir: {}", e))?;
-    Ok(path.join("vaulty.db"))
+/// Sets the worked solution of an exercise outside the trash. A given `text`
+/// replaces the solution text and a given `image_base64` the solution image, which
+/// is saved as `save_image` saves images; an empty string clears either one, and
+/// leaving one out keeps it. The replaced image file is deleted once the exercise
+/// is saved. Returns the updated exercise.
+#[command]
+#[instrument(skip_all, fields(id = %id))]
+fn save_solution<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    id: String,
+    text: Option<String>,
+    image_base64: Option<String>,
+) -> Result<Exercise, AppError> {
+    let mut conn = get_connection(&pool)?;
+    let settings = Settings::load(&conn)?;
+    let tx = conn.transaction()?;
+
+    let select = format!("SELECT {} FROM exercises WHERE id = ?1 AND deleted_at IS NULL", EXERCISE_COLUMNS);
+    let mut exercise = tx
+        .query_row(&select, params![id], Exercise::from_row)
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("exercise {}", id)))?;
+
+    if let Some(text) = text {
+        exercise.solution_text = Some(text).filter(|text| !text.trim().is_empty());
+    }
+    let mut new_image = None;
+    let mut old_image = None;
+    if let Some(data) = image_base64 {
+        if !data.trim().is_empty() {
+            new_image = Some(write_image(&get_images_dir(&app)?, &data, None, &settings)?);
+        }
+        old_image = exercise.solution_image_uri.take();
+        exercise.solution_image_uri = new_image.as_ref().map(|path| path.to_string_lossy().into_owned());
+    }
+
+    let result = (|| {
+        exercise.upsert(&tx)?;
+        let saved = tx.query_row(&select, params![id], Exercise::from_row)?;
+        tx.commit()?;
+        Ok(saved)
+    })();
+    if result.is_err() {
+        if let Some(path) = &new_image {
+            let _ = fs::remove_file(path);
+        }
+        return result;
+    }
+
+    if let Some(old_image) = old_image {
+        if let Some(thumbnail) = thumbnail_path(Path::new(&old_image)) {
+            let _ = fs::remove_file(thumbnail);
+        }
+        let _ = fs::remove_file(old_image);
+    }
+    result
 }
 
-fn get_images_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, String> {
-    let path = app
-        .path_resolver()
-        .app_data_dir()
-        .ok_or_else(|| "Failed to get app data directory".to_string())?
-        .join("images");
-    fs::create_dir_all(&path).map_err(|e| format!("Failed to create images dir: {}", e))?;
-    Ok(path)
+/// Stacks images top to bottom, left-aligned on a white background as wide as the
+/// widest of them.
+fn stack_vertically(images: &[DynamicImage]) -> DynamicImage {
+    let width = images.iter().map(DynamicImage::width).max().unwrap_or(0);
+    let height = images.iter().map(DynamicImage::height).sum();
+    let mut composite = ImageBuffer::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+    let mut top = 0;
+    for image in images {
+        image::imageops::overlay(&mut composite, &image.to_rgba8(), 0, top);
+        top += i64::from(image.height());
+    }
+    DynamicImage::ImageRgba8(composite)
 }
 
-fn init_db<R: Runtime>(app: &AppHandle<R>) -> Result<(), String> {
-    let db_path = get_db_path(app)?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+/// Cuts the full-width band described by `bbox` (in image pixels) out of a page.
+/// A band running past the bottom of the page is clamped to it.
+fn crop_band(page: &DynamicImage, bbox: &BoundingBox) -> Result<DynamicImage, AppError> {
+    if bbox.y < 0.0 || bbox.height < 0.0 {
+        return Err(AppError::InvalidInput(format!(
+            "Bounding box must not be negative (y: {}, height: {})",
+            bbox.y, bbox.height
+        )));
+    }
 
-    // Check if table exists and has correct schema
-    let table_info: Result<Vec<String>, _> = conn
-        .prepare("PRAGMA table_info(exercises)").map_err(|e| e.to_string())?
-        .query_map([], |row| row.get::<_, String>(1)).map_err(|e| e.to_string())?
-        .collect();
+    let top = bbox.y.round() as u32;
+    let bottom = ((bbox.y + bbox.height).round() as u32).min(page.height());
+    if top >= bottom {
+        return Err(AppError::InvalidInput(format!(
+            "Bounding box on page {} is outside the {}px tall page",
+            bbox.page,
+            page.height()
+        )));
+    }
+    Ok(page.crop_imm(0, top, page.width(), bottom - top))
+}
 
-    let columns = table_info.unwrap_or_default();
-
-    // If table doesn't have 'tags' column, drop and recreate
-    if !columns.is_empty() && !columns.contains(&"tags".to_string()) {
-        eprintln!("[DB] Old schema detected, dropping and recreating exercises table...");
-        conn.execute("DROP TABLE IF EXISTS exercises", []).map_err(|e| e.to_string())?;
-    }
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS exercises (
-            id TEXT PRIMARY KEY,
-            name TEXT,
-            tags TEXT,
-            course TEXT,
-            week INTEGER,
-            content TEXT,
-            notes TEXT,
-            image_path TEXT,
-            page_image_path TEXT,
-            bounding_box TEXT,
-            created_at INTEGER
-        )",
-        [],
-    ).map_err(|e| e.to_string())?;
-
-    // Add notes column if it doesn't exist (migration for existing databases)
-    if !columns.contains(&"notes".to_string()) && !columns.is_empty() {
-        eprintln!("[DB] Adding notes column to existing table...");
-        let _ = conn.execute("ALTER TABLE exercises ADD COLUMN notes TEXT", []);
-    }
-
-    eprintln!("[DB] Database initialized successfully");
-    Ok(())
+/// Crops each box's band out of its page image and saves them, stacked top to
+/// bottom, as a new PNG in the images dir. `page_images` maps page numbers to
+/// image paths; the boxes must be on consecutive pages, in order, so an exercise
+/// running over a page break comes out as one image. Bands narrower than the
+/// widest are padded with white on the right.
+#[command]
+fn crop_exercise_image<R: Runtime>(
+    app: AppHandle<R>,
+    page_images: HashMap<u32, String>,
+    bboxes: Vec<BoundingBox>,
+) -> Result<String, AppError> {
+    if bboxes.is_empty() {
+        return Err(AppError::InvalidInput("No bounding box to crop".to_string()));
+    }
+    if bboxes.windows(2).any(|pair| pair[1].page != pair[0].page + 1) {
+        return Err(AppError::InvalidInput(
+            "Bounding boxes must be on consecutive pages, in order".to_string(),
+        ));
+    }
+
+    let mut bands = Vec::new();
+    for bbox in &bboxes {
+        let page_image_path = page_images
+            .get(&bbox.page)
+            .ok_or_else(|| AppError::InvalidInput(format!("No image given for page {}", bbox.page)))?;
+        bands.push(crop_band(&image::open(page_image_path)?, bbox)?);
+    }
+
+    let crop = match bands.as_slice() {
+        [band] => band.clone(),
+        _ => stack_vertically(&bands),
+    };
+    let file_path = get_images_dir(&app)?.join(format!("{}.png", Uuid::new_v4()));
+    crop.save_with_format(&file_path, image::ImageFormat::Png)?;
+
+    debug!(
+        "Saved {}px crop of pages {}-{} to {:?}",
+        crop.height(),
+        bboxes[0].page,
+        bboxes[bboxes.len() - 1].page,
+        file_path
+    );
+    Ok(file_path.to_string_lossy().into_owned())
 }
 
+/// Lists exercises, optionally filtered by course, week, status and minimum
+/// difficulty, sorted and paged. With no arguments every exercise is returned
+/// ordered by course then week.
+///
+/// Offset paging is only stable with an ordering that doesn't tie, which in
+/// practice means one ending in `order_index` such as the default sort.
 #[command]
-fn save_image<R: Runtime>(app: AppHandle<R>, base64_data: String) -> Result<String, String> {
-    let images_dir = get_images_dir(&app)?;
-    let file_name = format!("{}.png", Uuid::new_v4());
-    let file_path = images_dir.join(&file_name);
+#[allow(clippy::too_many_arguments)]
+fn get_all_exercises(
+    pool: State<'_, DbPool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    course: Option<String>,
+    sort_by: Option<SortBy>,
+    include_archived: Option<bool>,
+    week: Option<i64>,
+    status: Option<ExerciseStatus>,
+    min_difficulty: Option<i64>,
+) -> Result<ExercisePage, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut conditions: Vec<&str> = vec!["deleted_at IS NULL"];
+    let mut args: Vec<Value> = Vec::new();
+    if let Some(course) = course {
+        conditions.push("course = ?");
+        args.push(Value::Text(course));
+    } else if !include_archived.unwrap_or(false) {
+        // Asking for a course by name gets it archived or not
+        conditions.push(courses::NOT_ARCHIVED);
+    }
+    if let Some(week) = week {
+        conditions.push("week = ?");
+        args.push(Value::Integer(week));
+    }
+    if let Some(status) = status {
+        conditions.push("status = ?");
+        args.push(Value::Text(status.as_str().to_string()));
+    }
+    if let Some(min_difficulty) = min_difficulty {
+        // Unrated exercises never match
+        conditions.push("difficulty >= ?");
+        args.push(Value::Integer(min_difficulty));
+    }
+    let where_clause = format!("WHERE {}", conditions.join(" AND "));
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM exercises {}", where_clause),
+        params_from_iter(&args),
+        |row| row.get(0),
+    )?;
+
+    // A negative LIMIT means no limit in SQLite
+    args.push(Value::Integer(limit.unwrap_or(-1)));
+    args.push(Value::Integer(offset.unwrap_or(0)));
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM exercises {} ORDER BY {} LIMIT ? OFFSET ?",
+        EXERCISE_COLUMNS,
+        where_clause,
+        sort_by.unwrap_or_default().order_by()
+    ))?;
+    let items = stmt
+        .query_map(params_from_iter(&args), Exercise::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ExercisePage { items, total })
+}
 
-    // Handle data:image/png;base64, prefix if present
-    let base64_clean = if let Some(idx) = base64_data.find(',') {
-        eprintln!("[RUST SAVE_IMAGE] Stripping data URI prefix");
-        &base64_data[idx + 1..]
-    } else {
-        &base64_data
+/// One exercise with everything stored for it, or `None` if there is no exercise
+/// with that id. Trashed exercises are returned with `deletedAt` set.
+#[command]
+fn get_exercise(pool: State<'_, DbPool>, id: String) -> Result<Option<Exercise>, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let exercise = conn
+        .query_row(
+            &format!("SELECT {} FROM exercises WHERE id = ?1", EXERCISE_COLUMNS),
+            params![id],
+            Exercise::from_row,
+        )
+        .optional()?;
+
+    Ok(exercise)
+}
+
+/// Reduces a source path to the file name stored in `source_file`, so exercises
+/// from the same sheet match wherever it was opened from. Blank names count as unset.
+fn source_file_name(source: &str) -> Option<String> {
+    let source = source.trim();
+    let name = Path::new(source).file_name().map_or(source.into(), |name| name.to_string_lossy());
+    (!name.is_empty()).then(|| name.into_owned())
+}
+
+/// Exercises outside the trash extracted from the PDF or image `file`, given as
+/// a path or file name, in page order.
+#[command]
+fn get_exercises_by_source(pool: State<'_, DbPool>, file: String) -> Result<Vec<Exercise>, AppError> {
+    let Some(file) = source_file_name(&file) else {
+        return Ok(Vec::new());
     };
+    let conn = get_connection(&pool)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM exercises WHERE source_file = ?1 AND deleted_at IS NULL
+         ORDER BY source_page NULLS LAST, {}",
+        EXERCISE_COLUMNS,
+        SortBy::CourseThenWeek.order_by()
+    ))?;
+    let exercises = stmt
+        .query_map(params![file], Exercise::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
 
-    eprintln!("[RUST SAVE_IMAGE] Clean base64 length: {}", base64_clean.len());
+    Ok(exercises)
+}
 
-    let data = general_purpose::STANDARD
-        .decode(base64_clean)
-        .map_err(|e| {
-            eprintln!("[RUST SAVE_IMAGE] ERROR: Failed to decode base64: {}", e);
-            e.to_string()
-        })?;
+/// The parts of an exercise outside the trash, in display order.
+#[command]
+fn get_exercise_children(pool: State<'_, DbPool>, id: String) -> Result<Vec<Exercise>, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM exercises WHERE parent_id = ?1 AND deleted_at IS NULL ORDER BY order_index, created_at, id",
+        EXERCISE_COLUMNS
+    ))?;
+    let children = stmt
+        .query_map(params![id], Exercise::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(children)
+}
 
-    eprintln!("[RUST SAVE_IMAGE] Decoded {} bytes", data.len());
+/// Starred exercises outside the trash, optionally only those of one course,
+/// ordered by course then week.
+#[command]
+fn get_favorites(pool: State<'_, DbPool>, course: Option<String>) -> Result<Vec<Exercise>, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM exercises
+         WHERE is_favorite = 1 AND deleted_at IS NULL AND (?1 IS NULL OR course = ?1)
+         ORDER BY {}",
+        EXERCISE_COLUMNS,
+        SortBy::CourseThenWeek.order_by()
+    ))?;
+    let favorites = stmt
+        .query_map(params![course], Exercise::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(favorites)
+}
 
-    fs::write(&file_path, data).map_err(|e| {
-        eprintln!("[RUST SAVE_IMAGE] ERROR: Failed to write file: {}", e);
-        e.to_string()
-    })?;
+/// Stars an exercise, or unstars it if it was starred. Returns whether it is now starred.
+#[command]
+fn toggle_favorite(pool: State<'_, DbPool>, id: String) -> Result<bool, AppError> {
+    let conn = get_connection(&pool)?;
 
-    eprintln!("[RUST SAVE_IMAGE] Image saved successfully");
-    Ok(file_path.to_string_lossy().into_owned())
+    conn.query_row(
+        "UPDATE exercises SET is_favorite = NOT is_favorite WHERE id = ?1 RETURNING is_favorite",
+        params![id],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or_else(|| AppError::NotFound(format!("exercise {}", id)))
 }
 
+/// Exercises changed after `timestamp` (Unix milliseconds), oldest change first.
+/// Trashed exercises are included with `deletedAt` set, so a sync can mirror deletes.
 #[command]
-fn get_all_exercises<R: Runtime>(app: AppHandle<R>) -> Result<Vec<Exercise>, String> {
-    let db_path = get_db_path(&app)?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-
-    let mut stmt = conn
-        .prepare("SELECT id, name, tags, course, week, content, notes, image_path, page_image_path, bounding_box, created_at FROM exercises")
-        .map_err(|e| e.to_string())?;
-
-    let exercise_iter = stmt
-        .query_map([], |row| {
-            let tags_str: String = row.get(2)?;
-            let tags: Vec<String> = serde_json::from_str(&tags_str).unwrap_or_default();
-
-            let bbox_str: Option<String> = row.get(9)?;
-            let bounding_box: Option<BoundingBox> = bbox_str
-                .and_then(|s| serde_json::from_str(&s).ok());
-
-            Ok(Exercise {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                tags,
-                course: row.get(3)?,
-                week: row.get(4)?,
-                content: row.get(5)?,
-                notes: row.get(6)?,
-                image_uri: row.get(7)?,
-                page_image_uri: row.get(8)?,
-                bounding_box,
-                created_at: row.get(10)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
+fn get_exercises_modified_since(pool: State<'_, DbPool>, timestamp: i64) -> Result<Vec<Exercise>, AppError> {
+    let conn = get_connection(&pool)?;
 
-    let mut exercises = Vec::new();
-    for exercise in exercise_iter {
-        exercises.push(exercise.map_err(|e| e.to_string())?);
-    }
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM exercises WHERE updated_at > ?1 ORDER BY updated_at, id",
+        EXERCISE_COLUMNS
+    ))?;
+    let exercises = stmt
+        .query_map(params![timestamp], Exercise::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(exercises)
 }
 
+/// A part's parent has to be another exercise outside the trash that isn't a part
+/// itself, and an exercise with parts of its own can't become a part.
+fn check_parent(conn: &rusqlite::Connection, exercise: &Exercise) -> Result<(), AppError> {
+    let Some(parent_id) = &exercise.parent_id else {
+        return Ok(());
+    };
+    if *parent_id == exercise.id {
+        return Err(AppError::InvalidInput("An exercise can't be a part of itself".to_string()));
+    }
+
+    let grandparent: Option<String> = conn
+        .query_row(
+            "SELECT parent_id FROM exercises WHERE id = ?1 AND deleted_at IS NULL",
+            params![parent_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("exercise {}", parent_id)))?;
+    let has_parts: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM exercises WHERE parent_id = ?1)",
+        params![exercise.id],
+        |row| row.get(0),
+    )?;
+    if grandparent.is_some() || has_parts {
+        return Err(AppError::InvalidInput("Parts of an exercise can't have parts of their own".to_string()));
+    }
+    Ok(())
+}
+
+/// Inserts or replaces an exercise. Its tags are normalized first, see
+/// `tags::normalize_tags`. A `parentId` makes it a part of that exercise.
 #[command]
-fn save_exercise<R: Runtime>(app: AppHandle<R>, exercise: Exercise) -> Result<(), String> {
-    let db_path = get_db_path(&app)?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-
-    let tags_str = serde_json::to_string(&exercise.tags).map_err(|e| e.to_string())?;
-    let bbox_str = serde_json::to_string(&exercise.bounding_box).map_err(|e| e.to_string())?;
-
-    conn.execute(
-        "INSERT OR REPLACE INTO exercises (id, name, tags, course, week, content, notes, image_path, page_image_path, bounding_box, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
-        params![
-            exercise.id,
-            exercise.name,
-            tags_str,
-            exercise.course,
-            exercise.week,
-            exercise.content,
-            exercise.notes,
-            exercise.image_uri,
-            exercise.page_image_uri,
-            bbox_str,
-            exercise.created_at,
-        ],
-    )
-    .map_err(|e| {
-        eprintln!("[RUST SAVE_EXERCISE] ERROR: Failed to execute insert: {}", e);
-        e.to_string()
-    })?;
+fn save_exercise(pool: State<'_, DbPool>, mut exercise: Exercise) -> Result<(), AppError> {
+    let conn = get_connection(&pool)?;
+    exercise.tags = tags::normalize_tags(exercise.tags);
+    exercise.source_file = exercise.source_file.as_deref().and_then(source_file_name);
+    check_parent(&conn, &exercise)?;
+
+    exercise
+        .upsert(&conn)
+        .inspect_err(|e| error!("Failed to execute insert: {}", e))?;
 
     Ok(())
 }
@@ -218,6 +572,11 @@ struct PartialExercise {
     tags: Vec<String>,
     #[serde(rename = "createdAt")]
     created_at: i64,
+    #[serde(rename = "sourcePage")]
+    source_page: Option<u32>,
+    /// The exercise's full problem statement as it appears on the page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -231,51 +590,111 @@ struct GeminiExercise {
     #[serde(rename = "exerciseType")]
     exercise_type: String,
     tags: Vec<String>,
+    #[serde(rename = "sourcePage", default)]
+    source_page: Option<u32>,
+    #[serde(default)]
+    content: Option<String>,
 }
 
-#[command]
-async fn analyze_page_image(base64_image: Option<String>, image_path: Option<String>, api_key: String) -> Result<Vec<PartialExercise>, String> {
-    eprintln!("[RUST ANALYZE] Starting analysis");
-    eprintln!("[RUST ANALYZE] base64_image provided: {}", base64_image.is_some());
-    eprintln!("[RUST ANALYZE] image_path provided: {:?}", image_path);
+/// The base64 payload of a `data:<mime>;base64,` URL, or `data` itself if it has
+/// no such prefix.
+fn strip_data_url(data: &str) -> &str {
+    data.strip_prefix("data:")
+        .and_then(|data_url| data_url.split_once(";base64,"))
+        .map_or(data, |(_, base64)| base64)
+}
 
-    let final_base64 = if let Some(b64) = base64_image {
-        eprintln!("[RUST ANALYZE] Using base64 image, length: {}", b64.len());
-        b64
-    } else if let Some(path) = image_path {
-        eprintln!("[RUST ANALYZE] Reading image from path: {}", path);
-        let data = fs::read(&path).map_err(|e| format!("Failed to read file: {}", e))?;
-        eprintln!("[RUST ANALYZE] Read {} bytes from file", data.len());
-        general_purpose::STANDARD.encode(data)
-    } else {
-        eprintln!("[RUST ANALYZE] ERROR: No image provided");
-        return Err("No image provided".to_string());
-    };
+/// Sends one image to Gemini and turns its answer into exercises. `page_count` as
+/// for `analyze_page_image`.
+async fn extract_with_gemini(
+    client: &reqwest::Client,
+    api_key: &str,
+    model: &str,
+    clean_base64: &str,
+    page_count: Option<u32>,
+) -> Result<Vec<PartialExercise>, AppError> {
+    let request_body = extraction_request(clean_base64, page_count)?;
 
-    // Clean base64 string if it contains metadata prefix
-    eprintln!("[RUST ANALYZE] Cleaning base64 prefix...");
-    let clean_base64 = final_base64
-        .strip_prefix("data:image/png;base64,")
-        .or_else(|| final_base64.strip_prefix("data:image/jpeg;base64,"))
-        .or_else(|| final_base64.strip_prefix("data:image/jpg;base64,"))
-        .or_else(|| final_base64.strip_prefix("data:image/webp;base64,"))
-        .unwrap_or(&final_base64);
+    debug!("Sending request to Gemini API...");
+    let response_json = gemini::generate_content(client, api_key, model, &request_body).await?;
+
+    debug!("Got response JSON");
 
-    eprintln!("[RUST ANALYZE] Clean base64 length: {}", clean_base64.len());
+    // Extract text from Gemini response
+    let text = response_json["candidates"][0]["content"]["parts"][0]["text"]
+        .as_str()
+        .ok_or_else(|| {
+            error!("No text in response");
+            AppError::Gemini("No text in response".to_string())
+        })?;
 
-    let client = reqwest::Client::new();
+    debug!("Extracted text from response: {}", text);
+
+    let gemini_response: GeminiExerciseResponse = serde_json::from_str(text)
+        .map_err(|e| {
+            error!("Failed to parse exercises: {}", e);
+            AppError::Gemini(format!("Failed to parse exercises: {}", e))
+        })?;
+
+    debug!("Parsed {} exercises", gemini_response.exercises.len());
+
+    // Convert to PartialExercise
+    let exercises: Vec<PartialExercise> = gemini_response.exercises.iter().map(|ex| {
+        let mut tags = vec![ex.exercise_type.clone()];
+        tags.extend(ex.tags.iter().cloned());
+        let tags = tags::normalize_tags(tags);
+
+        PartialExercise {
+            id: Uuid::new_v4().to_string(),
+            name: ex.name.clone(),
+            tags,
+            created_at: chrono::Utc::now().timestamp_millis(),
+            // A single page needs no attribution from the model
+            source_page: match page_count {
+                Some(1) => Some(1),
+                Some(pages) => ex.source_page.map(|p| p.clamp(1, pages)),
+                None => None,
+            },
+            content: ex.content.clone().filter(|content| !content.trim().is_empty()),
+        }
+    }).collect();
+
+    Ok(exercises)
+}
+
+/// The `generateContent` body asking Gemini for the exercises on one image, given
+/// as base64 or a data URL. The MIME type sent is sniffed from the bytes.
+fn extraction_request(image_base64: &str, page_count: Option<u32>) -> Result<serde_json::Value, AppError> {
+    let mut prompt = "Analyze this textbook/PDF page. Identify all distinct exercises or questions. For each exercise, provide:\n\n1. A 4-WORD NAME starting with the exercise number (e.g., 'Ex 1.2 Ridge Regression', 'Problem 5 Calculate MSE', 'Q3 Prove Convergence'). Format: [Exercise Number] [Task Description]. Maximum 4 words total. ALWAYS include the exercise number as the first part of the name.\n\n2. The type of exercise - must be EXACTLY one of: 'exercise', 'homework', or 'programming'\n\n3. Relevant topic tags - should be specific keywords about the concepts, techniques, or topics covered.\n\nIMPORTANT FORMATTING:\n- The 'exerciseType' field should contain ONLY: 'exercise', 'homework', or 'programming'\n- The 'tags' array should contain topic keywords ONLY (do NOT include the exercise type in tags)\n- The exercise type will be automatically added as the first tag by the system\n\n4. The full problem statement in 'content', transcribed verbatim including all sub-questions. Write formulas in LaTeX between $ signs. Leave out page headers, footers and other exercises.".to_string();
+    if let Some(pages) = page_count.filter(|&n| n > 1) {
+        prompt.push_str(&format!(
+            "\n\nThe image contains {} pages stacked vertically, top to bottom. Set 'sourcePage' to the 1-based number of the page each exercise starts on.",
+            pages
+        ));
+    }
+
+    // The prefix may not match the bytes (a JPEG saved under .png, say), so the
+    // bytes decide. 16 base64 characters cover the 12 bytes WebP needs.
+    let clean_base64 = strip_data_url(image_base64);
+    let header = general_purpose::STANDARD
+        .decode(&clean_base64.as_bytes()[..clean_base64.len().min(16)])
+        .unwrap_or_default();
+    let mime_type = images::sniff_mime_type(&header).ok_or_else(|| {
+        AppError::InvalidInput("Unsupported image format; analysis takes PNG, JPEG or WebP".to_string())
+    })?;
+    debug!("Sending image as {}", mime_type);
 
     let request_body = serde_json::json!({
         "contents": [{
             "parts": [
                 {
                     "inline_data": {
-                        "mime_type": "image/png",
+                        "mime_type": mime_type,
                         "data": clean_base64
                     }
                 },
                 {
-                    "text": "Analyze this textbook/PDF page. Identify all distinct exercises or questions. For each exercise, provide:\n\n1. A 4-WORD NAME starting with the exercise number (e.g., 'Ex 1.2 Ridge Regression', 'Problem 5 Calculate MSE', 'Q3 Prove Convergence'). Format: [Exercise Number] [Task Description]. Maximum 4 words total. ALWAYS include the exercise number as the first part of the name.\n\n2. The type of exercise - must be EXACTLY one of: 'exercise', 'homework', or 'programming'\n\n3. Relevant topic tags - should be specific keywords about the concepts, techniques, or topics covered.\n\nIMPORTANT FORMATTING:\n- The 'exerciseType' field should contain ONLY: 'exercise', 'homework', or 'programming'\n- The 'tags' array should contain topic keywords ONLY (do NOT include the exercise type in tags)\n- The exercise type will be automatically added as the first tag by the system"
+                    "text": prompt
                 }
             ]
         }],
@@ -301,9 +720,17 @@ async fn analyze_page_image(base64_image: Option<String>, image_path: Option<Str
                                     "type": "array",
                                     "items": {"type": "string"},
                                     "description": "Topic keywords only (e.g., 'ridge regression', 'regularization', 'linear algebra'). Do NOT include exercise type."
+                                },
+                                "sourcePage": {
+                                    "type": "integer",
+                                    "description": "1-based page number the exercise starts on, when the image holds several pages"
+                                },
+                                "content": {
+                                    "type": "string",
+                                    "description": "The full problem statement, verbatim, with formulas in LaTeX"
                                 }
                             },
-                            "required": ["name", "exerciseType", "tags"]
+                            "required": ["name", "exerciseType", "tags", "content"]
                         }
                     }
                 }
@@ -316,155 +743,1225 @@ async fn analyze_page_image(base64_image: Option<String>, image_path: Option<Str
         }
     });
 
-    eprintln!("[RUST ANALYZE] Sending request to Gemini API...");
-    let response = client
-        .post(format!("https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash:generateContent?key={}", api_key))
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| {
-            eprintln!("[RUST ANALYZE] ERROR: Failed to send request: {}", e);
-            format!("Failed to send request: {}", e)
-        })?;
-
-    eprintln!("[RUST ANALYZE] Response status: {}", response.status());
+    Ok(request_body)
+}
 
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        eprintln!("[RUST ANALYZE] ERROR: API request failed: {}", error_text);
-        return Err(format!("API request failed: {}", error_text));
+/// Extracts exercises from one image. `page_count` is the number of PDF pages
+/// stitched top to bottom into the image; when given, each exercise reports the
+/// 1-based page it starts on. Passing a `request_id` lets `cancel_analysis` abort
+/// the request while it is in flight. `model` defaults to the one chosen in settings.
+///
+/// With `use_local_ocr` the image is read with Tesseract instead and split at
+/// exercise headings; no API key is needed and the results come without tags.
+///
+/// The whole image goes out in one request, so `analysis-progress` reports
+/// `{ current: 0, total }` when it is sent and `{ current: total, total }` once
+/// the exercises are in, `total` being `page_count` (or 1). The `requestId` is
+/// passed along to tell concurrent analyses apart.
+#[command]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip_all, fields(request_id = ?request_id, page_count = ?page_count))]
+async fn analyze_page_image<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    registry: State<'_, AnalysisRegistry>,
+    http: State<'_, HttpClient>,
+    base64_image: Option<String>,
+    image_path: Option<String>,
+    api_key: String,
+    page_count: Option<u32>,
+    request_id: Option<String>,
+    model: Option<String>,
+    use_local_ocr: Option<bool>,
+) -> Result<Vec<PartialExercise>, AppError> {
+    debug!("Starting analysis");
+    let use_local_ocr = use_local_ocr.unwrap_or(false);
+    if api_key.trim().is_empty() && !use_local_ocr {
+        return Err(AppError::MissingApiKey);
     }
+    let model = match model {
+        Some(model) => model,
+        None => {
+            let conn = get_connection(&pool)?;
+            settings::default_model(&conn)?
+        }
+    };
+    gemini::validate_model(&model)?;
+    debug!("Using model {}", model);
+    debug!("base64_image provided: {}", base64_image.is_some());
+    debug!("image_path provided: {:?}", image_path);
 
-    let response_json: serde_json::Value = response.json().await
-        .map_err(|e| {
-            eprintln!("[RUST ANALYZE] ERROR: Failed to parse response: {}", e);
-            format!("Failed to parse response: {}", e)
-        })?;
+    let final_base64 = if let Some(b64) = base64_image {
+        debug!("Using base64 image, length: {}", b64.len());
+        b64
+    } else if let Some(path) = image_path {
+        debug!("Reading image from path: {}", path);
+        let data = fs::read(&path).map_err(|e| AppError::Io(format!("Failed to read file: {}", e)))?;
+        debug!("Read {} bytes from file", data.len());
+        general_purpose::STANDARD.encode(data)
+    } else {
+        error!("No image provided");
+        return Err(AppError::InvalidInput("No image provided".to_string()));
+    };
 
-    eprintln!("[RUST ANALYZE] Got response JSON");
+    // Clean base64 string if it contains metadata prefix
+    debug!("Cleaning base64 prefix...");
+    let clean_base64 = strip_data_url(&final_base64);
+
+    debug!("Clean base64 length: {}", clean_base64.len());
+
+    let total = page_count.unwrap_or(1);
+    let report = |current: u32| {
+        emit_progress(
+            &app,
+            ANALYSIS_PROGRESS_EVENT,
+            Progress { current, total, request_id: request_id.clone() },
+        )
+    };
 
-    // Extract text from Gemini response
-    let text = response_json["candidates"][0]["content"]["parts"][0]["text"]
-        .as_str()
-        .ok_or_else(|| {
-            eprintln!("[RUST ANALYZE] ERROR: No text in response");
-            "No text in response".to_string()
-        })?;
+    if use_local_ocr {
+        report(0);
+        let image = general_purpose::STANDARD.decode(clean_base64)?;
+        let created_at = chrono::Utc::now().timestamp_millis();
+        let exercises = tauri::async_runtime::spawn_blocking(move || ocr::extract_exercises(&image))
+            .await
+            .map_err(|e| AppError::Io(format!("OCR task failed: {}", e)))??;
+        debug!("Found {} exercises with local OCR", exercises.len());
+        report(total);
+
+        return Ok(exercises
+            .into_iter()
+            .map(|exercise| PartialExercise {
+                id: Uuid::new_v4().to_string(),
+                name: exercise.name,
+                tags: Vec::new(),
+                created_at,
+                // Stacked pages can't be told apart in plain text
+                source_page: (page_count == Some(1)).then_some(1),
+                content: Some(exercise.content),
+            })
+            .collect());
+    }
 
-    eprintln!("[RUST ANALYZE] Extracted text from response: {}", text);
+    let client = settings::http_client(&http, &pool)?;
+    report(0);
+    let registration = request_id.clone().map(|id| registry.register(id)).transpose()?;
+    let cancelled = async {
+        match &registration {
+            Some(registration) => registration.token.cancelled().await,
+            None => std::future::pending().await,
+        }
+    };
+    // Dropping the request future on cancel aborts the HTTP call and any retry backoff
+    let exercises = tokio::select! {
+        result = extract_with_gemini(&client, &api_key, &model, clean_base64, page_count) => result,
+        _ = cancelled => Err(AppError::Cancelled),
+    }
+    .inspect_err(|e| error!("Analysis failed: {}", e))?;
+    drop(registration);
 
-    let gemini_response: GeminiExerciseResponse = serde_json::from_str(text)
-        .map_err(|e| {
-            eprintln!("[RUST ANALYZE] ERROR: Failed to parse exercises: {}", e);
-            format!("Failed to parse exercises: {}", e)
-        })?;
+    debug!("Returning {} exercises", exercises.len());
+    report(total);
+    Ok(exercises)
+}
 
-    eprintln!("[RUST ANALYZE] Parsed {} exercises", gemini_response.exercises.len());
+/// Most chunks `analyze_pages` has in flight at once.
+const MAX_CONCURRENT_CHUNKS: usize = 3;
+
+/// Extracts exercises from the rendered pages of a PDF, for documents too long to
+/// send as one stacked image. The pages go to Gemini in chunks of `chunk_size`
+/// (by default the `analysis_chunk_pages` setting), each stacked into one image,
+/// with at most three requests in flight. Exercises come back in page order with
+/// `sourcePage` counted from the first of `image_paths`. One failed chunk fails
+/// the whole analysis.
+///
+/// `analysis-progress` reports the number of pages whose chunk is done, out of
+/// all pages. `request_id` and `model` work as for `analyze_page_image`.
+#[command]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip_all, fields(request_id = ?request_id, pages = image_paths.len()))]
+async fn analyze_pages<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    registry: State<'_, AnalysisRegistry>,
+    http: State<'_, HttpClient>,
+    image_paths: Vec<String>,
+    api_key: String,
+    chunk_size: Option<u32>,
+    request_id: Option<String>,
+    model: Option<String>,
+) -> Result<Vec<PartialExercise>, AppError> {
+    if api_key.trim().is_empty() {
+        return Err(AppError::MissingApiKey);
+    }
+    if image_paths.is_empty() {
+        return Err(AppError::InvalidInput("No pages to analyze".to_string()));
+    }
+    let (model, chunk_size) = {
+        let conn = get_connection(&pool)?;
+        let model = match model {
+            Some(model) => model,
+            None => settings::default_model(&conn)?,
+        };
+        let chunk_size = match chunk_size {
+            Some(size) => size,
+            None => Settings::load(&conn)?.analysis_chunk_pages,
+        };
+        (model, chunk_size)
+    };
+    gemini::validate_model(&model)?;
+    if chunk_size == 0 {
+        return Err(AppError::InvalidInput("Chunk size must be at least 1 page".to_string()));
+    }
 
-    // Convert to PartialExercise
-    let exercises: Vec<PartialExercise> = gemini_response.exercises.iter().map(|ex| {
-        let mut tags = vec![ex.exercise_type.clone()];
-        tags.extend(ex.tags.iter().cloned());
-        // Remove duplicates
-        tags.sort();
-        tags.dedup();
+    let total = image_paths.len() as u32;
+    let report = |current: u32| {
+        emit_progress(
+            &app,
+            ANALYSIS_PROGRESS_EVENT,
+            Progress { current, total, request_id: request_id.clone() },
+        )
+    };
 
-        PartialExercise {
-            id: Uuid::new_v4().to_string(),
-            name: ex.name.clone(),
-            tags,
-            created_at: chrono::Utc::now().timestamp_millis(),
+    let client = settings::http_client(&http, &pool)?;
+    // Before any chunk starts, so a duplicate id is turned away without sending anything
+    let registration = request_id.clone().map(|id| registry.register(id)).transpose()?;
+    let chunks: Vec<Vec<String>> = image_paths.chunks(chunk_size as usize).map(<[String]>::to_vec).collect();
+    debug!("Analyzing {} pages in {} chunks with {}", total, chunks.len(), model);
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_CHUNKS));
+    let mut tasks = JoinSet::new();
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let (permits, client) = (permits.clone(), client.clone());
+        let (api_key, model) = (api_key.clone(), model.clone());
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await.map_err(|e| AppError::Io(e.to_string()))?;
+            let pages = chunk.len() as u32;
+            let stacked = tauri::async_runtime::spawn_blocking(move || -> Result<String, AppError> {
+                let images = chunk.iter().map(image::open).collect::<Result<Vec<_>, _>>()?;
+                let mut png = std::io::Cursor::new(Vec::new());
+                stack_vertically(&images).write_to(&mut png, image::ImageFormat::Png)?;
+                Ok(general_purpose::STANDARD.encode(png.into_inner()))
+            })
+            .await
+            .map_err(|e| AppError::Io(format!("Stacking pages failed: {}", e)))??;
+            let exercises = extract_with_gemini(&client, &api_key, &model, &stacked, Some(pages)).await?;
+            Ok::<_, AppError>((index, pages, exercises))
+        });
+    }
+
+    report(0);
+    let cancelled = async {
+        match &registration {
+            Some(registration) => registration.token.cancelled().await,
+            None => std::future::pending().await,
         }
-    }).collect();
+    };
+    let collect = async {
+        let mut results = Vec::new();
+        let mut done = 0;
+        while let Some(joined) = tasks.join_next().await {
+            let (index, pages, exercises) =
+                joined.map_err(|e| AppError::Io(format!("Analysis task failed: {}", e)))??;
+            done += pages;
+            report(done);
+            results.push((index, exercises));
+        }
+        Ok(results)
+    };
+    // Returning early drops the JoinSet, which aborts the chunks still running
+    let mut results = tokio::select! {
+        result = collect => result,
+        _ = cancelled => Err(AppError::Cancelled),
+    }
+    .inspect_err(|e| error!("Analysis failed: {}", e))?;
+    drop(registration);
+
+    results.sort_by_key(|(index, _)| *index);
+    let exercises: Vec<PartialExercise> = results
+        .into_iter()
+        .flat_map(|(index, exercises)| {
+            let first_page = index as u32 * chunk_size;
+            exercises.into_iter().map(move |mut exercise| {
+                exercise.source_page = exercise.source_page.map(|page| first_page + page);
+                exercise
+            })
+        })
+        .collect();
 
-    eprintln!("[RUST ANALYZE] Returning {} exercises", exercises.len());
+    debug!("Returning {} exercises", exercises.len());
     Ok(exercises)
 }
 
+/// Moves an exercise to the trash. An exercise with parts outside the trash is
+/// only trashed with `cascade`, which trashes the parts along with it, undoable
+/// together.
 #[command]
-fn delete_exercise<R: Runtime>(app: AppHandle<R>, id: String) -> Result<(), String> {
-    let db_path = get_db_path(&app)?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-
-    // First get the image paths to delete files
-    let mut stmt = conn
-        .prepare("SELECT image_path, page_image_path FROM exercises WHERE id = ?1")
-        .map_err(|e| e.to_string())?;
-    
-    let paths: Option<(Option<String>, Option<String>)> = stmt
-        .query_row(params![id], |row| {
-            Ok((row.get(0)?, row.get(1)?))
-        })
-        .ok();
+fn delete_exercise(pool: State<'_, DbPool>, id: String, cascade: Option<bool>) -> Result<(), AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+    let deleted_at = chrono::Utc::now().timestamp_millis();
+
+    let parts: Vec<String> = tx
+        .prepare("SELECT id FROM exercises WHERE parent_id = ?1 AND deleted_at IS NULL")?
+        .query_map(params![id], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    if !parts.is_empty() && !cascade.unwrap_or(false) {
+        return Err(AppError::InvalidInput(format!(
+            "This exercise has {} parts; delete them first or delete with cascade",
+            parts.len()
+        )));
+    }
 
-    if let Some((img_path, _page_img_path)) = paths {
-        if let Some(p) = img_path {
-            let _ = fs::remove_file(p);
+    // Moves the exercise to the trash; files stay on disk until the trash is purged
+    let trashed = tx.execute(
+        "UPDATE exercises SET deleted_at = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+        params![id, deleted_at],
+    )?;
+    if trashed > 0 {
+        let mut ids = vec![id.clone()];
+        for part in parts {
+            tx.execute("UPDATE exercises SET deleted_at = ?2 WHERE id = ?1", params![part, deleted_at])?;
+            ids.push(part);
         }
+        undo::record_operation(&tx, "delete_exercise", &id, deleted_at, &ids)?;
     }
 
-    conn.execute("DELETE FROM exercises WHERE id = ?1", params![id])
-        .map_err(|e| e.to_string())?;
-
+    tx.commit()?;
     Ok(())
 }
 
+#[derive(Debug, Serialize)]
+struct BulkDeleteResult {
+    id: String,
+    deleted: bool,
+    /// Why the exercise wasn't trashed: it doesn't exist, already was, or has
+    /// parts that would be left behind.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+/// Moves several exercises to the trash in one transaction, undoable as a single
+/// operation, and reports per id whether it was trashed. Unknown ids don't fail
+/// the batch. As with `delete_exercise`, image files stay until the trash is purged,
+/// and an exercise whose parts aren't all listed is only trashed with `cascade`,
+/// which takes the parts along.
 #[command]
-fn delete_course<R: Runtime>(app: AppHandle<R>, course: String) -> Result<(), String> {
-    let db_path = get_db_path(&app)?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+fn bulk_delete_exercises(
+    pool: State<'_, DbPool>,
+    ids: Vec<String>,
+    cascade: Option<bool>,
+) -> Result<Vec<BulkDeleteResult>, AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+    let deleted_at = chrono::Utc::now().timestamp_millis();
+    let listed: HashSet<String> = ids.iter().cloned().collect();
+
+    let mut results = Vec::with_capacity(ids.len());
+    let mut trashed_ids = Vec::new();
+    for id in ids {
+        let state: Option<Option<i64>> = tx
+            .query_row("SELECT deleted_at FROM exercises WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?;
+        let reason = match state {
+            None => Some("not found".to_string()),
+            Some(Some(_)) => Some("already in the trash".to_string()),
+            Some(None) => {
+                // Listed parts are trashed in their own turn
+                let parts: Vec<String> = tx
+                    .prepare("SELECT id FROM exercises WHERE parent_id = ?1 AND deleted_at IS NULL")?
+                    .query_map(params![id], |row| row.get(0))?
+                    .filter(|part| part.as_ref().map_or(true, |part| !listed.contains(part)))
+                    .collect::<Result<_, _>>()?;
+                if !parts.is_empty() && !cascade.unwrap_or(false) {
+                    Some(format!("has {} parts; list them too or delete with cascade", parts.len()))
+                } else {
+                    for id in std::iter::once(&id).chain(&parts) {
+                        tx.execute("UPDATE exercises SET deleted_at = ?2 WHERE id = ?1", params![id, deleted_at])?;
+                    }
+                    trashed_ids.push(id.clone());
+                    trashed_ids.extend(parts);
+                    None
+                }
+            }
+        };
+        results.push(BulkDeleteResult {
+            deleted: reason.is_none(),
+            id,
+            reason,
+        });
+    }
+    let target = format!("{} exercises", trashed_ids.len());
+    undo::record_operation(&tx, "bulk_delete_exercises", &target, deleted_at, &trashed_ids)?;
 
-    // First get all image paths from exercises in this course to delete files
-    let mut stmt = conn
-        .prepare("SELECT image_path, page_image_path FROM exercises WHERE course = ?1")
-        .map_err(|e| e.to_string())?;
-    
-    let mut rows = stmt.query(params![course]).map_err(|e| e.to_string())?;
-    
-    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
-        let img_path: Option<String> = row.get(0).ok();
-        let _page_img_path: Option<String> = row.get(1).ok();
-        
-        if let Some(p) = img_path {
-            let _ = fs::remove_file(p);
-        }
+    tx.commit()?;
+    info!("Trashed {} of {} exercises", results.iter().filter(|result| result.deleted).count(), results.len());
+    Ok(results)
+}
+
+/// Moves every exercise of a course to the trash. A course with no exercises
+/// outside the trash is reported as not found.
+#[command]
+fn delete_course(pool: State<'_, DbPool>, course: String) -> Result<(), AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+    let deleted_at = chrono::Utc::now().timestamp_millis();
+
+    let ids: Vec<String> = tx
+        .prepare("SELECT id FROM exercises WHERE course = ?1 AND deleted_at IS NULL")?
+        .query_map(params![course], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    if ids.is_empty() {
+        return Err(AppError::NotFound(format!("course {}", course)));
     }
 
-    // Delete all exercises for this course
-    conn.execute("DELETE FROM exercises WHERE course = ?1", params![course])
-        .map_err(|e| e.to_string())?;
+    // Trash every exercise in the course; each can be restored individually
+    tx.execute(
+        "UPDATE exercises SET deleted_at = ?2 WHERE course = ?1 AND deleted_at IS NULL",
+        params![course, deleted_at],
+    )?;
+    undo::record_operation(&tx, "delete_course", &course, deleted_at, &ids)?;
 
+    tx.commit()?;
     Ok(())
 }
 
+/// Renames a course, trashed exercises included. Renaming onto a course that
+/// already exists is rejected; `merge_courses` combines two courses.
 #[command]
-fn rename_course<R: Runtime>(app: AppHandle<R>, old_name: String, new_name: String) -> Result<(), String> {
-    let db_path = get_db_path(&app)?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+fn rename_course(pool: State<'_, DbPool>, old_name: String, new_name: String) -> Result<(), AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    let course_exists = |name: &str| -> Result<bool, AppError> {
+        Ok(tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM exercises WHERE course = ?1)",
+            params![name],
+            |row| row.get(0),
+        )?)
+    };
+    if !course_exists(&old_name)? {
+        return Err(AppError::NotFound(format!("course {}", old_name)));
+    }
+    if new_name == old_name {
+        return Ok(());
+    }
+    if course_exists(&new_name)? {
+        return Err(AppError::InvalidInput(format!(
+            "A course named {} already exists; merge the courses to combine them",
+            new_name
+        )));
+    }
 
-    conn.execute(
+    tx.execute(
         "UPDATE exercises SET course = ?1 WHERE course = ?2",
         params![new_name, old_name]
-    ).map_err(|e| e.to_string())?;
+    )?;
+    courses::carry_metadata(&tx, &old_name, &new_name)?;
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// Sets the display order of a week's exercises to match `ordered_ids`, in one
+/// transaction. Exercises of the week left out of `ordered_ids`, such as trashed
+/// ones, follow in their previous order so no two share a position. An id listed
+/// twice is rejected.
+#[command]
+fn reorder_exercises(pool: State<'_, DbPool>, course: String, week: i64, ordered_ids: Vec<String>) -> Result<(), AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+    reorder_week(&tx, &course, week, &ordered_ids)?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn reorder_week(tx: &Transaction, course: &str, week: i64, ordered_ids: &[String]) -> Result<(), AppError> {
+    let listed: HashSet<&String> = ordered_ids.iter().collect();
+    if listed.len() != ordered_ids.len() {
+        return Err(AppError::InvalidInput("An exercise is listed more than once".to_string()));
+    }
+    let rest: Vec<String> = tx
+        .prepare(&format!(
+            "SELECT id FROM exercises WHERE course = ?1 AND week = ?2 ORDER BY {}",
+            SortBy::CourseThenWeek.order_by()
+        ))?
+        .query_map(params![course, week], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?
+        .into_iter()
+        .filter(|id| !listed.contains(id))
+        .collect();
+
+    for (index, id) in ordered_ids.iter().enumerate() {
+        let updated = tx.execute(
+            "UPDATE exercises SET order_index = ?1 WHERE id = ?2 AND course = ?3 AND week = ?4",
+            params![index as i64, id, course, week],
+        )?;
+        if updated == 0 {
+            return Err(AppError::InvalidInput(format!(
+                "Exercise {} is not in {} week {}",
+                id, course, week
+            )));
+        }
+    }
+    for (index, id) in rest.iter().enumerate() {
+        tx.execute(
+            "UPDATE exercises SET order_index = ?1 WHERE id = ?2",
+            params![(ordered_ids.len() + index) as i64, id],
+        )?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MergeCoursesReport {
+    moved: usize,
+    /// Weeks both courses had, now combined in the target.
+    merged_weeks: Vec<i64>,
+}
+
+/// Moves every exercise of `source` into `target`, trashed ones included, which
+/// leaves `source` empty. Exercises from a week the target already has are
+/// appended after the target's own, keeping their relative order.
+#[command]
+fn merge_courses(pool: State<'_, DbPool>, source: String, target: String) -> Result<MergeCoursesReport, AppError> {
+    if source == target {
+        return Err(AppError::InvalidInput("Cannot merge a course into itself".to_string()));
+    }
+
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    let weeks: Vec<i64> = tx
+        .prepare("SELECT DISTINCT week FROM exercises WHERE course = ?1 ORDER BY week")?
+        .query_map(params![source], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    if weeks.is_empty() {
+        return Err(AppError::NotFound(format!("course {}", source)));
+    }
+
+    let mut report = MergeCoursesReport { moved: 0, merged_weeks: Vec::new() };
+    for week in weeks {
+        let next_index: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(order_index), -1) + 1 FROM exercises WHERE course = ?1 AND week = ?2",
+            params![target, week],
+            |row| row.get(0),
+        )?;
+        let target_has_week: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM exercises WHERE course = ?1 AND week = ?2)",
+            params![target, week],
+            |row| row.get(0),
+        )?;
+        if target_has_week {
+            report.merged_weeks.push(week);
+        }
+        report.moved += tx.execute(
+            "UPDATE exercises SET course = ?2, order_index = COALESCE(order_index, 0) + ?4
+             WHERE course = ?1 AND week = ?3",
+            params![source, target, week, next_index],
+        )?;
+    }
+    courses::carry_metadata(&tx, &source, &target)?;
+
+    tx.commit()?;
+    info!("Merged {} exercises from {} into {}", report.moved, source, target);
+    Ok(report)
+}
+
+/// Writes only the fields present in `changes`, in a single UPDATE, so fields the
+/// frontend never loaded aren't overwritten. Tags are normalized as on save. An
+/// exercise given a new course or week goes to the end of that week.
+#[command]
+fn update_exercise(pool: State<'_, DbPool>, id: String, changes: ExerciseUpdate) -> Result<(), AppError> {
+    let mut assignments: Vec<&str> = Vec::new();
+    let mut args: Vec<Value> = Vec::new();
+
+    if let Some(name) = changes.name {
+        if name.trim().is_empty() {
+            return Err(AppError::InvalidInput("Exercise name must not be empty".to_string()));
+        }
+        assignments.push("name = ?");
+        args.push(Value::Text(name));
+    }
+    if let Some(tags) = changes.tags {
+        assignments.push("tags = ?");
+        args.push(Value::Text(serde_json::to_string(&tags::normalize_tags(tags))?));
+    }
+    if let Some(notes) = changes.notes {
+        assignments.push("notes = ?");
+        args.push(notes.map_or(Value::Null, Value::Text));
+    }
+    if let Some(content) = changes.content {
+        assignments.push("content = ?");
+        args.push(content.map_or(Value::Null, Value::Text));
+    }
+    if let Some(bounding_boxes) = changes.bounding_boxes {
+        assignments.push("bounding_box = ?");
+        args.push(bounding_boxes_column(&bounding_boxes)?.map_or(Value::Null, Value::Text));
+    }
+    if let Some(status) = changes.status {
+        assignments.push("status = ?");
+        args.push(Value::Text(status.as_str().to_string()));
+    }
+    if let Some(due_at) = changes.due_at {
+        assignments.push("due_at = ?");
+        args.push(due_at.map_or(Value::Null, Value::Integer));
+    }
+    if let Some(is_favorite) = changes.is_favorite {
+        assignments.push("is_favorite = ?");
+        args.push(Value::Integer(is_favorite.into()));
+    }
+    if let Some(difficulty) = changes.difficulty {
+        if let Some(difficulty) = difficulty {
+            check_difficulty(difficulty)?;
+        }
+        assignments.push("difficulty = ?");
+        args.push(difficulty.map_or(Value::Null, Value::Integer));
+    }
+    if changes.course.is_some() || changes.week.is_some() {
+        // The subquery sees the row as it was, so the target is spelled out again
+        assignments.push(
+            "course = COALESCE(?, course), week = COALESCE(?, week), order_index = (
+                 SELECT COALESCE(MAX(o.order_index), -1) + 1 FROM exercises o
+                 WHERE o.course = COALESCE(?, exercises.course) AND o.week = COALESCE(?, exercises.week)
+                     AND o.id != exercises.id
+             )",
+        );
+        let course = changes.course.map_or(Value::Null, Value::Text);
+        let week = changes.week.map_or(Value::Null, Value::Integer);
+        args.extend([course.clone(), week.clone(), course, week]);
+    }
+    if assignments.is_empty() {
+        return Err(AppError::InvalidInput("No fields to update".to_string()));
+    }
+
+    args.push(Value::Text(id.clone()));
+    let conn = get_connection(&pool)?;
+    let updated = conn.execute(
+        &format!("UPDATE exercises SET {} WHERE id = ?", assignments.join(", ")),
+        params_from_iter(&args),
+    )?;
+    if updated == 0 {
+        return Err(AppError::NotFound(format!("exercise {}", id)));
+    }
+
+    Ok(())
+}
+
+/// Marks an exercise as todo, in progress or done.
+#[command]
+fn set_exercise_status(pool: State<'_, DbPool>, id: String, status: ExerciseStatus) -> Result<(), AppError> {
+    let conn = get_connection(&pool)?;
+    let updated = conn.execute(
+        "UPDATE exercises SET status = ?1 WHERE id = ?2",
+        params![status.as_str(), id],
+    )?;
+    if updated == 0 {
+        return Err(AppError::NotFound(format!("exercise {}", id)));
+    }
+
+    Ok(())
+}
+
+/// Rates an exercise from 1 to 5 stars, or clears the rating with `None`.
+#[command]
+fn set_exercise_difficulty(pool: State<'_, DbPool>, id: String, difficulty: Option<i64>) -> Result<(), AppError> {
+    if let Some(difficulty) = difficulty {
+        check_difficulty(difficulty)?;
+    }
+
+    let conn = get_connection(&pool)?;
+    let updated = conn.execute(
+        "UPDATE exercises SET difficulty = ?1 WHERE id = ?2",
+        params![difficulty, id],
+    )?;
+    if updated == 0 {
+        return Err(AppError::NotFound(format!("exercise {}", id)));
+    }
 
     Ok(())
 }
 
+fn write_due_date(pool: &DbPool, id: &str, due_at: Option<i64>) -> Result<(), AppError> {
+    let conn = get_connection(pool)?;
+    let updated = conn.execute("UPDATE exercises SET due_at = ?1 WHERE id = ?2", params![due_at, id])?;
+    if updated == 0 {
+        return Err(AppError::NotFound(format!("exercise {}", id)));
+    }
+    Ok(())
+}
+
+/// Sets the deadline of an exercise, in Unix milliseconds.
+#[command]
+fn set_due_date(pool: State<'_, DbPool>, id: String, due_at: i64) -> Result<(), AppError> {
+    write_due_date(&pool, &id, Some(due_at))
+}
+
+#[command]
+fn clear_due_date(pool: State<'_, DbPool>, id: String) -> Result<(), AppError> {
+    write_due_date(&pool, &id, None)
+}
+
+/// Exercises due before `before` (Unix milliseconds) that aren't done yet, soonest
+/// deadline first. Pass the current time to get what is overdue.
+#[command]
+fn get_due_exercises(pool: State<'_, DbPool>, before: i64) -> Result<Vec<Exercise>, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM exercises
+         WHERE due_at < ?1 AND status != 'done' AND deleted_at IS NULL
+         ORDER BY due_at, {}",
+        EXERCISE_COLUMNS,
+        SortBy::CourseThenWeek.order_by()
+    ))?;
+    let exercises = stmt
+        .query_map(params![before], Exercise::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(exercises)
+}
+
+/// Gives week `old_number` of a course a new number, taking its metadata along.
+/// If the course already has a `new_number` week this fails unless `merge` is set,
+/// in which case the exercises are appended after that week's own and its
+/// metadata is kept. Trashed exercises move too.
+#[command]
+fn renumber_week(
+    pool: State<'_, DbPool>,
+    course_name: String,
+    old_number: i64,
+    new_number: i64,
+    merge: Option<bool>,
+) -> Result<usize, AppError> {
+    if new_number < 0 {
+        return Err(AppError::InvalidInput("Week numbers can't be negative".to_string()));
+    }
+    if old_number == new_number {
+        return Ok(0);
+    }
+
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    let count_week = |week: i64| -> Result<i64, AppError> {
+        Ok(tx.query_row(
+            "SELECT COUNT(*) FROM exercises WHERE course = ?1 AND week = ?2",
+            params![course_name, week],
+            |row| row.get(0),
+        )?)
+    };
+    if count_week(old_number)? == 0 {
+        return Err(AppError::NotFound(format!("{} week {}", course_name, old_number)));
+    }
+    let existing = count_week(new_number)?;
+    if existing > 0 && !merge.unwrap_or(false) {
+        return Err(AppError::InvalidInput(format!(
+            "{} already has a week {} with {} exercises; merge to combine them",
+            course_name, new_number, existing
+        )));
+    }
+
+    let next_index: i64 = tx.query_row(
+        "SELECT COALESCE(MAX(order_index), -1) + 1 FROM exercises WHERE course = ?1 AND week = ?2",
+        params![course_name, new_number],
+        |row| row.get(0),
+    )?;
+    let moved = tx.execute(
+        "UPDATE exercises SET week = ?3, order_index = COALESCE(order_index, 0) + ?4
+         WHERE course = ?1 AND week = ?2",
+        params![course_name, old_number, new_number, next_index],
+    )?;
+    tx.execute(
+        "UPDATE OR IGNORE week_metadata SET week = ?3 WHERE course = ?1 AND week = ?2",
+        params![course_name, old_number, new_number],
+    )?;
+    tx.execute(
+        "DELETE FROM week_metadata WHERE course = ?1 AND week = ?2",
+        params![course_name, old_number],
+    )?;
+
+    tx.commit()?;
+    info!("Renumbered {} week {} to {} ({} exercises)", course_name, old_number, new_number, moved);
+    Ok(moved)
+}
+
+/// Files an exercise under a different course/week, appending it to the end of
+/// that week. Image files are left where they are. Exercises in the trash can't be
+/// moved.
+#[command]
+fn move_exercise(pool: State<'_, DbPool>, id: String, target_course: String, target_week: i64) -> Result<(), AppError> {
+    if target_week < 0 {
+        return Err(AppError::InvalidInput("Week numbers can't be negative".to_string()));
+    }
+    if target_course.trim().is_empty() {
+        return Err(AppError::InvalidInput("Course name must not be empty".to_string()));
+    }
+
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    let updated = tx.execute(
+        "UPDATE exercises SET course = ?2, week = ?3, order_index = (
+             SELECT COALESCE(MAX(order_index), -1) + 1 FROM exercises
+             WHERE course = ?2 AND week = ?3 AND id != ?1
+         )
+         WHERE id = ?1 AND deleted_at IS NULL",
+        params![id, target_course, target_week],
+    )?;
+    if updated == 0 {
+        return Err(AppError::NotFound(format!("exercise {}", id)));
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// Files several exercises under one course/week in a single transaction,
+/// appending them to the end of that week in the order given, and returns how many
+/// moved. Exercises already there stay where they are. Fails without moving
+/// anything if an id doesn't exist.
+#[command]
+fn move_exercises(pool: State<'_, DbPool>, ids: Vec<String>, course: String, week: i64) -> Result<usize, AppError> {
+    if week < 0 {
+        return Err(AppError::InvalidInput("Week numbers can't be negative".to_string()));
+    }
+    if course.trim().is_empty() {
+        return Err(AppError::InvalidInput("Course name must not be empty".to_string()));
+    }
+
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    let mut moved = 0;
+    for id in &ids {
+        let updated = tx.execute(
+            "UPDATE exercises SET course = ?2, week = ?3, order_index = (
+                 SELECT COALESCE(MAX(order_index), -1) + 1 FROM exercises
+                 WHERE course = ?2 AND week = ?3 AND id != ?1
+             )
+             WHERE id = ?1 AND NOT (course = ?2 AND week = ?3)",
+            params![id, course, week],
+        )?;
+        if updated == 0 {
+            let exists: bool = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM exercises WHERE id = ?1)",
+                params![id],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                return Err(AppError::NotFound(format!("exercise {}", id)));
+            }
+        }
+        moved += updated;
+    }
+
+    tx.commit()?;
+    info!("Moved {} of {} exercises to {} week {}", moved, ids.len(), course, week);
+    Ok(moved)
+}
+
+/// Copies the image at `path` into `images_dir` under a fresh name and returns the
+/// copy's path. `cache` maps originals to their copies, so an image used twice is
+/// copied once; it also lists every file written, for cleaning up after a failure.
+fn copy_image_file(images_dir: &Path, cache: &mut HashMap<String, String>, path: &str) -> Result<String, AppError> {
+    if let Some(copy) = cache.get(path) {
+        return Ok(copy.clone());
+    }
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("png");
+    let new_path = images_dir.join(format!("{}.{}", Uuid::new_v4(), extension));
+    fs::copy(path, &new_path)?;
+    let new_path = new_path.to_string_lossy().into_owned();
+    cache.insert(path.to_string(), new_path.clone());
+    Ok(new_path)
+}
+
+/// Copies an exercise with " (copy)" added to the name, to the end of its own week
+/// or of `target_course`/`target_week` when given, and returns the copy. The image
+/// files, solution image included, are copied too, so the copy keeps working
+/// whatever happens to the original.
+#[command]
+fn duplicate_exercise<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    exercise_id: String,
+    target_course: Option<String>,
+    target_week: Option<i64>,
+) -> Result<Exercise, AppError> {
+    if target_week.is_some_and(|week| week < 0) {
+        return Err(AppError::InvalidInput("Week numbers can't be negative".to_string()));
+    }
+
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    let mut exercise = tx
+        .query_row(
+            &format!("SELECT {} FROM exercises WHERE id = ?1 AND deleted_at IS NULL", EXERCISE_COLUMNS),
+            params![exercise_id],
+            Exercise::from_row,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("exercise {}", exercise_id)))?;
+
+    exercise.id = Uuid::new_v4().to_string();
+    exercise.name = format!("{} (copy)", exercise.name);
+    exercise.created_at = chrono::Utc::now().timestamp_millis();
+    exercise.order_index = None;
+    exercise.status = ExerciseStatus::default();
+    exercise.due_at = None;
+    exercise.review = None;
+    exercise.total_seconds = 0;
+    if let Some(course) = target_course {
+        exercise.course = course;
+    }
+    if let Some(week) = target_week {
+        exercise.week = week;
+    }
+
+    let images_dir = get_images_dir(&app)?;
+    let mut copied_images: HashMap<String, String> = HashMap::new();
+    let result = (|| {
+        let mut copy_image = |path: &str| copy_image_file(&images_dir, &mut copied_images, path);
+        for image in &mut exercise.images {
+            image.path = copy_image(&image.path)?;
+        }
+        for image in [&mut exercise.image_uri, &mut exercise.page_image_uri, &mut exercise.solution_image_uri] {
+            *image = image.as_deref().map(&mut copy_image).transpose()?;
+        }
+        exercise.upsert(&tx)?;
+        let copy = tx.query_row(
+            &format!("SELECT {} FROM exercises WHERE id = ?1", EXERCISE_COLUMNS),
+            params![exercise.id],
+            Exercise::from_row,
+        )?;
+        tx.commit()?;
+        Ok(copy)
+    })();
+
+    if result.is_err() {
+        for path in copied_images.into_values() {
+            let _ = fs::remove_file(path);
+        }
+    }
+    result
+}
+
+/// Emitted by `duplicate_course` after each exercise is copied.
+const DUPLICATE_COURSE_PROGRESS_EVENT: &str = "duplicate-course-progress";
+
+/// `copied` reaches `total` only once the copy is committed; `failed` is set
+/// instead when it was rolled back.
+#[derive(Clone, Serialize)]
+struct DuplicateCourseProgress {
+    copied: usize,
+    total: usize,
+    failed: bool,
+}
+
+/// Copies every exercise of `source` outside the trash into a new course, with
+/// fresh ids and copies of the image files; exercises cut from the same page share
+/// one copied page image, as the originals do. Copies start out as todo, unreviewed
+/// and without a due date, and notes and solutions are left out unless
+/// `include_notes` is set. With `reset_content` the problem statements are left
+/// out too, for a course to fill in again.
+/// Week titles and the course's color and description carry over; dates and the
+/// semester don't. Returns how many exercises were copied.
+#[command]
+async fn duplicate_course<R: Runtime>(
+    app: AppHandle<R>,
+    source: String,
+    new_name: String,
+    include_notes: bool,
+    reset_content: Option<bool>,
+) -> Result<usize, AppError> {
+    // Copying images and rows blocks, so keep it off the command's thread
+    tauri::async_runtime::spawn_blocking(move || {
+        copy_course(&app, &source, &new_name, include_notes, reset_content.unwrap_or(false))
+    })
+    .await
+    .map_err(|e| AppError::Io(format!("Duplicating the course failed: {}", e)))?
+}
+
+fn copy_course<R: Runtime>(
+    app: &AppHandle<R>,
+    source: &str,
+    new_name: &str,
+    include_notes: bool,
+    reset_content: bool,
+) -> Result<usize, AppError> {
+    let new_name = new_name.trim().to_string();
+    if new_name.is_empty() {
+        return Err(AppError::InvalidInput("Course name must not be empty".to_string()));
+    }
+
+    let pool = app.state::<DbPool>();
+    let mut conn = get_connection(&pool)?;
+    // A course with no exercises left can still have its metadata, which the copy would inherit
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM exercises WHERE course = ?1)
+             OR EXISTS (SELECT 1 FROM course_metadata WHERE course = ?1)
+             OR EXISTS (SELECT 1 FROM week_metadata WHERE course = ?1)",
+        params![new_name],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Err(AppError::InvalidInput(format!("A course named {} already exists", new_name)));
+    }
+
+    let exercises = conn
+        .prepare(&format!(
+            "SELECT {} FROM exercises WHERE course = ?1 AND deleted_at IS NULL ORDER BY {}",
+            EXERCISE_COLUMNS,
+            SortBy::CourseThenWeek.order_by()
+        ))?
+        .query_map(params![source], Exercise::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    if exercises.is_empty() {
+        return Err(AppError::NotFound(format!("course {}", source)));
+    }
+
+    let images_dir = get_images_dir(app)?;
+    let total = exercises.len();
+    let mut copied_files: HashMap<String, String> = HashMap::new();
+    let result = (|| {
+        let mut copy_image = |path: &str| copy_image_file(&images_dir, &mut copied_files, path);
+
+        // Parts follow their parent into the copy
+        let copied_ids: HashMap<String, String> = exercises
+            .iter()
+            .map(|exercise| (exercise.id.clone(), Uuid::new_v4().to_string()))
+            .collect();
+        let tx = conn.transaction()?;
+        let created_at = chrono::Utc::now().timestamp_millis();
+        for (index, mut exercise) in exercises.into_iter().enumerate() {
+            let _ = app.emit_all(
+                DUPLICATE_COURSE_PROGRESS_EVENT,
+                DuplicateCourseProgress { copied: index, total, failed: false },
+            );
+
+            exercise.id = copied_ids[&exercise.id].clone();
+            exercise.parent_id = exercise.parent_id.and_then(|parent| copied_ids.get(&parent).cloned());
+            exercise.course = new_name.clone();
+            exercise.created_at = created_at;
+            exercise.status = ExerciseStatus::default();
+            exercise.due_at = None;
+            exercise.review = None;
+            exercise.total_seconds = 0;
+            if !include_notes {
+                exercise.notes = None;
+                exercise.solution_text = None;
+                exercise.solution_image_uri = None;
+                exercise.images.retain(|image| image.kind != ImageKind::Solution);
+            }
+            if reset_content {
+                exercise.content = None;
+            }
+            for image in &mut exercise.images {
+                image.path = copy_image(&image.path)?;
+            }
+            exercise.image_uri = exercise.image_uri.as_deref().map(&mut copy_image).transpose()?;
+            exercise.page_image_uri = exercise.page_image_uri.as_deref().map(&mut copy_image).transpose()?;
+            exercise.solution_image_uri = exercise.solution_image_uri.as_deref().map(&mut copy_image).transpose()?;
+            exercise.upsert(&tx)?;
+        }
+
+        tx.execute(
+            "INSERT INTO week_metadata (course, week, title)
+             SELECT ?2, week, title FROM week_metadata WHERE course = ?1 AND title IS NOT NULL",
+            params![source, new_name],
+        )?;
+        tx.execute(
+            "INSERT INTO course_metadata (course, color, description)
+             SELECT ?2, color, description FROM course_metadata WHERE course = ?1",
+            params![source, new_name],
+        )?;
+        tx.commit()?;
+        Ok(total)
+    })();
+
+    let failed = result.is_err();
+    match &result {
+        Ok(_) => info!("Duplicated {} into {} ({} exercises)", source, new_name, total),
+        Err(_) => {
+            for path in copied_files.values() {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+    let _ = app.emit_all(
+        DUPLICATE_COURSE_PROGRESS_EVENT,
+        DuplicateCourseProgress { copied: if failed { 0 } else { total }, total, failed },
+    );
+    result
+}
+
+#[command]
+fn get_startup_error(startup_error: State<'_, StartupError>, pool: State<'_, DbPool>) -> Option<AppError> {
+    if pool.is_locked() {
+        return Some(AppError::VaultLocked);
+    }
+    startup_error.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// Pdfium bindings can only be initialized once per process, so keep them around
+static PDFIUM: OnceLock<Option<Pdfium>> = OnceLock::new();
+
+fn get_pdfium() -> Option<&'static Pdfium> {
+    PDFIUM
+        .get_or_init(|| {
+            // Prefer a library shipped next to the executable, then the system one
+            let exe_dir = std::env::current_exe()
+                .ok()
+                .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+                .unwrap_or_default();
+
+            Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(&exe_dir))
+                .or_else(|_| Pdfium::bind_to_system_library())
+                .map(Pdfium::new)
+                .map_err(|e| warn!("Failed to load pdfium library: {}", e))
+                .ok()
+        })
+        .as_ref()
+}
+
+fn render_pdf_with_pdfium(
+    path: &str,
+    dpi: u32,
+    first_page: u32,
+    last_page: u32,
+    mut on_page: impl FnMut(u32),
+) -> Result<Vec<String>, String> {
+    let pdfium = get_pdfium().ok_or_else(|| "pdfium library not available".to_string())?;
+
+    let document = pdfium
+        .load_pdf_from_file(path, None)
+        .map_err(|e| format!("Failed to open PDF: {}", e))?;
+
+    // PDF user space is 72 units per inch
+    let config = PdfRenderConfig::new().scale_page_by_factor(dpi as f32 / 72.0);
+
+    let mut image_data_urls = Vec::new();
+    let pages = document
+        .pages()
+        .iter()
+        .enumerate()
+        .skip((first_page - 1) as usize)
+        .take((last_page - first_page + 1) as usize);
+    for (index, page) in pages {
+        let img = page
+            .render_with_config(&config)
+            .and_then(|bitmap| bitmap.as_image())
+            .map_err(|e| format!("Failed to render page {}: {}", index + 1, e))?;
+
+        let mut bytes: Vec<u8> = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode: {}", e))?;
+
+        let base64 = general_purpose::STANDARD.encode(&bytes);
+        image_data_urls.push(format!("data:image/png;base64,{}", base64));
+        on_page(image_data_urls.len() as u32);
+    }
+
+    if image_data_urls.is_empty() {
+        return Err("PDF has no pages".to_string());
+    }
+
+    Ok(image_data_urls)
+}
+
+/// Emitted by `pdf_to_images` as pages are rendered.
+const PDF_PROGRESS_EVENT: &str = "pdf-progress";
+
+/// Emitted by `analyze_page_image` when its request is sent and when it completes.
+const ANALYSIS_PROGRESS_EVENT: &str = "analysis-progress";
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Progress {
+    current: u32,
+    total: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+fn emit_progress<R: Runtime>(app: &AppHandle<R>, event: &str, progress: Progress) {
+    if let Err(e) = app.emit_all(event, progress) {
+        warn!("Failed to emit {}: {}", event, e);
+    }
+}
+
+/// Number of PNG files in `dir`, which is how far pdftoppm has got.
+fn count_rendered_pages(dir: &Path) -> u32 {
+    fs::read_dir(dir).map_or(0, |entries| {
+        entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().extension().is_some_and(|e| e == "png"))
+            .count() as u32
+    })
+}
+
+/// Renders PDF pages to PNG data URLs, at `dpi` or else the `pdf_dpi` setting.
+/// `pdf-progress` events report `{ current, total }` pages as they are done.
 #[command]
-fn pdf_to_images(path: String) -> Result<Vec<String>, String> {
-    eprintln!("Converting PDF to images: {}", path);
+#[instrument(skip(app, pool, path))]
+async fn pdf_to_images<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    path: String,
+    dpi: Option<u32>,
+    page_range: Option<(u32, u32)>,
+) -> Result<Vec<String>, AppError> {
+    let dpi = match dpi {
+        Some(dpi) => dpi,
+        None => {
+            let conn = get_connection(&pool)?;
+            Settings::load(&conn)?.pdf_dpi
+        }
+    };
+    if dpi == 0 {
+        return Err(AppError::InvalidInput("DPI must be greater than 0".to_string()));
+    }
+
+    // Rendering blocks while it polls the converter, so keep it off the command's thread
+    tauri::async_runtime::spawn_blocking(move || render_pdf_pages(&app, &path, dpi, page_range))
+        .await
+        .map_err(|e| AppError::PdfConversion(format!("PDF conversion task failed: {}", e)))?
+}
+
+fn render_pdf_pages<R: Runtime>(
+    app: &AppHandle<R>,
+    path: &str,
+    dpi: u32,
+    page_range: Option<(u32, u32)>,
+) -> Result<Vec<String>, AppError> {
+    debug!("Converting PDF to images: {}", path);
     
     // Load PDF to get page count
-    let doc = Document::load(&path)
-        .map_err(|e| format!("Failed to open PDF: {}", e))?;
+    let doc = Document::load(path)
+        .map_err(|e| AppError::PdfConversion(format!("Failed to open PDF: {}", e)))?;
     
-    let num_pages = doc.get_pages().len();
-    eprintln!("PDF has {} pages", num_pages);
+    let num_pages = doc.get_pages().len() as u32;
+    debug!("PDF has {} pages", num_pages);
+
+    // Page range is 1-based and inclusive, defaulting to the whole document
+    let (first_page, last_page) = page_range.unwrap_or((1, num_pages));
+    if first_page == 0 || first_page > last_page || last_page > num_pages {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid page range {}-{}: document has {} pages",
+            first_page, last_page, num_pages
+        )));
+    }
+    debug!("Converting pages {}-{} at {} DPI", first_page, last_page, dpi);
+
+    let dpi_arg = dpi.to_string();
+    let first_arg = first_page.to_string();
+    let last_arg = last_page.to_string();
     
     // Create temporary directory for converted images
     let temp_dir = std::env::temp_dir().join(format!("vaulty_pdf_{}", Uuid::new_v4()));
     fs::create_dir_all(&temp_dir)
-        .map_err(|e| format!("Failed to create temp dir: {}", e))?;
+        .map_err(|e| AppError::Io(format!("Failed to create temp dir: {}", e)))?;
     
     let mut image_data_urls = Vec::new();
     
@@ -475,18 +1972,47 @@ fn pdf_to_images(path: String) -> Result<Vec<String>, String> {
         "pdftoppm",                      // System PATH
     ];
     
+    let total = last_page - first_page + 1;
+    let report = |current: u32| {
+        emit_progress(app, PDF_PROGRESS_EVENT, Progress { current, total, request_id: None })
+    };
+
     let mut success = false;
     for pdftoppm_path in pdftoppm_paths {
-        let output = std::process::Command::new(pdftoppm_path)
-            .args(&[
+        let child = std::process::Command::new(pdftoppm_path)
+            .args([
                 "-png",
-                "-r", "150", // 150 DPI for good quality
-                &path,
+                "-r", &dpi_arg,
+                "-f", &first_arg,
+                "-l", &last_arg,
+                path,
                 temp_dir.join("page").to_str().unwrap()
             ])
-            .output();
-        
-        if output.is_ok() && output.as_ref().unwrap().status.success() {
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else { continue };
+
+        // pdftoppm only reports when it is done, so watch the pages it writes instead
+        let mut reported = 0;
+        let status = loop {
+            match child.try_wait() {
+                Ok(None) => {}
+                Ok(Some(status)) => break Some(status),
+                Err(_) => break None,
+            }
+            // The newest file may still be being written
+            let done = count_rendered_pages(&temp_dir).saturating_sub(1);
+            if done > reported {
+                reported = done;
+                report(done);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(200));
+        };
+
+        if status.is_some_and(|status| status.success()) {
+            debug!("Converted PDF with {}", pdftoppm_path);
+            report(total);
             success = true;
             break;
         }
@@ -494,24 +2020,39 @@ fn pdf_to_images(path: String) -> Result<Vec<String>, String> {
     
     if !success {
         // Try sips (macOS built-in)
-        eprintln!("pdftoppm not available, trying sips...");
+        debug!("pdftoppm not available, trying sips...");
         let output = std::process::Command::new("sips")
-            .args(&[
+            .args([
                 "-s", "format", "png",
-                &path,
+                path,
                 "--out", temp_dir.to_str().unwrap()
             ])
             .output();
         
         if output.is_ok() && output.unwrap().status.success() {
+            debug!("Converted PDF with sips");
+            report(total);
             success = true;
         }
     }
     
     if !success {
-        // If both fail, create placeholders
-        eprintln!("No PDF converter available, creating placeholders");
-        for _page_num in 0..num_pages {
+        // Fall back to rendering in-process with pdfium
+        debug!("sips not available, trying pdfium...");
+        match render_pdf_with_pdfium(path, dpi, first_page, last_page, report) {
+            Ok(urls) => {
+                debug!("Rendered {} pages with pdfium", urls.len());
+                let _ = fs::remove_dir_all(&temp_dir);
+                return Ok(urls);
+            }
+            Err(e) => warn!("pdfium rendering failed: {}", e),
+        }
+    }
+
+    if !success {
+        // If every backend fails, create placeholders
+        warn!("No PDF converter available, creating placeholders");
+        for _page_num in first_page..=last_page {
             let img: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_pixel(
                 1224, 1584,
                 Rgba([255, 255, 255, 255])
@@ -519,18 +2060,18 @@ fn pdf_to_images(path: String) -> Result<Vec<String>, String> {
             
             let mut bytes: Vec<u8> = Vec::new();
             DynamicImage::ImageRgba8(img)
-                .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png)
-                .map_err(|e| format!("Failed to encode: {}", e))?;
+                .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png)?;
             
             let base64 = general_purpose::STANDARD.encode(&bytes);
             image_data_urls.push(format!("data:image/png;base64,{}", base64));
         }
+        report(total);
         
         return Ok(image_data_urls);
     }
     
     // Read generated PNG files and convert to base64
-    for page_num in 1..=num_pages {
+    for page_num in first_page..=last_page {
         // pdftoppm uses 1-based indexing with padding
         let possible_names = vec![
             format!("page-{}.png", page_num),
@@ -543,9 +2084,9 @@ fn pdf_to_images(path: String) -> Result<Vec<String>, String> {
         for name in &possible_names {
             let img_path = temp_dir.join(name);
             if img_path.exists() {
-                eprintln!("Reading page {} from {:?}", page_num, img_path);
+                debug!("Reading page {} from {:?}", page_num, img_path);
                 let img_bytes = fs::read(&img_path)
-                    .map_err(|e| format!("Failed to read image: {}", e))?;
+                    .map_err(|e| AppError::Io(format!("Failed to read image: {}", e)))?;
                 
                 let base64 = general_purpose::STANDARD.encode(&img_bytes);
                 image_data_urls.push(format!("data:image/png;base64,{}", base64));
@@ -555,7 +2096,7 @@ fn pdf_to_images(path: String) -> Result<Vec<String>, String> {
         }
         
         if !found {
-            eprintln!("Warning: Could not find image for page {}", page_num);
+            warn!("Could not find image for page {}", page_num);
         }
     }
     
@@ -563,17 +2104,37 @@ fn pdf_to_images(path: String) -> Result<Vec<String>, String> {
     let _ = fs::remove_dir_all(&temp_dir);
     
     if image_data_urls.is_empty() {
-        return Err("Failed to convert any PDF pages to images".to_string());
+        return Err(AppError::PdfConversion("Failed to convert any PDF pages to images".to_string()));
     }
     
-    eprintln!("Successfully converted {} pages", image_data_urls.len());
+    debug!("Successfully converted {} pages", image_data_urls.len());
     Ok(image_data_urls)
 }
 
 fn main() {
     tauri::Builder::default()
         .setup(|app| {
-            init_db(&app.handle()).expect("failed to init db");
+            if let Ok(app_data_dir) = db::get_app_data_dir(&app.handle()) {
+                logging::init(&app_data_dir.join("logs"));
+            }
+
+            // A missing data directory, a database that won't open or a failed migration is
+            // reported to the UI. A failed migration leaves the database at its previous
+            // version; an encrypted vault is migrated by unlock_vault once the passphrase is known.
+            let (pool, startup_error) = match db::get_db_path(&app.handle()).and_then(DbPool::open) {
+                Ok(pool) => {
+                    let startup_error = if pool.is_locked() { None } else { db::init_db(&pool).err() };
+                    (pool, startup_error)
+                }
+                Err(e) => (DbPool::closed(), Some(e)),
+            };
+            if let Some(e) = &startup_error {
+                error!("Failed to open database: {}", e);
+            }
+            app.manage(pool);
+            app.manage(StartupError(Mutex::new(startup_error)));
+            app.manage(AnalysisRegistry::default());
+            app.manage(HttpClient::default());
 
             // Check for updates on startup (in production builds only)
             #[cfg(not(debug_assertions))]
@@ -583,11 +2144,11 @@ fn main() {
                     match handle.updater().check().await {
                         Ok(update) => {
                             if update.is_update_available() {
-                                println!("Update available: {}", update.latest_version());
+                                info!("Update available: {}", update.latest_version());
                             }
                         }
                         Err(e) => {
-                            println!("Failed to check for updates: {}", e);
+                            warn!("Failed to check for updates: {}", e);
                         }
                     }
                 });
@@ -597,14 +2158,205 @@ fn main() {
         })
         .invoke_handler(tauri::generate_handler![
             save_image,
+            create_exercise_with_image,
+            save_solution,
+            exercise_images::add_exercise_image,
+            exercise_images::remove_exercise_image,
+            exercise_images::reorder_exercise_images,
+            attachments::add_attachment,
+            attachments::list_attachments,
+            attachments::remove_attachment,
+            attachments::open_attachment,
+            links::link_exercises,
+            links::unlink_exercises,
+            links::get_linked_exercises,
+            crop_exercise_image,
             get_all_exercises,
+            get_exercise,
+            get_exercise_children,
+            get_exercises_by_source,
+            get_exercises_modified_since,
             save_exercise,
             delete_exercise,
+            bulk_delete_exercises,
             delete_course,
             rename_course,
+            merge_courses,
+            reorder_exercises,
+            update_exercise,
+            set_exercise_status,
+            set_exercise_difficulty,
+            toggle_favorite,
+            get_favorites,
+            set_due_date,
+            clear_due_date,
+            get_due_exercises,
+            attempts::log_attempt,
+            attempts::get_attempts,
+            attempts::delete_attempt,
+            review::record_review,
+            review::get_review_queue,
+            statistics::get_statistics,
+            statistics::get_database_stats,
+            timer::start_timer,
+            timer::stop_timer,
+            timer::get_running_timer,
+            renumber_week,
+            move_exercise,
+            move_exercises,
+            duplicate_exercise,
+            duplicate_course,
             analyze_page_image,
-            pdf_to_images
+            analyze_pages,
+            pdf_to_images,
+            cost::estimate_analysis_cost,
+            get_startup_error,
+            analysis::cancel_analysis,
+            backup::export_vault_json,
+            backup::export_course,
+            backup::export_course_markdown,
+            anki::export_to_anki,
+            backup::import_vault_json,
+            backup::import_course,
+            backup::create_backup,
+            backup::restore_backup,
+            search::search_exercises,
+            settings::save_api_key,
+            settings::get_api_key,
+            settings::validate_api_key,
+            settings::get_proxy,
+            settings::set_proxy,
+            settings::get_default_model,
+            settings::get_setting,
+            settings::set_setting,
+            settings::set_default_model,
+            images::cleanup_orphaned_images,
+            images::verify_images,
+            images::get_image_thumbnail,
+            maintenance::optimize_database,
+            maintenance::cleanup_storage,
+            storage::get_data_directory,
+            storage::move_data_directory,
+            courses::get_courses,
+            courses::list_courses,
+            courses::update_course_metadata,
+            courses::update_week_metadata,
+            profiles::list_vaults,
+            profiles::create_vault,
+            profiles::switch_vault,
+            profiles::delete_vault,
+            tags::filter_by_tags,
+            tags::get_all_tags,
+            tags::rename_tag,
+            tags::merge_tags,
+            tags::delete_tag,
+            tags::bulk_edit_tags,
+            trash::list_trash,
+            trash::restore_exercise,
+            trash::purge_trash,
+            history::get_exercise_history,
+            history::revert_exercise,
+            history::set_history_limit,
+            undo::undo_last_operation,
+            vault::get_vault_status,
+            vault::set_vault_passphrase,
+            vault::unlock_vault,
+            vault::change_passphrase
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_connection;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn reorder_rejects_duplicate_ids() {
+        let mut conn = test_connection();
+        conn.execute_batch(
+            "INSERT INTO exercises (id, name, tags, course, week, created_at, order_index) VALUES
+                 ('a', 'Ex 1', '[]', 'Algebra', 1, 0, 0),
+                 ('b', 'Ex 2', '[]', 'Algebra', 1, 0, 1),
+                 ('c', 'Ex 3', '[]', 'Algebra', 1, 0, 2);",
+        )
+        .unwrap();
+
+        let tx = conn.transaction().unwrap();
+        let duplicated = reorder_week(&tx, "Algebra", 1, &ids(&["c", "a", "c"]));
+        assert!(matches!(duplicated, Err(AppError::InvalidInput(_))));
+        drop(tx);
+
+        let tx = conn.transaction().unwrap();
+        reorder_week(&tx, "Algebra", 1, &ids(&["c", "a"])).unwrap();
+        tx.commit().unwrap();
+        let order: Vec<String> = conn
+            .prepare("SELECT id FROM exercises ORDER BY order_index")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(order, ids(&["c", "a", "b"]));
+        let positions: Vec<i64> = conn
+            .prepare("SELECT order_index FROM exercises ORDER BY order_index")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(positions, [0, 1, 2]);
+    }
+
+
+    const JPEG_HEADER: &[u8] = b"\xff\xd8\xff\xe0\0\x10JFIF\0\x01\x01\0\0\x01\0\x01";
+
+    fn sent_image(request: &serde_json::Value) -> (&str, &str) {
+        let inline_data = &request["contents"][0]["parts"][0]["inline_data"];
+        (inline_data["mime_type"].as_str().unwrap(), inline_data["data"].as_str().unwrap())
+    }
+
+    #[test]
+    fn extraction_request_sends_jpeg_data_urls_as_jpeg() {
+        let base64 = general_purpose::STANDARD.encode(JPEG_HEADER);
+        let request = extraction_request(&format!("data:image/jpeg;base64,{}", base64), None).unwrap();
+        assert_eq!(sent_image(&request), ("image/jpeg", base64.as_str()));
+    }
+
+    #[test]
+    fn extraction_request_trusts_the_bytes_over_the_prefix() {
+        let base64 = general_purpose::STANDARD.encode(JPEG_HEADER);
+        let request = extraction_request(&format!("data:image/png;base64,{}", base64), None).unwrap();
+        assert_eq!(sent_image(&request), ("image/jpeg", base64.as_str()));
+
+        let not_an_image = general_purpose::STANDARD.encode(b"GIF89a\x01\0\x01\0\0\0\0\0");
+        let request = extraction_request(&format!("data:image/png;base64,{}", not_an_image), None);
+        assert!(matches!(request, Err(AppError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn copy_image_file_copies_each_image_once() {
+        let dir = std::env::temp_dir().join(format!("vaulty-copy-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let original = dir.join("original.webp");
+        fs::write(&original, b"webp").unwrap();
+        let original = original.to_string_lossy().into_owned();
+
+        let mut cache = HashMap::new();
+        let copy = copy_image_file(&dir, &mut cache, &original).unwrap();
+        assert_ne!(copy, original);
+        assert!(copy.ends_with(".webp"));
+        assert_eq!(fs::read(&copy).unwrap(), b"webp");
+        assert_eq!(copy_image_file(&dir, &mut cache, &original).unwrap(), copy);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        let missing = dir.join("missing.png").to_string_lossy().into_owned();
+        assert!(copy_image_file(&dir, &mut cache, &missing).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+use rusqlite::{Connection, ErrorCode};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use tauri::{command, AppHandle, Runtime, State};
+use tracing::info;
+
+use crate::db::{get_connection, get_db_path, get_images_dir, DbPool};
+use crate::error::AppError;
+use crate::images::{delete_orphaned_images, find_orphaned_images};
+
+#[derive(Debug, Serialize)]
+pub struct OptimizeReport {
+    #[serde(rename = "beforeBytes")]
+    pub before_bytes: u64,
+    #[serde(rename = "afterBytes")]
+    pub after_bytes: u64,
+}
+
+/// Size of the database including its write-ahead log.
+fn database_size(db_path: &Path) -> u64 {
+    let mut wal_path = db_path.as_os_str().to_owned();
+    wal_path.push("-wal");
+    [db_path, Path::new(&wal_path)]
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Rebuilds the database file to reclaim space left by deleted rows and refreshes
+/// the query planner statistics.
+///
+/// VACUUM needs the database to itself. If another command holds a write
+/// transaction (an import, say) this gives up after the busy timeout and returns
+/// a `busy` error rather than blocking the app.
+#[command]
+pub fn optimize_database<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+) -> Result<OptimizeReport, AppError> {
+    let conn = get_connection(&pool)?;
+    let report = vacuum(&conn, &get_db_path(&app)?)?;
+
+    info!(
+        "Optimized database: {} -> {} bytes",
+        report.before_bytes, report.after_bytes
+    );
+    Ok(report)
+}
+
+fn vacuum(conn: &Connection, db_path: &Path) -> Result<OptimizeReport, AppError> {
+    let before_bytes = database_size(db_path);
+
+    conn.execute_batch(
+        "VACUUM;
+         ANALYZE;
+         PRAGMA optimize;
+         PRAGMA wal_checkpoint(TRUNCATE);",
+    )
+    .map_err(|e| match e.sqlite_error_code() {
+        Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => AppError::Busy(
+            "another operation is using the database, try again once it finishes".to_string(),
+        ),
+        _ => AppError::from(e),
+    })?;
+
+    Ok(OptimizeReport {
+        before_bytes,
+        after_bytes: database_size(db_path),
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct StorageCleanupReport {
+    #[serde(rename = "orphanedImagesDeleted")]
+    pub orphaned_images_deleted: usize,
+    /// Orphaned image bytes plus whatever VACUUM gave back.
+    #[serde(rename = "bytesFreed")]
+    pub bytes_freed: u64,
+}
+
+/// Deletes orphaned image files, as `cleanup_orphaned_images` with `confirm` does,
+/// then optimizes the database.
+#[command]
+pub fn cleanup_storage<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+) -> Result<StorageCleanupReport, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut orphans = find_orphaned_images(&conn, &get_images_dir(&app)?)?;
+    delete_orphaned_images(&mut orphans)?;
+    let optimized = vacuum(&conn, &get_db_path(&app)?)?;
+
+    let report = StorageCleanupReport {
+        orphaned_images_deleted: orphans.files.len(),
+        bytes_freed: orphans.total_bytes + optimized.before_bytes.saturating_sub(optimized.after_bytes),
+    };
+    info!(
+        "Storage cleanup freed {} bytes ({} images)",
+        report.bytes_freed, report.orphaned_images_deleted
+    );
+    Ok(report)
+}
@@ -0,0 +1,800 @@
+use rusqlite::{Connection, Transaction};
+use tracing::info;
+
+use crate::error::AppError;
+
+type Migration = fn(&Transaction) -> rusqlite::Result<()>;
+
+/// Schema migrations in order. `PRAGMA user_version` stores how many have been
+/// applied, so only append to this list; never reorder or edit a shipped entry.
+const MIGRATIONS: &[Migration] = &[
+    create_exercises,
+    add_tags_column,
+    add_notes_column,
+    create_app_settings,
+    create_exercises_fts,
+    add_order_index_column,
+    create_course_week_index,
+    add_source_page_column,
+    add_deleted_at_column,
+    create_exercise_history,
+    create_undo_operations,
+    create_tag_tables,
+    add_updated_at_column,
+    create_course_metadata,
+    create_week_metadata,
+    add_status_column,
+    add_difficulty_column,
+    convert_bounding_boxes,
+    add_is_favorite_column,
+    add_due_at_column,
+    add_review_columns,
+    create_attempts,
+    create_timer_sessions,
+    add_solution_columns,
+    create_exercise_images,
+    create_attachments,
+    create_exercise_links,
+    add_parent_id,
+    add_source_file,
+    purge_trashed_parts,
+];
+
+/// Brings the database up to the latest schema version. Each migration runs in
+/// its own transaction together with the version bump, so a failure leaves the
+/// database at the last version that completed.
+pub fn run(conn: &mut Connection) -> Result<(), AppError> {
+    let current: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+        let version = index + 1;
+        info!("Applying migration {}", version);
+
+        let tx = conn.transaction()?;
+        migration(&tx)
+            .and_then(|_| tx.pragma_update(None, "user_version", version))
+            .map_err(|e| AppError::Migration(format!("Migration {} failed: {}", version, e)))?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+fn has_column(tx: &Transaction, table: &str, column: &str) -> rusqlite::Result<bool> {
+    let mut stmt = tx.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(columns.iter().any(|c| c == column))
+}
+
+fn add_column_if_missing(tx: &Transaction, table: &str, column: &str, decl: &str) -> rusqlite::Result<()> {
+    if !has_column(tx, table, column)? {
+        tx.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl), [])?;
+    }
+    Ok(())
+}
+
+fn create_exercises(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS exercises (
+            id TEXT PRIMARY KEY,
+            name TEXT,
+            course TEXT,
+            week INTEGER,
+            content TEXT,
+            image_path TEXT,
+            page_image_path TEXT,
+            bounding_box TEXT,
+            created_at INTEGER
+        )",
+        [],
+    )?;
+
+    // Tables from early builds may predate some of these columns
+    for (column, decl) in [
+        ("name", "TEXT"),
+        ("course", "TEXT"),
+        ("week", "INTEGER"),
+        ("content", "TEXT"),
+        ("image_path", "TEXT"),
+        ("page_image_path", "TEXT"),
+        ("bounding_box", "TEXT"),
+        ("created_at", "INTEGER"),
+    ] {
+        add_column_if_missing(tx, "exercises", column, decl)?;
+    }
+    Ok(())
+}
+
+fn add_tags_column(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "tags", "TEXT")?;
+    tx.execute("UPDATE exercises SET tags = '[]' WHERE tags IS NULL", [])?;
+    Ok(())
+}
+
+fn add_notes_column(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "notes", "TEXT")
+}
+
+fn create_app_settings(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS app_settings (
+            key TEXT PRIMARY KEY,
+            value TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// The index keeps its own copy of the text keyed by exercise id rather than using
+// external content: `INSERT OR REPLACE` doesn't fire delete triggers, and implicit
+// rowids on a TEXT-keyed table can change on VACUUM.
+fn create_exercises_fts(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS exercises_fts USING fts5(
+            id UNINDEXED,
+            name,
+            content,
+            notes,
+            tags,
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS exercises_fts_insert AFTER INSERT ON exercises BEGIN
+            DELETE FROM exercises_fts WHERE id = new.id;
+            INSERT INTO exercises_fts (id, name, content, notes, tags)
+            VALUES (new.id, new.name, new.content, new.notes, new.tags);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS exercises_fts_update AFTER UPDATE ON exercises BEGIN
+            DELETE FROM exercises_fts WHERE id = old.id;
+            INSERT INTO exercises_fts (id, name, content, notes, tags)
+            VALUES (new.id, new.name, new.content, new.notes, new.tags);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS exercises_fts_delete AFTER DELETE ON exercises BEGIN
+            DELETE FROM exercises_fts WHERE id = old.id;
+        END;
+
+        DELETE FROM exercises_fts;
+        INSERT INTO exercises_fts (id, name, content, notes, tags)
+        SELECT id, name, content, notes, tags FROM exercises;",
+    )
+}
+
+fn add_order_index_column(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "order_index", "INTEGER")?;
+    // Number existing exercises within each course/week by creation time
+    tx.execute(
+        "UPDATE exercises SET order_index = (
+            SELECT COUNT(*) FROM exercises AS other
+            WHERE other.course IS exercises.course
+              AND other.week IS exercises.week
+              AND (other.created_at < exercises.created_at
+                   OR (other.created_at = exercises.created_at AND other.id < exercises.id))
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_course_week_index(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS idx_exercises_course_week_created
+         ON exercises (course, week, created_at)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn add_source_page_column(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "source_page", "INTEGER")
+}
+
+fn add_deleted_at_column(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "deleted_at", "INTEGER")
+}
+
+// Edits and moves to the trash record the previous text of the exercise. The
+// per-exercise cap is read from app_settings on every insert, so changing it
+// takes effect with the next edit.
+fn create_exercise_history(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS exercise_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            exercise_id TEXT NOT NULL,
+            name TEXT,
+            tags TEXT,
+            notes TEXT,
+            content TEXT,
+            reason TEXT NOT NULL,
+            recorded_at INTEGER NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_exercise_history_exercise
+        ON exercise_history (exercise_id, id);
+
+        CREATE TRIGGER IF NOT EXISTS exercise_history_update
+        AFTER UPDATE OF name, tags, notes, content ON exercises
+        WHEN old.name IS NOT new.name OR old.tags IS NOT new.tags
+          OR old.notes IS NOT new.notes OR old.content IS NOT new.content
+        BEGIN
+            INSERT INTO exercise_history (exercise_id, name, tags, notes, content, reason, recorded_at)
+            VALUES (old.id, old.name, old.tags, old.notes, old.content, 'update',
+                    CAST(strftime('%s', 'now') AS INTEGER) * 1000);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS exercise_history_trash
+        AFTER UPDATE OF deleted_at ON exercises
+        WHEN old.deleted_at IS NULL AND new.deleted_at IS NOT NULL
+        BEGIN
+            INSERT INTO exercise_history (exercise_id, name, tags, notes, content, reason, recorded_at)
+            VALUES (old.id, old.name, old.tags, old.notes, old.content, 'delete', new.deleted_at);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS exercise_history_purge AFTER DELETE ON exercises BEGIN
+            DELETE FROM exercise_history WHERE exercise_id = old.id;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS exercise_history_prune AFTER INSERT ON exercise_history BEGIN
+            DELETE FROM exercise_history
+            WHERE exercise_id = new.exercise_id
+              AND id NOT IN (
+                  SELECT id FROM exercise_history
+                  WHERE exercise_id = new.exercise_id
+                  ORDER BY id DESC
+                  LIMIT MAX(0, COALESCE(
+                      (SELECT CAST(value AS INTEGER) FROM app_settings WHERE key = 'history_limit'),
+                      20
+                  ))
+              );
+        END;",
+    )
+}
+
+fn create_undo_operations(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS undo_operations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            target TEXT NOT NULL,
+            deleted_at INTEGER NOT NULL,
+            exercise_ids TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// The tags JSON column stays the source of truth for each exercise's tags and
+// their order; these tables are an index over it for lookups by tag, maintained
+// by triggers like the search index. Names compare ASCII case-insensitively, so
+// one row stands for every casing of a tag.
+fn create_tag_tables(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS tags (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE
+        );
+
+        CREATE TABLE IF NOT EXISTS exercise_tags (
+            exercise_id TEXT NOT NULL,
+            tag_id INTEGER NOT NULL,
+            PRIMARY KEY (exercise_id, tag_id)
+        ) WITHOUT ROWID;
+
+        CREATE INDEX IF NOT EXISTS idx_exercise_tags_tag ON exercise_tags (tag_id, exercise_id);
+
+        CREATE TRIGGER IF NOT EXISTS exercise_tags_insert AFTER INSERT ON exercises BEGIN
+            INSERT OR IGNORE INTO tags (name)
+            SELECT trim(value) FROM json_each(CASE WHEN json_valid(new.tags) THEN new.tags ELSE '[]' END)
+            WHERE type = 'text' AND trim(value) != '';
+            INSERT OR IGNORE INTO exercise_tags (exercise_id, tag_id)
+            SELECT new.id, tags.id
+            FROM json_each(CASE WHEN json_valid(new.tags) THEN new.tags ELSE '[]' END) AS tag
+            JOIN tags ON tags.name = trim(tag.value)
+            WHERE tag.type = 'text';
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS exercise_tags_update AFTER UPDATE OF tags ON exercises BEGIN
+            DELETE FROM exercise_tags WHERE exercise_id = old.id;
+            DELETE FROM tags WHERE NOT EXISTS (SELECT 1 FROM exercise_tags WHERE tag_id = tags.id);
+            INSERT OR IGNORE INTO tags (name)
+            SELECT trim(value) FROM json_each(CASE WHEN json_valid(new.tags) THEN new.tags ELSE '[]' END)
+            WHERE type = 'text' AND trim(value) != '';
+            INSERT OR IGNORE INTO exercise_tags (exercise_id, tag_id)
+            SELECT new.id, tags.id
+            FROM json_each(CASE WHEN json_valid(new.tags) THEN new.tags ELSE '[]' END) AS tag
+            JOIN tags ON tags.name = trim(tag.value)
+            WHERE tag.type = 'text';
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS exercise_tags_delete AFTER DELETE ON exercises BEGIN
+            DELETE FROM exercise_tags WHERE exercise_id = old.id;
+            DELETE FROM tags WHERE NOT EXISTS (SELECT 1 FROM exercise_tags WHERE tag_id = tags.id);
+        END;
+
+        INSERT OR IGNORE INTO tags (name)
+        SELECT trim(tag.value)
+        FROM exercises, json_each(CASE WHEN json_valid(exercises.tags) THEN exercises.tags ELSE '[]' END) AS tag
+        WHERE tag.type = 'text' AND trim(tag.value) != '';
+
+        INSERT OR IGNORE INTO exercise_tags (exercise_id, tag_id)
+        SELECT exercises.id, tags.id
+        FROM exercises, json_each(CASE WHEN json_valid(exercises.tags) THEN exercises.tags ELSE '[]' END) AS tag
+        JOIN tags ON tags.name = trim(tag.value)
+        WHERE tag.type = 'text';",
+    )
+}
+
+// Saves set updated_at themselves; the trigger covers every other UPDATE (moves,
+// renames, tag edits, trashing) that doesn't.
+fn add_updated_at_column(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "updated_at", "INTEGER")?;
+    tx.execute_batch(
+        "UPDATE exercises SET updated_at = created_at WHERE updated_at IS NULL;
+
+        CREATE INDEX IF NOT EXISTS idx_exercises_updated_at ON exercises (updated_at);
+
+        CREATE TRIGGER IF NOT EXISTS exercises_touch AFTER UPDATE ON exercises
+        WHEN new.updated_at IS old.updated_at
+        BEGIN
+            UPDATE exercises
+            SET updated_at = CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)
+            WHERE id = new.id;
+        END;",
+    )
+}
+
+// Courses only exist as the `course` text of their exercises; this holds what the
+// user has set for one, keyed on that name. Rows are dropped with the course's
+// last exercise when the trash is purged.
+fn create_course_metadata(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS course_metadata (
+            course TEXT PRIMARY KEY,
+            color TEXT,
+            semester TEXT,
+            archived INTEGER NOT NULL DEFAULT 0,
+            description TEXT
+        );",
+    )
+}
+
+// Like course_metadata, keyed on the (course, week) pair of the exercises.
+fn create_week_metadata(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS week_metadata (
+            course TEXT NOT NULL,
+            week INTEGER NOT NULL,
+            title TEXT,
+            start_date TEXT,
+            end_date TEXT,
+            PRIMARY KEY (course, week)
+        ) WITHOUT ROWID;",
+    )
+}
+
+fn add_status_column(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        tx,
+        "exercises",
+        "status",
+        "TEXT NOT NULL DEFAULT 'todo' CHECK (status IN ('todo', 'in_progress', 'done'))",
+    )
+}
+
+fn add_difficulty_column(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "difficulty", "INTEGER CHECK (difficulty BETWEEN 1 AND 5)")
+}
+
+// Exercises can span pages now, so bounding_box holds a list of boxes that each
+// name their page. An existing box goes on the page the exercise came from.
+fn convert_bounding_boxes(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "UPDATE exercises
+         SET bounding_box = CASE
+             WHEN json_valid(bounding_box) AND json_type(bounding_box) = 'object'
+             THEN json_array(json_set(bounding_box, '$.page', COALESCE(source_page, 1)))
+         END
+         WHERE bounding_box IS NOT NULL
+           AND NOT (json_valid(bounding_box) AND json_type(bounding_box) = 'array');",
+    )
+}
+
+// Few exercises are starred, so a partial index keeps the starred listing fast
+// without indexing every row.
+fn add_is_favorite_column(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "is_favorite", "INTEGER NOT NULL DEFAULT 0")?;
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_exercises_favorite
+         ON exercises (course, week, order_index) WHERE is_favorite = 1;",
+    )
+}
+
+fn add_due_at_column(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "due_at", "INTEGER")?;
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_exercises_due_at ON exercises (due_at) WHERE due_at IS NOT NULL;",
+    )
+}
+
+// SM-2 state, all NULL until an exercise's first review.
+fn add_review_columns(tx: &Transaction) -> rusqlite::Result<()> {
+    for (column, decl) in [
+        ("ease_factor", "REAL"),
+        ("interval_days", "INTEGER"),
+        ("repetitions", "INTEGER"),
+        ("next_review_at", "INTEGER"),
+    ] {
+        add_column_if_missing(tx, "exercises", column, decl)?;
+    }
+    Ok(())
+}
+
+// Attempts go with their exercise when it is purged from the trash, like its history.
+fn create_attempts(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS attempts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            exercise_id TEXT NOT NULL,
+            attempted_at INTEGER NOT NULL,
+            outcome TEXT NOT NULL CHECK (outcome IN ('solved', 'partial', 'failed')),
+            minutes_spent INTEGER CHECK (minutes_spent >= 0),
+            note TEXT
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_attempts_exercise ON attempts (exercise_id, attempted_at);
+
+        CREATE TRIGGER IF NOT EXISTS attempts_purge AFTER DELETE ON exercises BEGIN
+            DELETE FROM attempts WHERE exercise_id = old.id;
+        END;",
+    )
+}
+
+// total_seconds sums the finished sessions, so closing one adds its length there.
+fn create_timer_sessions(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "total_seconds", "INTEGER NOT NULL DEFAULT 0")?;
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS timer_sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            exercise_id TEXT NOT NULL,
+            started_at INTEGER NOT NULL,
+            ended_at INTEGER
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_timer_sessions_exercise ON timer_sessions (exercise_id);
+        CREATE INDEX IF NOT EXISTS idx_timer_sessions_open ON timer_sessions (exercise_id) WHERE ended_at IS NULL;
+
+        CREATE TRIGGER IF NOT EXISTS timer_sessions_purge AFTER DELETE ON exercises BEGIN
+            DELETE FROM timer_sessions WHERE exercise_id = old.id;
+        END;",
+    )
+}
+
+// FTS5 tables can't gain columns, so the search index is rebuilt with one for the
+// solution text. Its columns keep their positions, with `solution` added last.
+fn add_solution_columns(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "solution_text", "TEXT")?;
+    add_column_if_missing(tx, "exercises", "solution_image_path", "TEXT")?;
+    tx.execute_batch(
+        "DROP TRIGGER IF EXISTS exercises_fts_insert;
+        DROP TRIGGER IF EXISTS exercises_fts_update;
+        DROP TRIGGER IF EXISTS exercises_fts_delete;
+        DROP TABLE IF EXISTS exercises_fts;
+
+        CREATE VIRTUAL TABLE exercises_fts USING fts5(
+            id UNINDEXED,
+            name,
+            content,
+            notes,
+            tags,
+            solution,
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+
+        CREATE TRIGGER exercises_fts_insert AFTER INSERT ON exercises BEGIN
+            DELETE FROM exercises_fts WHERE id = new.id;
+            INSERT INTO exercises_fts (id, name, content, notes, tags, solution)
+            VALUES (new.id, new.name, new.content, new.notes, new.tags, new.solution_text);
+        END;
+
+        CREATE TRIGGER exercises_fts_update AFTER UPDATE ON exercises BEGIN
+            DELETE FROM exercises_fts WHERE id = old.id;
+            INSERT INTO exercises_fts (id, name, content, notes, tags, solution)
+            VALUES (new.id, new.name, new.content, new.notes, new.tags, new.solution_text);
+        END;
+
+        CREATE TRIGGER exercises_fts_delete AFTER DELETE ON exercises BEGIN
+            DELETE FROM exercises_fts WHERE id = old.id;
+        END;
+
+        INSERT INTO exercises_fts (id, name, content, notes, tags, solution)
+        SELECT id, name, content, notes, tags, solution_text FROM exercises;",
+    )
+}
+
+// Every image of an exercise, in display order. The image_path, page_image_path and
+// solution_image_path columns stay as the first image of their kind, so code that
+// only knows about one image keeps working: triggers carry changes to a column over
+// into this table, and commands changing the table write the columns back.
+fn create_exercise_images(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS exercise_images (
+            exercise_id TEXT NOT NULL,
+            path TEXT NOT NULL,
+            kind TEXT NOT NULL CHECK (kind IN ('crop', 'page', 'solution')),
+            position INTEGER NOT NULL,
+            PRIMARY KEY (exercise_id, kind, path)
+        ) WITHOUT ROWID;
+
+        CREATE INDEX IF NOT EXISTS idx_exercise_images_path ON exercise_images (path);
+
+        CREATE TRIGGER IF NOT EXISTS exercise_images_purge AFTER DELETE ON exercises BEGIN
+            DELETE FROM exercise_images WHERE exercise_id = old.id;
+        END;",
+    )?;
+
+    for (position, (kind, column)) in [("crop", "image_path"), ("page", "page_image_path"), ("solution", "solution_image_path")]
+        .into_iter()
+        .enumerate()
+    {
+        let first = format!(
+            "(SELECT path FROM exercise_images WHERE exercise_id = new.id AND kind = '{}' ORDER BY position, path LIMIT 1)",
+            kind
+        );
+        tx.execute_batch(&format!(
+            "CREATE TRIGGER IF NOT EXISTS exercise_images_{kind}_insert
+            AFTER INSERT ON exercises WHEN new.{column} IS NOT NULL
+            BEGIN
+                INSERT OR IGNORE INTO exercise_images (exercise_id, path, kind, position)
+                VALUES (new.id, new.{column}, '{kind}', {position});
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS exercise_images_{kind}_update
+            AFTER UPDATE OF {column} ON exercises WHEN old.{column} IS NOT new.{column}
+            BEGIN
+                -- When the column named the first image, clearing it drops that image
+                -- and changing it swaps the new one into its place
+                DELETE FROM exercise_images
+                WHERE exercise_id = new.id AND kind = '{kind}' AND new.{column} IS NULL
+                  AND path = old.{column} AND path = {first};
+                DELETE FROM exercise_images
+                WHERE exercise_id = new.id AND kind = '{kind}' AND path = new.{column} AND old.{column} = {first};
+                UPDATE exercise_images SET path = new.{column}
+                WHERE exercise_id = new.id AND kind = '{kind}' AND new.{column} IS NOT NULL
+                  AND path = old.{column} AND path = {first};
+
+                -- Otherwise the new image goes in front of the others of its kind
+                INSERT OR REPLACE INTO exercise_images (exercise_id, path, kind, position)
+                SELECT new.id, new.{column}, '{kind}', COALESCE(
+                    (SELECT MIN(position) - 1 FROM exercise_images WHERE exercise_id = new.id AND kind = '{kind}'),
+                    (SELECT COALESCE(MAX(position) + 1, 0) FROM exercise_images WHERE exercise_id = new.id)
+                )
+                WHERE new.{column} IS NOT NULL AND new.{column} IS NOT {first};
+
+                -- Clearing the first of several images promotes the next one
+                UPDATE exercises SET {column} = {first} WHERE id = new.id AND {column} IS NULL;
+            END;
+
+            INSERT OR IGNORE INTO exercise_images (exercise_id, path, kind, position)
+            SELECT id, {column}, '{kind}', {position} FROM exercises WHERE {column} IS NOT NULL;",
+            kind = kind,
+            column = column,
+            position = position,
+            first = first,
+        ))?;
+    }
+    Ok(())
+}
+
+fn create_attachments(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS attachments (
+            id TEXT PRIMARY KEY,
+            exercise_id TEXT NOT NULL,
+            file_name TEXT NOT NULL,
+            path TEXT NOT NULL,
+            mime_type TEXT NOT NULL,
+            size_bytes INTEGER NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_attachments_exercise ON attachments (exercise_id, created_at);
+
+        CREATE TRIGGER IF NOT EXISTS attachments_purge AFTER DELETE ON exercises BEGIN
+            DELETE FROM attachments WHERE exercise_id = old.id;
+        END;",
+    )
+}
+
+// One link per ordered pair; linking again changes the relation.
+fn create_exercise_links(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS exercise_links (
+            from_id TEXT NOT NULL,
+            to_id TEXT NOT NULL,
+            relation TEXT NOT NULL CHECK (relation IN ('related', 'prerequisite', 'duplicate-of')),
+            linked_at INTEGER NOT NULL,
+            PRIMARY KEY (from_id, to_id),
+            CHECK (from_id != to_id)
+        ) WITHOUT ROWID;
+
+        CREATE INDEX IF NOT EXISTS idx_exercise_links_to ON exercise_links (to_id);
+
+        CREATE TRIGGER IF NOT EXISTS exercise_links_purge AFTER DELETE ON exercises BEGIN
+            DELETE FROM exercise_links WHERE from_id = old.id OR to_id = old.id;
+        END;",
+    )
+}
+
+fn add_parent_id(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "parent_id", "TEXT")?;
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_exercises_parent ON exercises (parent_id) WHERE parent_id IS NOT NULL;
+
+        -- A part whose parent is purged stands on its own
+        CREATE TRIGGER IF NOT EXISTS exercises_parent_purge AFTER DELETE ON exercises BEGIN
+            UPDATE exercises SET parent_id = NULL WHERE parent_id = old.id;
+        END;",
+    )
+}
+
+fn add_source_file(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "exercises", "source_file", "TEXT")?;
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_exercises_source_file ON exercises (source_file) WHERE source_file IS NOT NULL;",
+    )
+}
+
+fn purge_trashed_parts(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "DROP TRIGGER IF EXISTS exercises_parent_purge;
+
+        -- Parts trashed along with their parent are purged with it; a live part
+        -- stands on its own
+        CREATE TRIGGER exercises_parent_purge AFTER DELETE ON exercises BEGIN
+            DELETE FROM exercises WHERE parent_id = old.id AND deleted_at IS NOT NULL;
+            UPDATE exercises SET parent_id = NULL WHERE parent_id = old.id;
+        END;",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_connection;
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn migrations_can_run_again() {
+        let mut conn = test_connection();
+        run(&mut conn).unwrap();
+    }
+
+    #[test]
+    fn purging_an_exercise_purges_what_belongs_to_it() {
+        let conn = test_connection();
+        conn.execute_batch(
+            "INSERT INTO exercises (id, name, course, week, created_at, tags, deleted_at, parent_id) VALUES
+                 ('parent', 'Ex 1', 'Algebra', 1, 0, '[]', NULL, NULL),
+                 ('trashed_part', 'Ex 1a', 'Algebra', 1, 0, '[]', NULL, 'parent'),
+                 ('live_part', 'Ex 1b', 'Algebra', 1, 0, '[]', NULL, 'parent'),
+                 ('other', 'Ex 2', 'Algebra', 1, 0, '[]', NULL, NULL);
+             INSERT INTO attempts (exercise_id, attempted_at, outcome) VALUES
+                 ('parent', 1, 'solved'), ('trashed_part', 1, 'failed'), ('other', 1, 'partial');
+             INSERT INTO exercise_images (exercise_id, path, kind, position) VALUES
+                 ('parent', 'p.png', 'page', 0), ('trashed_part', 'a.png', 'crop', 0);
+             INSERT INTO exercise_links (from_id, to_id, relation, linked_at) VALUES
+                 ('parent', 'other', 'related', 0);
+             UPDATE exercises SET deleted_at = 5 WHERE id IN ('parent', 'trashed_part');",
+        )
+        .unwrap();
+        assert!(count(&conn, "SELECT COUNT(*) FROM exercise_history WHERE exercise_id = 'parent'") > 0);
+
+        conn.execute("DELETE FROM exercises WHERE id = 'parent'", []).unwrap();
+
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM exercises WHERE id IN ('parent', 'trashed_part')"), 0);
+        assert_eq!(
+            count(&conn, "SELECT COUNT(*) FROM exercises WHERE id = 'live_part' AND parent_id IS NULL"),
+            1
+        );
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM attempts"), 1);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM exercise_images"), 0);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM exercise_links"), 0);
+        assert_eq!(
+            count(&conn, "SELECT COUNT(*) FROM exercise_history WHERE exercise_id IN ('parent', 'trashed_part')"),
+            0
+        );
+    }
+
+    #[test]
+    fn legacy_databases_keep_their_data() {
+        // The flat table the first releases created, before tags and notes existed
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE exercises (
+                id TEXT PRIMARY KEY,
+                name TEXT,
+                course TEXT,
+                week INTEGER,
+                content TEXT,
+                image_path TEXT,
+                page_image_path TEXT,
+                bounding_box TEXT,
+                created_at INTEGER
+            );
+            INSERT INTO exercises VALUES
+                ('a', 'Ex 1', 'Algebra', 3, 'Solve x', '/img/a.png', '/img/page.png', '{"y":0.25,"height":0.5}', 1700000000000),
+                ('b', 'Ex 2', 'Analysis 2', 0, NULL, NULL, NULL, NULL, 1700000000001);"#,
+        )
+        .unwrap();
+
+        run(&mut conn).unwrap();
+
+        type Row = (String, String, String, i64, Option<String>, Option<String>, Option<String>, i64);
+        let rows: Vec<Row> = conn
+            .prepare("SELECT id, name, course, week, content, image_path, page_image_path, created_at FROM exercises ORDER BY id")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                    row.get(7)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let text = |value: &str| Some(value.to_string());
+        assert_eq!(
+            rows,
+            [
+                (
+                    "a".to_string(),
+                    "Ex 1".to_string(),
+                    "Algebra".to_string(),
+                    3,
+                    text("Solve x"),
+                    text("/img/a.png"),
+                    text("/img/page.png"),
+                    1700000000000
+                ),
+                ("b".to_string(), "Ex 2".to_string(), "Analysis 2".to_string(), 0, None, None, None, 1700000000001),
+            ]
+        );
+
+        // Columns added since are filled in rather than left to break reads. Only
+        // 'b' keeps its backfilled updated_at, as converting 'a's box touched it.
+        let added: (String, Option<String>, String, i64) = conn
+            .query_row("SELECT tags, notes, status, updated_at FROM exercises WHERE id = 'b'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap();
+        assert_eq!(added, ("[]".to_string(), None, "todo".to_string(), 1700000000001));
+
+        // The single box became a one-page list
+        let bounding_box: String = conn
+            .query_row("SELECT bounding_box FROM exercises WHERE id = 'a'", [], |row| row.get(0))
+            .unwrap();
+        let boxes: serde_json::Value = serde_json::from_str(&bounding_box).unwrap();
+        assert_eq!(boxes, serde_json::json!([{ "y": 0.25, "height": 0.5, "page": 1 }]));
+
+        assert_eq!(
+            count(&conn, "SELECT COUNT(*) FROM exercise_images WHERE exercise_id = 'a'"),
+            2,
+            "both image columns should be listed as images"
+        );
+        assert_eq!(count(&conn, "PRAGMA user_version"), MIGRATIONS.len() as i64);
+    }
+}
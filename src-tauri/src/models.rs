@@ -0,0 +1,380 @@
+use rusqlite::{params, Connection, Row};
+
+use crate::error::AppError;
+use crate::exercise_images;
+use crate::review::ReviewState;
+use serde::{Deserialize, Serialize};
+
+/// A full-width band of one page, in pixels of that page's image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoundingBox {
+    /// 1-based page of the source PDF; 1 for exercises cut from a single image.
+    #[serde(default = "first_page")]
+    pub page: u32,
+    pub y: f64,
+    pub height: f64,
+}
+
+fn first_page() -> u32 {
+    1
+}
+
+/// What an image attached to an exercise shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageKind {
+    /// The exercise cut out of its page.
+    Crop,
+    /// The full page it was cut from.
+    Page,
+    Solution,
+}
+
+impl ImageKind {
+    /// The value stored in the `kind` column.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ImageKind::Crop => "crop",
+            ImageKind::Page => "page",
+            ImageKind::Solution => "solution",
+        }
+    }
+
+    pub fn from_column(value: &str) -> Self {
+        match value {
+            "page" => ImageKind::Page,
+            "solution" => ImageKind::Solution,
+            _ => ImageKind::Crop,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExerciseImage {
+    pub path: String,
+    pub kind: ImageKind,
+    /// Display order among all the exercise's images, lowest first.
+    pub position: i64,
+}
+
+/// How far the user has got with an exercise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExerciseStatus {
+    #[default]
+    Todo,
+    InProgress,
+    Done,
+}
+
+impl ExerciseStatus {
+    /// The value stored in the `status` column.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExerciseStatus::Todo => "todo",
+            ExerciseStatus::InProgress => "in_progress",
+            ExerciseStatus::Done => "done",
+        }
+    }
+
+    fn from_column(value: &str) -> Self {
+        match value {
+            "in_progress" => ExerciseStatus::InProgress,
+            "done" => ExerciseStatus::Done,
+            _ => ExerciseStatus::Todo,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exercise {
+    pub id: String,
+    pub name: String,
+    pub tags: Vec<String>,
+    pub course: String,
+    pub week: i64,
+    pub content: Option<String>,
+    pub notes: Option<String>,
+    #[serde(rename = "imageUri")]
+    pub image_uri: Option<String>,
+    #[serde(rename = "pageImageUri")]
+    pub page_image_uri: Option<String>,
+    /// One box per page the exercise covers, on consecutive pages in order.
+    #[serde(
+        rename = "boundingBoxes",
+        alias = "boundingBox",
+        default,
+        deserialize_with = "one_or_many"
+    )]
+    pub bounding_boxes: Vec<BoundingBox>,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    /// Display position within the exercise's course/week.
+    #[serde(rename = "orderIndex", default)]
+    pub order_index: Option<i64>,
+    /// 1-based page of the source PDF the exercise was extracted from.
+    #[serde(rename = "sourcePage", default)]
+    pub source_page: Option<i64>,
+    /// File name of the PDF or image the exercise was extracted from.
+    #[serde(rename = "sourceFile", default)]
+    pub source_file: Option<String>,
+    /// When the exercise was moved to the trash, if it has been.
+    #[serde(rename = "deletedAt", default)]
+    pub deleted_at: Option<i64>,
+    /// Last time anything about the exercise changed. Set by the database; ignored
+    /// when saving.
+    #[serde(rename = "updatedAt", default)]
+    pub updated_at: Option<i64>,
+    #[serde(default)]
+    pub status: ExerciseStatus,
+    /// 1 to 5 stars, unset until the user rates it.
+    #[serde(default)]
+    pub difficulty: Option<i64>,
+    #[serde(rename = "isFavorite", default)]
+    pub is_favorite: bool,
+    /// Deadline in Unix milliseconds, for homework.
+    #[serde(rename = "dueAt", default)]
+    pub due_at: Option<i64>,
+    /// Spaced repetition schedule; unset until the first review.
+    #[serde(default)]
+    pub review: Option<ReviewState>,
+    /// Time tracked with the timer, over finished sessions.
+    #[serde(rename = "totalSeconds", default)]
+    pub total_seconds: i64,
+    /// The user's worked solution.
+    #[serde(rename = "solutionText", default)]
+    pub solution_text: Option<String>,
+    #[serde(rename = "solutionImageUri", default)]
+    pub solution_image_uri: Option<String>,
+    /// Every image of the exercise in display order. `imageUri`, `pageImageUri` and
+    /// `solutionImageUri` are the first image of their kind. Stored only with a new
+    /// exercise; after that the image commands change them.
+    #[serde(default)]
+    pub images: Vec<ExerciseImage>,
+    /// The exercise this is a part of, e.g. 3.2 for part (b) of 3.2. Parts are
+    /// never nested further.
+    #[serde(rename = "parentId", default)]
+    pub parent_id: Option<String>,
+}
+
+/// Fields to change with `update_exercise`. Anything left out keeps its stored
+/// value; `null` clears the nullable ones.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExerciseUpdate {
+    pub name: Option<String>,
+    pub tags: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub notes: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub content: Option<Option<String>>,
+    pub course: Option<String>,
+    pub week: Option<i64>,
+    /// An empty list clears the boxes.
+    pub bounding_boxes: Option<Vec<BoundingBox>>,
+    pub status: Option<ExerciseStatus>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub difficulty: Option<Option<i64>>,
+    pub is_favorite: Option<bool>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub due_at: Option<Option<i64>>,
+}
+
+/// Tells a field sent as `null` (`Some(None)`) apart from one left out (`None`).
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+pub fn check_difficulty(difficulty: i64) -> Result<(), AppError> {
+    if !(1..=5).contains(&difficulty) {
+        return Err(AppError::InvalidInput(format!(
+            "Difficulty must be between 1 and 5, not {}",
+            difficulty
+        )));
+    }
+    Ok(())
+}
+
+/// Reads a list of boxes, or the single box (or `null`) of exports made before
+/// exercises could span pages.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<BoundingBox>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        Many(Vec<BoundingBox>),
+        One(BoundingBox),
+    }
+
+    Ok(match Option::<OneOrMany>::deserialize(deserializer)? {
+        Some(OneOrMany::Many(boxes)) => boxes,
+        Some(OneOrMany::One(bbox)) => vec![bbox],
+        None => Vec::new(),
+    })
+}
+
+/// The `bounding_box` column value: a JSON array, or NULL when there are no boxes.
+pub fn bounding_boxes_column(boxes: &[BoundingBox]) -> Result<Option<String>, AppError> {
+    if boxes.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(boxes)?))
+}
+
+/// One page of a listing plus the number of rows matching its filters.
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+}
+
+pub type ExercisePage = Page<Exercise>;
+
+/// Columns read by `Exercise::from_row`, ready to drop into a SELECT list.
+pub const EXERCISE_COLUMNS: &str =
+    "id, name, tags, course, week, content, notes, image_path, page_image_path, bounding_box, created_at, order_index, source_page, deleted_at, updated_at, status, difficulty, is_favorite, due_at, ease_factor, interval_days, repetitions, next_review_at, total_seconds, solution_text, solution_image_path, parent_id, source_file,
+     (SELECT json_group_array(json_object('path', path, 'kind', kind, 'position', position))
+      FROM (SELECT path, kind, position FROM exercise_images WHERE exercise_id = id ORDER BY position, path)) AS images";
+
+impl Exercise {
+    pub fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let tags_str: String = row.get("tags")?;
+        let tags: Vec<String> = serde_json::from_str(&tags_str).unwrap_or_default();
+
+        let bbox_str: Option<String> = row.get("bounding_box")?;
+        let bounding_boxes: Vec<BoundingBox> = bbox_str
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        Ok(Exercise {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            tags,
+            course: row.get("course")?,
+            week: row.get("week")?,
+            content: row.get("content")?,
+            notes: row.get("notes")?,
+            image_uri: row.get("image_path")?,
+            page_image_uri: row.get("page_image_path")?,
+            bounding_boxes,
+            created_at: row.get("created_at")?,
+            order_index: row.get("order_index")?,
+            source_page: row.get("source_page")?,
+            source_file: row.get("source_file")?,
+            deleted_at: row.get("deleted_at")?,
+            updated_at: row.get("updated_at")?,
+            status: ExerciseStatus::from_column(&row.get::<_, String>("status")?),
+            difficulty: row.get("difficulty")?,
+            is_favorite: row.get("is_favorite")?,
+            due_at: row.get("due_at")?,
+            review: match row.get::<_, Option<i64>>("next_review_at")? {
+                Some(next_review_at) => Some(ReviewState {
+                    ease_factor: row.get("ease_factor")?,
+                    interval_days: row.get("interval_days")?,
+                    repetitions: row.get("repetitions")?,
+                    next_review_at,
+                }),
+                None => None,
+            },
+            total_seconds: row.get("total_seconds")?,
+            solution_text: row.get("solution_text")?,
+            solution_image_uri: row.get("solution_image_path")?,
+            images: serde_json::from_str(&row.get::<_, String>("images")?).unwrap_or_default(),
+            parent_id: row.get("parent_id")?,
+        })
+    }
+    /// Inserts the exercise, or updates it in place if the id exists so that update
+    /// triggers (search index, edit history) fire. Without an explicit `order_index`
+    /// the stored position is kept, or the exercise is appended to the end of its week.
+    /// `images` is only written for a new exercise.
+    pub fn upsert(&self, conn: &Connection) -> Result<(), AppError> {
+        if let Some(difficulty) = self.difficulty {
+            check_difficulty(difficulty)?;
+        }
+        let is_new = !conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM exercises WHERE id = ?1)",
+            params![self.id],
+            |row| row.get::<_, bool>(0),
+        )?;
+        let tags_str = serde_json::to_string(&self.tags)?;
+        let bbox_str = bounding_boxes_column(&self.bounding_boxes)?;
+
+        conn.execute(
+            "INSERT INTO exercises (id, name, tags, course, week, content, notes, image_path, page_image_path, bounding_box, created_at, order_index, source_page, deleted_at, updated_at, status, difficulty, is_favorite, due_at, ease_factor, interval_days, repetitions, next_review_at, total_seconds, solution_text, solution_image_path, parent_id, source_file)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, COALESCE(
+                 ?12,
+                 (SELECT order_index FROM exercises WHERE id = ?1),
+                 (SELECT COALESCE(MAX(order_index), -1) + 1 FROM exercises WHERE course = ?4 AND week = ?5)
+             ), ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28)
+             ON CONFLICT(id) DO UPDATE SET
+                 name = excluded.name,
+                 tags = excluded.tags,
+                 course = excluded.course,
+                 week = excluded.week,
+                 content = excluded.content,
+                 notes = excluded.notes,
+                 image_path = excluded.image_path,
+                 page_image_path = excluded.page_image_path,
+                 bounding_box = excluded.bounding_box,
+                 created_at = excluded.created_at,
+                 order_index = excluded.order_index,
+                 source_page = excluded.source_page,
+                 deleted_at = excluded.deleted_at,
+                 updated_at = excluded.updated_at,
+                 status = excluded.status,
+                 difficulty = excluded.difficulty,
+                 is_favorite = excluded.is_favorite,
+                 due_at = excluded.due_at,
+                 ease_factor = excluded.ease_factor,
+                 interval_days = excluded.interval_days,
+                 repetitions = excluded.repetitions,
+                 next_review_at = excluded.next_review_at,
+                 total_seconds = excluded.total_seconds,
+                 solution_text = excluded.solution_text,
+                 solution_image_path = excluded.solution_image_path,
+                 parent_id = excluded.parent_id,
+                 source_file = excluded.source_file",
+            params![
+                self.id,
+                self.name,
+                tags_str,
+                self.course,
+                self.week,
+                self.content,
+                self.notes,
+                self.image_uri,
+                self.page_image_uri,
+                bbox_str,
+                self.created_at,
+                self.order_index,
+                self.source_page,
+                self.deleted_at,
+                chrono::Utc::now().timestamp_millis(),
+                self.status.as_str(),
+                self.difficulty,
+                self.is_favorite,
+                self.due_at,
+                self.review.as_ref().map(|review| review.ease_factor),
+                self.review.as_ref().map(|review| review.interval_days),
+                self.review.as_ref().map(|review| review.repetitions),
+                self.review.as_ref().map(|review| review.next_review_at),
+                self.total_seconds,
+                self.solution_text,
+                self.solution_image_uri,
+                self.parent_id,
+                self.source_file,
+            ],
+        )?;
+
+        if is_new && !self.images.is_empty() {
+            exercise_images::insert_images(conn, &self.id, &self.images)?;
+        }
+        Ok(())
+    }
+}
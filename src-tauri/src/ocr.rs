@@ -0,0 +1,94 @@
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::OnceLock;
+use tracing::{debug, warn};
+
+use crate::error::AppError;
+
+/// Checked in order, like the pdftoppm paths: bundled apps don't see the shell's PATH.
+const TESSERACT_PATHS: &[&str] = &[
+    "/opt/homebrew/bin/tesseract", // Apple Silicon Homebrew
+    "/usr/local/bin/tesseract",    // Intel Homebrew
+    "tesseract",                   // System PATH
+];
+
+/// An exercise found in OCR text: its heading and everything up to the next one.
+pub struct OcrExercise {
+    pub name: String,
+    pub content: String,
+}
+
+/// Runs the `tesseract` CLI over an image file and returns the recognized text.
+fn recognize_text(image_path: &Path) -> Result<String, AppError> {
+    for tesseract in TESSERACT_PATHS {
+        match Command::new(tesseract).arg(image_path).arg("stdout").output() {
+            Ok(output) if output.status.success() => {
+                debug!("Recognized text with {}", tesseract);
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+            }
+            Ok(output) => warn!(
+                "{} failed: {}",
+                tesseract,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(_) => continue,
+        }
+    }
+    Err(AppError::Io(
+        "Local OCR needs Tesseract; install it (e.g. `brew install tesseract`) or add a Gemini API key".to_string(),
+    ))
+}
+
+/// Lines starting an exercise: "Exercise 3", "Problem 2.1", "Task 4b", "Q5", ...
+fn heading_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?im)^[ \t]*(?:exercise|problem|task|question|ex\.?|q)[ \t]*(\d+(?:\.\d+)*[a-z]?)\b")
+            .expect("heading pattern is valid")
+    })
+}
+
+/// Splits OCR text at exercise headings. Text before the first heading is dropped;
+/// a page without any heading becomes a single exercise.
+fn split_exercises(text: &str) -> Vec<OcrExercise> {
+    let headings: Vec<_> = heading_pattern().captures_iter(text).collect();
+    if headings.is_empty() {
+        let content = text.trim();
+        if content.is_empty() {
+            return Vec::new();
+        }
+        return vec![OcrExercise {
+            name: "Untitled exercise".to_string(),
+            content: content.to_string(),
+        }];
+    }
+
+    headings
+        .iter()
+        .enumerate()
+        .map(|(index, captures)| {
+            let start = captures.get(0).map_or(0, |m| m.start());
+            let end = headings
+                .get(index + 1)
+                .and_then(|next| next.get(0))
+                .map_or(text.len(), |m| m.start());
+            OcrExercise {
+                name: format!("Exercise {}", &captures[1]),
+                content: text[start..end].trim().to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Extracts exercises from image bytes without calling Gemini: the text is read
+/// with Tesseract and split at headings like "Exercise 3" or "Problem 2.1".
+pub fn extract_exercises(image: &[u8]) -> Result<Vec<OcrExercise>, AppError> {
+    let temp_path = std::env::temp_dir().join(format!("vaulty_ocr_{}.png", uuid::Uuid::new_v4()));
+    fs::write(&temp_path, image)?;
+    let text = recognize_text(&temp_path);
+    let _ = fs::remove_file(&temp_path);
+
+    Ok(split_exercises(&text?))
+}
@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle, Manager, Runtime, State};
+use tracing::info;
+
+use crate::backup::write_atomically;
+use crate::db::{configured_data_dir, get_app_data_dir, init_db, set_data_dir, DbPool, StartupError};
+use crate::error::AppError;
+use crate::gemini::HttpClient;
+use crate::storage::remove_database_files;
+
+/// Registry of vault profiles, next to `data_dir` in the app data dir. Which one is
+/// open is still decided by `data_dir`; this only names the directories.
+const VAULTS_CONFIG: &str = "vaults.json";
+
+/// Name given to the vault that existed before any profiles were created.
+const DEFAULT_VAULT_NAME: &str = "Default";
+
+/// Emitted with the new vault's name once `switch_vault` has opened it.
+pub const VAULT_SWITCHED_EVENT: &str = "vault-switched";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultEntry {
+    name: String,
+    path: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VaultProfile {
+    pub name: String,
+    pub path: String,
+    pub active: bool,
+    /// False when the directory is missing, e.g. on an unplugged drive.
+    pub available: bool,
+}
+
+/// The data dir in use, whether or not it is currently reachable.
+fn active_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, AppError> {
+    match configured_data_dir(app)? {
+        Some(dir) => Ok(dir),
+        None => get_app_data_dir(app),
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    a == b
+        || match (a.canonicalize(), b.canonicalize()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+}
+
+/// Registered vaults. Without a registry the current data dir is the only vault.
+fn load_vaults<R: Runtime>(app: &AppHandle<R>) -> Result<Vec<VaultEntry>, AppError> {
+    match fs::read(get_app_data_dir(app)?.join(VAULTS_CONFIG)) {
+        Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![VaultEntry {
+            name: DEFAULT_VAULT_NAME.to_string(),
+            path: active_dir(app)?,
+        }]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save_vaults<R: Runtime>(app: &AppHandle<R>, vaults: &[VaultEntry]) -> Result<(), AppError> {
+    let contents = serde_json::to_vec_pretty(vaults)?;
+    write_atomically(&get_app_data_dir(app)?.join(VAULTS_CONFIG), |temp_path| {
+        Ok(fs::write(temp_path, &contents)?)
+    })
+}
+
+fn find_vault(vaults: &[VaultEntry], name: &str) -> Result<usize, AppError> {
+    vaults
+        .iter()
+        .position(|vault| vault.name.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| AppError::NotFound(format!("vault {}", name)))
+}
+
+/// Records that the open vault's data now lives in `new_dir`, after `move_data_directory`.
+pub fn active_vault_moved<R: Runtime>(app: &AppHandle<R>, old_dir: &Path, new_dir: &Path) -> Result<(), AppError> {
+    if !get_app_data_dir(app)?.join(VAULTS_CONFIG).exists() {
+        return Ok(());
+    }
+    let mut vaults = load_vaults(app)?;
+    for vault in vaults.iter_mut().filter(|vault| same_dir(&vault.path, old_dir)) {
+        vault.path = new_dir.to_path_buf();
+    }
+    save_vaults(app, &vaults)
+}
+
+#[command]
+pub fn list_vaults<R: Runtime>(app: AppHandle<R>) -> Result<Vec<VaultProfile>, AppError> {
+    let active = active_dir(&app)?;
+    Ok(load_vaults(&app)?
+        .into_iter()
+        .map(|vault| VaultProfile {
+            active: same_dir(&vault.path, &active),
+            available: vault.path.is_dir(),
+            path: vault.path.to_string_lossy().into_owned(),
+            name: vault.name,
+        })
+        .collect())
+}
+
+/// Registers a new vault without switching to it. Its data goes in `path`, or in a
+/// directory of its own under the app data dir. A directory that already holds a
+/// vault (copied from another machine, say) is registered as it is.
+#[command]
+pub fn create_vault<R: Runtime>(
+    app: AppHandle<R>,
+    name: String,
+    path: Option<String>,
+) -> Result<VaultProfile, AppError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("Vault name cannot be empty".to_string()));
+    }
+
+    let mut vaults = load_vaults(&app)?;
+    if find_vault(&vaults, &name).is_ok() {
+        return Err(AppError::InvalidInput(format!("A vault named {} already exists", name)));
+    }
+
+    let dir = match path.as_deref().map(str::trim) {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => {
+            let dir_name: String = name
+                .chars()
+                .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+                .collect();
+            get_app_data_dir(&app)?.join("vaults").join(dir_name)
+        }
+    };
+    if !dir.is_absolute() {
+        return Err(AppError::InvalidInput(format!("Not an absolute path: {}", dir.display())));
+    }
+    fs::create_dir_all(&dir)?;
+    let dir = dir.canonicalize()?;
+    if let Some(existing) = vaults.iter().find(|vault| same_dir(&vault.path, &dir)) {
+        return Err(AppError::InvalidInput(format!(
+            "{} is already used by the vault {}",
+            dir.display(),
+            existing.name
+        )));
+    }
+
+    vaults.push(VaultEntry {
+        name: name.clone(),
+        path: dir.clone(),
+    });
+    save_vaults(&app, &vaults)?;
+
+    info!("Created vault {} in {:?}", name, dir);
+    Ok(VaultProfile {
+        name,
+        path: dir.to_string_lossy().into_owned(),
+        active: false,
+        available: true,
+    })
+}
+
+/// Closes the open vault and opens `name` in its place, migrating it first if it is
+/// new or older. An encrypted vault is left locked for the frontend to unlock. On
+/// failure the previous vault is reopened.
+#[command]
+pub fn switch_vault<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    http: State<'_, HttpClient>,
+    startup_error: State<'_, StartupError>,
+    name: String,
+) -> Result<(), AppError> {
+    let vaults = load_vaults(&app)?;
+    let vault = &vaults[find_vault(&vaults, &name)?];
+    if !vault.path.is_dir() {
+        return Err(AppError::Io(format!(
+            "The vault directory {} is not available",
+            vault.path.display()
+        )));
+    }
+
+    let old_dir = active_dir(&app)?;
+    if same_dir(&vault.path, &old_dir) {
+        return Ok(());
+    }
+    let is_default = same_dir(&vault.path, &get_app_data_dir(&app)?);
+
+    let result = set_data_dir(&app, (!is_default).then_some(vault.path.as_path()))
+        .and_then(|_| pool.switch(vault.path.join("vaulty.db")))
+        .and_then(|_| if pool.is_locked() { Ok(()) } else { init_db(&pool) });
+    // Whichever vault ends up open, its proxy setting applies from now on
+    http.reset();
+    if let Err(e) = result {
+        let old_is_default = same_dir(&old_dir, &get_app_data_dir(&app)?);
+        let _ = set_data_dir(&app, (!old_is_default).then_some(old_dir.as_path()));
+        let _ = pool.switch(old_dir.join("vaulty.db"));
+        return Err(e);
+    }
+
+    *startup_error.0.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    info!("Switched to vault {} in {:?}", vault.name, vault.path);
+    app.emit_all(VAULT_SWITCHED_EVENT, &vault.name)
+        .map_err(|e| AppError::Io(format!("Failed to notify windows of the switch: {}", e)))?;
+    Ok(())
+}
+
+/// Removes a vault from the registry and deletes its database, images and
+/// attachments. Nothing else in its directory is touched, and the directory itself
+/// only goes if that leaves it empty. The open vault can't be deleted; switch away from it first.
+#[command]
+pub fn delete_vault<R: Runtime>(app: AppHandle<R>, name: String, confirm: bool) -> Result<(), AppError> {
+    if !confirm {
+        return Err(AppError::InvalidInput(
+            "Deleting a vault permanently removes its exercises and images; confirm to continue".to_string(),
+        ));
+    }
+
+    let mut vaults = load_vaults(&app)?;
+    let vault = vaults.remove(find_vault(&vaults, &name)?);
+    if same_dir(&vault.path, &active_dir(&app)?) {
+        return Err(AppError::InvalidInput("Switch to another vault before deleting this one".to_string()));
+    }
+
+    let images_dir = vault.path.join("images");
+    if let Some(nested) = vaults.iter().find(|other| other.path.starts_with(&images_dir)) {
+        return Err(AppError::InvalidInput(format!(
+            "The vault {} is stored inside this vault's images",
+            nested.name
+        )));
+    }
+
+    if vault.path.is_dir() {
+        remove_database_files(&vault.path.join("vaulty.db"));
+        for dir in [images_dir, vault.path.join("attachments"), vault.path.join("restore.bak")] {
+            match fs::remove_dir_all(&dir) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        let _ = fs::remove_dir(&vault.path);
+    }
+    save_vaults(&app, &vaults)?;
+
+    info!("Deleted vault {} in {:?}", vault.name, vault.path);
+    Ok(())
+}
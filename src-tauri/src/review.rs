@@ -0,0 +1,187 @@
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+use crate::courses::NOT_ARCHIVED;
+use crate::db::{get_connection, DbPool};
+use crate::error::AppError;
+use crate::models::{Exercise, EXERCISE_COLUMNS};
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// SM-2 starts every item at this ease and never lets it drop below the minimum.
+const INITIAL_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+
+/// Exercises never reviewed are due this many days after they were added.
+const DEFAULT_INTERVAL_DAYS: i64 = 1;
+
+/// SM-2 scheduling state of an exercise that has been reviewed at least once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewState {
+    pub ease_factor: f64,
+    pub interval_days: i64,
+    /// Successful reviews in a row; a failed one resets it.
+    pub repetitions: i64,
+    /// Unix milliseconds.
+    pub next_review_at: i64,
+}
+
+impl ReviewState {
+    /// The state after a review graded `grade` (0–5, 3 and up counting as
+    /// recalled) at `now`, following SM-2: a failed review starts the intervals
+    /// over, a successful one grows them by the ease factor, and the ease moves
+    /// with how easy the grade says it was.
+    fn after(previous: Option<&ReviewState>, grade: u8, now: i64) -> ReviewState {
+        let (ease, interval, repetitions) = previous
+            .map_or((INITIAL_EASE, 0, 0), |state| (state.ease_factor, state.interval_days, state.repetitions));
+
+        let (interval_days, repetitions) = if grade >= 3 {
+            let interval = match repetitions {
+                0 => 1,
+                1 => 6,
+                _ => (interval as f64 * ease).round() as i64,
+            };
+            (interval, repetitions + 1)
+        } else {
+            (1, 0)
+        };
+
+        let miss = f64::from(5 - grade);
+        let ease_factor = (ease + 0.1 - miss * (0.08 + miss * 0.02)).max(MIN_EASE);
+
+        ReviewState {
+            ease_factor,
+            interval_days,
+            repetitions,
+            next_review_at: now + interval_days * DAY_MS,
+        }
+    }
+}
+
+/// Schedules the next review of an exercise from how well it went: 0 is a
+/// complete blackout, 5 a perfect answer. Returns the new schedule.
+#[command]
+pub fn record_review(pool: State<'_, DbPool>, id: String, grade: u8) -> Result<ReviewState, AppError> {
+    if grade > 5 {
+        return Err(AppError::InvalidInput(format!("Grade must be between 0 and 5, not {}", grade)));
+    }
+
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    let exercise = tx
+        .query_row(
+            &format!("SELECT {} FROM exercises WHERE id = ?1 AND deleted_at IS NULL", EXERCISE_COLUMNS),
+            params![id],
+            Exercise::from_row,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("exercise {}", id)))?;
+
+    let state = ReviewState::after(exercise.review.as_ref(), grade, chrono::Utc::now().timestamp_millis());
+    tx.execute(
+        "UPDATE exercises SET ease_factor = ?1, interval_days = ?2, repetitions = ?3, next_review_at = ?4
+         WHERE id = ?5",
+        params![state.ease_factor, state.interval_days, state.repetitions, state.next_review_at, id],
+    )?;
+    tx.commit()?;
+
+    Ok(state)
+}
+
+/// Exercises due for review, most urgent first: the further past due relative to
+/// its interval, the more urgent. Exercises never reviewed are due a day after
+/// they were added. Trashed exercises and archived courses are left out.
+#[command]
+pub fn get_review_queue(pool: State<'_, DbPool>, limit: Option<i64>) -> Result<Vec<Exercise>, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "WITH due AS (
+             SELECT *,
+                    COALESCE(next_review_at, created_at + ?1 * {day}) AS due_at_ms,
+                    COALESCE(interval_days, ?1) AS period
+             FROM exercises
+             WHERE deleted_at IS NULL AND {not_archived}
+         )
+         SELECT {columns} FROM due
+         WHERE due_at_ms <= ?2
+         ORDER BY (?2 - due_at_ms) * 1.0 / MAX(period, 1) DESC, due_at_ms, id
+         LIMIT ?3",
+        day = DAY_MS,
+        not_archived = NOT_ARCHIVED,
+        columns = EXERCISE_COLUMNS,
+    ))?;
+    let queue = stmt
+        // A negative LIMIT means no limit in SQLite
+        .query_map(
+            params![DEFAULT_INTERVAL_DAYS, chrono::Utc::now().timestamp_millis(), limit.unwrap_or(-1)],
+            Exercise::from_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(queue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(ease_factor: f64, interval_days: i64, repetitions: i64) -> ReviewState {
+        ReviewState { ease_factor, interval_days, repetitions, next_review_at: 0 }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn ease_moves_with_each_grade() {
+        for (grade, ease) in [(0, 1.7), (1, 1.96), (2, 2.18), (3, 2.36), (4, 2.5), (5, 2.6)] {
+            assert_close(ReviewState::after(None, grade, 0).ease_factor, ease);
+        }
+    }
+
+    #[test]
+    fn intervals_grow_on_recalled_grades() {
+        let now = 1_000;
+        let first = ReviewState::after(None, 4, now);
+        assert_eq!((first.interval_days, first.repetitions), (1, 1));
+        assert_eq!(first.next_review_at, now + DAY_MS);
+
+        let second = ReviewState::after(Some(&first), 4, now);
+        assert_eq!((second.interval_days, second.repetitions), (6, 2));
+
+        let third = ReviewState::after(Some(&second), 4, now);
+        assert_eq!((third.interval_days, third.repetitions), (15, 3));
+        assert_eq!(third.next_review_at, now + 15 * DAY_MS);
+
+        // A higher ease stretches the interval further
+        let easy = ReviewState::after(Some(&state(2.6, 6, 2)), 5, now);
+        assert_eq!(easy.interval_days, 16);
+        assert_close(easy.ease_factor, 2.7);
+    }
+
+    #[test]
+    fn failed_grades_start_over() {
+        for grade in 0..3 {
+            let next = ReviewState::after(Some(&state(2.5, 30, 5)), grade, 0);
+            assert_eq!((next.interval_days, next.repetitions), (1, 0), "grade {}", grade);
+            assert_eq!(next.next_review_at, DAY_MS);
+        }
+    }
+
+    #[test]
+    fn ease_never_drops_below_the_floor() {
+        let next = ReviewState::after(Some(&state(1.4, 6, 2)), 0, 0);
+        assert_close(next.ease_factor, MIN_EASE);
+
+        let mut review = None;
+        for _ in 0..10 {
+            review = Some(ReviewState::after(review.as_ref(), 3, 0));
+        }
+        assert!(review.unwrap().ease_factor >= MIN_EASE);
+    }
+}
@@ -0,0 +1,124 @@
+use rusqlite::params;
+use serde::Serialize;
+use tauri::{command, State};
+
+use crate::db::{get_connection, DbPool};
+use crate::error::AppError;
+use crate::models::{Exercise, Page, EXERCISE_COLUMNS};
+use crate::sort::SortBy;
+
+// Marker used to detect which columns a highlight() call touched; NULL columns never match
+const HIT_MARKER: &str = "char(1)";
+
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    pub exercise: Exercise,
+    /// Fields containing at least one query term: "name", "content", "notes", "tags"
+    /// or "solution".
+    #[serde(rename = "matchedFields")]
+    pub matched_fields: Vec<String>,
+    /// Excerpt from the best matching field with hits wrapped in `**`.
+    pub snippet: String,
+}
+
+/// Turns free text into an FTS5 query that can't fail to parse: each word becomes
+/// a quoted prefix term, so operators and quotes typed by the user are literal.
+fn build_match_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| term.replace('"', ""))
+        .filter(|term| term.chars().any(char::is_alphanumeric))
+        .map(|term| format!("\"{}\"*", term))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Ranked full-text search, one page at a time. Ties in rank fall back to the
+/// display order so consecutive pages don't overlap. With `sort_by` the results
+/// are ordered by it instead, and rank only breaks ties. `min_difficulty` leaves
+/// out unrated exercises and those rated below it.
+#[command]
+pub fn search_exercises(
+    pool: State<'_, DbPool>,
+    query: String,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    min_difficulty: Option<i64>,
+    sort_by: Option<SortBy>,
+) -> Result<Page<SearchResult>, AppError> {
+    let Some(match_query) = build_match_query(&query) else {
+        return Ok(Page {
+            items: Vec::new(),
+            total: 0,
+        });
+    };
+
+    let conn = get_connection(&pool)?;
+
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM exercises_fts
+         JOIN exercises ON exercises.id = exercises_fts.id
+         WHERE exercises_fts MATCH ?1 AND exercises.deleted_at IS NULL
+             AND (?2 IS NULL OR exercises.difficulty >= ?2)",
+        params![match_query, min_difficulty],
+        |row| row.get(0),
+    )?;
+
+    // bm25 weights follow the column order: id, name, content, notes, tags, solution
+    let sql = format!(
+        "SELECT {columns}, s.snippet, s.name_hit, s.content_hit, s.notes_hit, s.tags_hit, s.solution_hit
+         FROM exercises
+         JOIN (
+             SELECT id AS fts_id,
+                    bm25(exercises_fts, 0.0, 10.0, 4.0, 2.0, 5.0, 2.0) AS score,
+                    snippet(exercises_fts, -1, '**', '**', '…', 16) AS snippet,
+                    ifnull(instr(highlight(exercises_fts, 1, {m}, ''), {m}), 0) > 0 AS name_hit,
+                    ifnull(instr(highlight(exercises_fts, 2, {m}, ''), {m}), 0) > 0 AS content_hit,
+                    ifnull(instr(highlight(exercises_fts, 3, {m}, ''), {m}), 0) > 0 AS notes_hit,
+                    ifnull(instr(highlight(exercises_fts, 4, {m}, ''), {m}), 0) > 0 AS tags_hit,
+                    ifnull(instr(highlight(exercises_fts, 5, {m}, ''), {m}), 0) > 0 AS solution_hit
+             FROM exercises_fts
+             WHERE exercises_fts MATCH ?1
+         ) s ON exercises.id = s.fts_id
+         WHERE exercises.deleted_at IS NULL AND (?4 IS NULL OR exercises.difficulty >= ?4)
+         ORDER BY {order}
+         LIMIT ?2 OFFSET ?3",
+        columns = EXERCISE_COLUMNS,
+        m = HIT_MARKER,
+        order = match sort_by {
+            Some(sort_by) => format!("{}, s.score, exercises.id", sort_by.order_by()),
+            None => "s.score, exercises.order_index, exercises.id".to_string(),
+        },
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let results = stmt
+        // A negative LIMIT means no limit in SQLite
+        .query_map(
+            params![match_query, limit.unwrap_or(-1), offset.unwrap_or(0), min_difficulty],
+            |row| {
+                let matched_fields = ["name", "content", "notes", "tags", "solution"]
+                    .iter()
+                    .filter_map(|field| match row.get::<_, bool>(format!("{}_hit", field).as_str()) {
+                        Ok(true) => Some(Ok(field.to_string())),
+                        Ok(false) => None,
+                        Err(e) => Some(Err(e)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(SearchResult {
+                    exercise: Exercise::from_row(row)?,
+                    matched_fields,
+                    snippet: row.get::<_, Option<String>>("snippet")?.unwrap_or_default(),
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Page { items: results, total })
+}
@@ -0,0 +1,292 @@
+use keyring::Entry;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{command, State};
+use tracing::{info, warn};
+
+use crate::db::{self, delete_setting, get_connection, DbPool};
+use crate::error::AppError;
+use crate::gemini::{self, HttpClient};
+use crate::images::SaveFormat;
+
+const KEYRING_SERVICE: &str = "com.vaulty.app";
+const API_KEY_ACCOUNT: &str = "gemini_api_key";
+const API_KEY_SETTING: &str = "gemini_api_key";
+const DEFAULT_MODEL_SETTING: &str = "default_model";
+const PROXY_SETTING: &str = "proxy_url";
+
+// app_settings only ever holds a reference into the OS keyring, never the key itself
+const KEYRING_REF_PREFIX: &str = "keyring:";
+
+fn api_key_entry() -> Result<Entry, AppError> {
+    Ok(Entry::new(KEYRING_SERVICE, API_KEY_ACCOUNT)?)
+}
+
+fn api_key_ref() -> String {
+    format!("{}{}", KEYRING_REF_PREFIX, API_KEY_ACCOUNT)
+}
+
+#[command]
+pub fn save_api_key(pool: State<'_, DbPool>, api_key: String) -> Result<(), AppError> {
+    let conn = get_connection(&pool)?;
+    let entry = api_key_entry()?;
+    let api_key = api_key.trim();
+
+    if api_key.is_empty() {
+        match entry.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(e.into()),
+        }
+        return delete_setting(&conn, API_KEY_SETTING);
+    }
+
+    entry.set_password(api_key)?;
+    db::set_setting(&conn, API_KEY_SETTING, &api_key_ref())
+}
+
+#[command]
+pub fn get_api_key(pool: State<'_, DbPool>) -> Result<Option<String>, AppError> {
+    let conn = get_connection(&pool)?;
+    let entry = api_key_entry()?;
+
+    match db::get_setting(&conn, API_KEY_SETTING)? {
+        None => Ok(None),
+        Some(value) if value.starts_with(KEYRING_REF_PREFIX) => match entry.get_password() {
+            Ok(key) => Ok(Some(key)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        },
+        Some(plaintext) => {
+            // Legacy plaintext value: move it into the keyring on first read
+            info!("Migrating plaintext API key into the OS keyring");
+            entry.set_password(&plaintext)?;
+            db::set_setting(&conn, API_KEY_SETTING, &api_key_ref())?;
+            Ok(Some(plaintext))
+        }
+    }
+}
+
+/// Asks Gemini whether `api_key` works, for feedback before the first analysis.
+/// The key is only sent along, never stored.
+#[command]
+pub async fn validate_api_key(
+    pool: State<'_, DbPool>,
+    http: State<'_, HttpClient>,
+    api_key: String,
+) -> Result<gemini::ApiKeyStatus, AppError> {
+    let client = http_client(&http, &pool)?;
+    Ok(gemini::check_api_key(&client, &api_key).await)
+}
+
+/// The client for Gemini requests, going through the configured proxy.
+pub fn http_client(http: &HttpClient, pool: &DbPool) -> Result<reqwest::Client, AppError> {
+    http.get(|| {
+        let conn = get_connection(pool)?;
+        db::get_setting(&conn, PROXY_SETTING)
+    })
+}
+
+/// The proxy Gemini requests go through, if one is set. Without one the
+/// `HTTPS_PROXY` environment variable and its relatives are used.
+#[command]
+pub fn get_proxy(pool: State<'_, DbPool>) -> Result<Option<String>, AppError> {
+    let conn = get_connection(&pool)?;
+    db::get_setting(&conn, PROXY_SETTING)
+}
+
+/// Sends Gemini requests through `proxy`, an `http://`, `https://`, `socks5://` or
+/// `socks5h://` URL that may include `user:password@`. An empty or missing one goes
+/// back to the environment's proxy settings. Takes effect for the next request.
+#[command]
+pub fn set_proxy(
+    pool: State<'_, DbPool>,
+    http: State<'_, HttpClient>,
+    proxy: Option<String>,
+) -> Result<(), AppError> {
+    let proxy = proxy.map(|proxy| proxy.trim().to_string()).filter(|proxy| !proxy.is_empty());
+    // Building the client first rejects a bad URL before it is stored
+    let client = gemini::build_client(proxy.as_deref())?;
+
+    let conn = get_connection(&pool)?;
+    match &proxy {
+        Some(proxy) => db::set_setting(&conn, PROXY_SETTING, proxy)?,
+        None => delete_setting(&conn, PROXY_SETTING)?,
+    }
+    http.replace(client);
+
+    info!("Proxy {}", if proxy.is_some() { "set" } else { "cleared" });
+    Ok(())
+}
+
+/// The model used when a request doesn't name one. A stored model that is no
+/// longer supported falls back to the built-in default.
+pub fn default_model(conn: &Connection) -> Result<String, AppError> {
+    Ok(db::get_setting(conn, DEFAULT_MODEL_SETTING)?
+        .filter(|model| gemini::validate_model(model).is_ok())
+        .unwrap_or_else(|| gemini::DEFAULT_MODEL.to_string()))
+}
+
+#[command]
+pub fn get_default_model(pool: State<'_, DbPool>) -> Result<String, AppError> {
+    let conn = get_connection(&pool)?;
+    default_model(&conn)
+}
+
+#[command]
+pub fn set_default_model(pool: State<'_, DbPool>, model: String) -> Result<(), AppError> {
+    let conn = get_connection(&pool)?;
+    gemini::validate_model(&model)?;
+    db::set_setting(&conn, DEFAULT_MODEL_SETTING, &model)
+}
+
+/// Settings read through `get_setting`/`set_setting`, named by their `app_settings` key.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingKey {
+    PdfDpi,
+    ImageFormat,
+    JpegQuality,
+    MaxSessionMinutes,
+    AnalysisChunkPages,
+}
+
+impl SettingKey {
+    const ALL: [SettingKey; 5] = [
+        SettingKey::PdfDpi,
+        SettingKey::ImageFormat,
+        SettingKey::JpegQuality,
+        SettingKey::MaxSessionMinutes,
+        SettingKey::AnalysisChunkPages,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            SettingKey::PdfDpi => "pdf_dpi",
+            SettingKey::ImageFormat => "image_format",
+            SettingKey::JpegQuality => "jpeg_quality",
+            SettingKey::MaxSessionMinutes => "max_session_minutes",
+            SettingKey::AnalysisChunkPages => "analysis_chunk_pages",
+        }
+    }
+}
+
+/// Rendering, image, timer and analysis settings. Each is stored as JSON under its key and falls
+/// back to its default when absent or invalid.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Settings {
+    /// Resolution PDF pages are rendered at, 72–600.
+    pub pdf_dpi: u32,
+    /// Format images are stored in when the caller doesn't ask for one.
+    pub image_format: SaveFormat,
+    /// 1–100, for images stored as JPEG.
+    pub jpeg_quality: u8,
+    /// 1–1440. A timer session still open at startup is counted as at most this long.
+    pub max_session_minutes: u32,
+    /// 1–50. `analyze_pages` sends pages to Gemini in groups of this many.
+    pub analysis_chunk_pages: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            pdf_dpi: 150,
+            image_format: SaveFormat::Png,
+            jpeg_quality: 85,
+            max_session_minutes: 120,
+            analysis_chunk_pages: 5,
+        }
+    }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(key: SettingKey, value: Value) -> Result<T, AppError> {
+    serde_json::from_value(value)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid value for {}: {}", key.as_str(), e)))
+}
+
+fn check_range(key: SettingKey, value: u32, range: std::ops::RangeInclusive<u32>) -> Result<(), AppError> {
+    if !range.contains(&value) {
+        return Err(AppError::InvalidInput(format!(
+            "{} must be between {} and {}, not {}",
+            key.as_str(),
+            range.start(),
+            range.end(),
+            value
+        )));
+    }
+    Ok(())
+}
+
+impl Settings {
+    /// Validates `value` and writes it into the field for `key`.
+    fn apply(&mut self, key: SettingKey, value: Value) -> Result<(), AppError> {
+        match key {
+            SettingKey::PdfDpi => {
+                let dpi = parse(key, value)?;
+                check_range(key, dpi, 72..=600)?;
+                self.pdf_dpi = dpi;
+            }
+            SettingKey::ImageFormat => self.image_format = parse(key, value)?,
+            SettingKey::JpegQuality => {
+                let quality: u32 = parse(key, value)?;
+                check_range(key, quality, 1..=100)?;
+                self.jpeg_quality = quality as u8;
+            }
+            SettingKey::MaxSessionMinutes => {
+                let minutes = parse(key, value)?;
+                check_range(key, minutes, 1..=1440)?;
+                self.max_session_minutes = minutes;
+            }
+            SettingKey::AnalysisChunkPages => {
+                let pages = parse(key, value)?;
+                check_range(key, pages, 1..=50)?;
+                self.analysis_chunk_pages = pages;
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: SettingKey) -> Result<Value, AppError> {
+        Ok(match key {
+            SettingKey::PdfDpi => Value::from(self.pdf_dpi),
+            SettingKey::ImageFormat => serde_json::to_value(self.image_format)?,
+            SettingKey::JpegQuality => Value::from(self.jpeg_quality),
+            SettingKey::MaxSessionMinutes => Value::from(self.max_session_minutes),
+            SettingKey::AnalysisChunkPages => Value::from(self.analysis_chunk_pages),
+        })
+    }
+
+    pub fn load(conn: &Connection) -> Result<Self, AppError> {
+        let mut settings = Settings::default();
+        for key in SettingKey::ALL {
+            let Some(stored) = db::get_setting(conn, key.as_str())? else {
+                continue;
+            };
+            let result = serde_json::from_str(&stored)
+                .map_err(AppError::from)
+                .and_then(|value| settings.apply(key, value));
+            if let Err(e) = result {
+                warn!("Ignoring stored {}: {}", key.as_str(), e);
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// The value of one setting, or its default if it was never set.
+#[command]
+pub fn get_setting(pool: State<'_, DbPool>, key: SettingKey) -> Result<Value, AppError> {
+    let conn = get_connection(&pool)?;
+    Settings::load(&conn)?.get(key)
+}
+
+/// Validates and stores one setting; out of range values are rejected.
+#[command]
+pub fn set_setting(pool: State<'_, DbPool>, key: SettingKey, value: Value) -> Result<(), AppError> {
+    let mut settings = Settings::default();
+    settings.apply(key, value)?;
+
+    let conn = get_connection(&pool)?;
+    db::set_setting(&conn, key.as_str(), &settings.get(key)?.to_string())
+}
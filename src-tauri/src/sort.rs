@@ -0,0 +1,153 @@
+use serde::Deserialize;
+use std::cmp::Ordering;
+
+/// Orderings accepted by the listing commands.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    CreatedAtDesc,
+    NameAsc,
+    WeekAsc,
+    /// Hardest first; unrated exercises last.
+    DifficultyDesc,
+    #[default]
+    CourseThenWeek,
+}
+
+impl SortBy {
+    pub fn order_by(self) -> &'static str {
+        match self {
+            SortBy::CreatedAtDesc => "created_at DESC",
+            SortBy::NameAsc => "name COLLATE NATURAL_ORDER, created_at",
+            SortBy::WeekAsc => "week, course COLLATE NATURAL_ORDER, order_index, created_at",
+            SortBy::DifficultyDesc => "difficulty DESC NULLS LAST, course COLLATE NATURAL_ORDER, week, order_index, created_at",
+            SortBy::CourseThenWeek => "course COLLATE NATURAL_ORDER, week, order_index, created_at",
+        }
+    }
+}
+
+/// Compares strings so embedded numbers sort by value ("Ex 1.2" < "Ex 1.10").
+/// Text runs compare case-insensitively.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_num = take_digits(&mut a_chars);
+                let b_num = take_digits(&mut b_chars);
+                let ordering = compare_digit_runs(&a_num, &b_num);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.peek().copied().filter(char::is_ascii_digit) {
+        digits.push(c);
+        chars.next();
+    }
+    digits
+}
+
+// Compares digit runs of any length without overflowing: more significant digits win
+fn compare_digit_runs(a: &str, b: &str) -> Ordering {
+    let a = a.trim_start_matches('0');
+    let b = b.trim_start_matches('0');
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_sort_by_value() {
+        assert_eq!(natural_cmp("Week 2", "Week 10"), Ordering::Less);
+        assert_eq!(natural_cmp("Ex 1.10", "Ex 1.2"), Ordering::Greater);
+        assert_eq!(natural_cmp("Ex 99999999999999999999999", "Ex 100000000000000000000000"), Ordering::Less);
+    }
+
+    #[test]
+    fn leading_zeros_only_break_ties() {
+        assert_eq!(natural_cmp("Week 02", "Week 10"), Ordering::Less);
+        assert_eq!(natural_cmp("Week 010", "Week 9"), Ordering::Greater);
+        // Equal values still order consistently, so sorting is stable
+        assert_eq!(natural_cmp("Week 007", "Week 7"), Ordering::Less);
+        assert_eq!(natural_cmp("Week 7", "Week 007"), Ordering::Greater);
+    }
+
+    #[test]
+    fn text_compares_case_insensitively() {
+        assert_eq!(natural_cmp("algebra", "Biology"), Ordering::Less);
+        assert_eq!(natural_cmp("ALGEBRA 2", "algebra 10"), Ordering::Less);
+        assert_ne!(natural_cmp("Week", "week"), Ordering::Equal);
+        assert_eq!(natural_cmp("Week", "Week"), Ordering::Equal);
+    }
+
+    #[test]
+    fn mixed_and_empty_strings() {
+        assert_eq!(natural_cmp("", ""), Ordering::Equal);
+        assert_eq!(natural_cmp("", "a"), Ordering::Less);
+        assert_eq!(natural_cmp("1", ""), Ordering::Greater);
+        assert_eq!(natural_cmp("a1b2", "a1b10"), Ordering::Less);
+        assert_eq!(natural_cmp("2a", "10"), Ordering::Less);
+        assert_eq!(natural_cmp("Ex 1", "Ex 1a"), Ordering::Less);
+        // Digits sort before letters, as in ASCII
+        assert_eq!(natural_cmp("1", "a"), Ordering::Less);
+    }
+
+    #[test]
+    fn every_ordering_runs_against_the_schema() {
+        let conn = crate::db::test_connection();
+        conn.execute_batch(
+            "INSERT INTO exercises (id, name, tags, course, week, created_at) VALUES
+                 ('a', 'Ex 10', '[]', 'Week 10', 1, 0),
+                 ('b', 'Ex 2', '[]', 'Week 2', 1, 0);",
+        )
+        .unwrap();
+        let sorts = [
+            SortBy::CreatedAtDesc,
+            SortBy::NameAsc,
+            SortBy::WeekAsc,
+            SortBy::DifficultyDesc,
+            SortBy::CourseThenWeek,
+        ];
+        for sort in sorts {
+            let sql = format!("SELECT id FROM exercises ORDER BY {}", sort.order_by());
+            let ids: Vec<String> = conn
+                .prepare(&sql)
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(ids.len(), 2, "{:?}", sort);
+        }
+
+        let first: String = conn
+            .query_row(
+                &format!("SELECT id FROM exercises ORDER BY {}", SortBy::NameAsc.order_by()),
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(first, "b");
+    }
+}
@@ -0,0 +1,258 @@
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use tauri::{command, AppHandle, Runtime, State};
+
+use crate::db::{get_connection, get_images_dir, DbPool};
+use crate::error::AppError;
+use crate::images::thumbnail_path;
+
+/// Exercise counts by status for some group of exercises.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Completion {
+    pub total: i64,
+    pub done: i64,
+    pub in_progress: i64,
+    /// Not done and past their due date.
+    pub overdue: i64,
+    /// Share of `total` that is done, 0–100.
+    pub percent_done: f64,
+}
+
+impl Completion {
+    fn add(&mut self, total: i64, done: i64, in_progress: i64, overdue: i64) {
+        self.total += total;
+        self.done += done;
+        self.in_progress += in_progress;
+        self.overdue += overdue;
+        self.percent_done = if self.total == 0 {
+            0.0
+        } else {
+            self.done as f64 * 100.0 / self.total as f64
+        };
+    }
+}
+
+/// How many exercises have each difficulty rating.
+#[derive(Debug, Default, Serialize)]
+pub struct DifficultyDistribution {
+    /// Exercises rated 1 to 5 stars, in that order.
+    pub rated: [i64; 5],
+    pub unrated: i64,
+}
+
+/// Logged attempts at a course's exercises.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttemptStatistics {
+    pub total: i64,
+    pub solved: i64,
+    /// Share of `total` that was solved, 0–100.
+    pub solve_rate: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeekStatistics {
+    pub week: i64,
+    #[serde(flatten)]
+    pub completion: Completion,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CourseStatistics {
+    pub course: String,
+    #[serde(flatten)]
+    pub completion: Completion,
+    pub difficulty: DifficultyDistribution,
+    pub attempts: AttemptStatistics,
+    /// In week order.
+    pub weeks: Vec<WeekStatistics>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Statistics {
+    #[serde(flatten)]
+    pub completion: Completion,
+    /// In natural order, archived courses included.
+    pub courses: Vec<CourseStatistics>,
+}
+
+/// Completion of every course and week and the difficulty ratings and attempts
+/// of every course, counting exercises outside the trash. An exercise with parts
+/// takes its status from them: done once every part is, in progress once any part
+/// has been started.
+#[command]
+pub fn get_statistics(pool: State<'_, DbPool>) -> Result<Statistics, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut stats = Statistics {
+        completion: Completion::default(),
+        courses: Vec::new(),
+    };
+    let mut stmt = conn.prepare(
+        "WITH live AS (SELECT * FROM exercises WHERE deleted_at IS NULL),
+         effective AS (
+             SELECT course, week, due_at, difficulty,
+                    CASE
+                        WHEN NOT EXISTS (SELECT 1 FROM live p WHERE p.parent_id = e.id) THEN status
+                        WHEN NOT EXISTS (SELECT 1 FROM live p WHERE p.parent_id = e.id AND p.status != 'done') THEN 'done'
+                        WHEN EXISTS (SELECT 1 FROM live p WHERE p.parent_id = e.id AND p.status != 'todo') THEN 'in_progress'
+                        ELSE 'todo'
+                    END AS status
+             FROM live e
+         )
+         SELECT course, week, COUNT(*), SUM(status = 'done'), SUM(status = 'in_progress'),
+                COUNT(CASE WHEN status != 'done' AND due_at < ?1 THEN 1 END), SUM(difficulty IS NULL),
+                SUM(difficulty IS 1), SUM(difficulty IS 2), SUM(difficulty IS 3),
+                SUM(difficulty IS 4), SUM(difficulty IS 5)
+         FROM effective
+         GROUP BY course, week
+         ORDER BY course COLLATE NATURAL_ORDER, course, week",
+    )?;
+    let mut rows = stmt.query(params![chrono::Utc::now().timestamp_millis()])?;
+    while let Some(row) = rows.next()? {
+        let course: String = row.get(0)?;
+        let (total, done, in_progress, overdue): (i64, i64, i64, i64) =
+            (row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?);
+
+        if stats.courses.last().is_none_or(|last| last.course != course) {
+            stats.courses.push(CourseStatistics {
+                course,
+                completion: Completion::default(),
+                difficulty: DifficultyDistribution::default(),
+                attempts: AttemptStatistics::default(),
+                weeks: Vec::new(),
+            });
+        }
+        let entry = stats.courses.last_mut().expect("a course was just pushed");
+        entry.difficulty.unrated += row.get::<_, i64>(6)?;
+        for (stars, count) in entry.difficulty.rated.iter_mut().enumerate() {
+            *count += row.get::<_, i64>(7 + stars)?;
+        }
+        let mut week = WeekStatistics {
+            week: row.get(1)?,
+            completion: Completion::default(),
+        };
+        week.completion.add(total, done, in_progress, overdue);
+        entry.weeks.push(week);
+        entry.completion.add(total, done, in_progress, overdue);
+        stats.completion.add(total, done, in_progress, overdue);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT e.course, COUNT(*), COUNT(CASE WHEN a.outcome = 'solved' THEN 1 END)
+         FROM attempts a JOIN exercises e ON e.id = a.exercise_id
+         WHERE e.deleted_at IS NULL
+         GROUP BY e.course",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let course: String = row.get(0)?;
+        if let Some(entry) = stats.courses.iter_mut().find(|entry| entry.course == course) {
+            let (total, solved): (i64, i64) = (row.get(1)?, row.get(2)?);
+            entry.attempts = AttemptStatistics {
+                total,
+                solved,
+                solve_rate: solved as f64 * 100.0 / total as f64,
+            };
+        }
+    }
+
+    Ok(stats)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseStats {
+    pub course_count: usize,
+    pub week_count: usize,
+    pub exercise_count: usize,
+    /// Size of the image files exercises refer to, thumbnails included.
+    pub total_image_bytes: u64,
+    pub tag_count: usize,
+    /// Exercises by their first tag, which analysis sets to the exercise type.
+    /// Exercises without tags are left out.
+    pub exercises_per_type: BTreeMap<String, i64>,
+}
+
+/// An overview of the vault for the dashboard. Counts cover exercises outside the
+/// trash; image bytes cover every image still on disk for an exercise, trashed
+/// ones included, since those files take up space until the trash is emptied.
+#[command]
+pub fn get_database_stats<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+) -> Result<DatabaseStats, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut courses: HashSet<String> = HashSet::new();
+    let mut weeks: HashSet<(String, i64)> = HashSet::new();
+    let mut tags: HashSet<String> = HashSet::new();
+    // By file name, as stored paths may spell the images dir differently
+    let mut referenced: HashSet<OsString> = HashSet::new();
+    let mut stats = DatabaseStats {
+        course_count: 0,
+        week_count: 0,
+        exercise_count: 0,
+        total_image_bytes: 0,
+        tag_count: 0,
+        exercises_per_type: BTreeMap::new(),
+    };
+
+    let mut stmt = conn.prepare("SELECT DISTINCT path FROM exercise_images")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let path: String = row.get(0)?;
+        let path = Path::new(&path);
+        for path in [Some(path.to_path_buf()), thumbnail_path(path)].into_iter().flatten() {
+            if let Some(name) = path.file_name() {
+                referenced.insert(name.to_owned());
+            }
+        }
+    }
+
+    let mut stmt = conn.prepare("SELECT course, week, tags, deleted_at IS NULL FROM exercises")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        if !row.get::<_, bool>(3)? {
+            continue;
+        }
+
+        let course: String = row.get(0)?;
+        weeks.insert((course.clone(), row.get(1)?));
+        courses.insert(course);
+        stats.exercise_count += 1;
+
+        let exercise_tags: Vec<String> = row
+            .get::<_, Option<String>>(2)?
+            .and_then(|tags| serde_json::from_str(&tags).ok())
+            .unwrap_or_default();
+        if let Some(kind) = exercise_tags.first() {
+            *stats.exercises_per_type.entry(kind.to_ascii_lowercase()).or_default() += 1;
+        }
+        // The tag index compares names ASCII case-insensitively, so count them that way too
+        tags.extend(
+            exercise_tags
+                .iter()
+                .map(|tag| tag.trim().to_ascii_lowercase())
+                .filter(|tag| !tag.is_empty()),
+        );
+    }
+    stats.course_count = courses.len();
+    stats.week_count = weeks.len();
+    stats.tag_count = tags.len();
+
+    for entry in fs::read_dir(get_images_dir(&app)?)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() && referenced.contains(&entry.file_name()) {
+            stats.total_image_bytes += metadata.len();
+        }
+    }
+
+    Ok(stats)
+}
@@ -0,0 +1,130 @@
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle, Runtime, State};
+use tracing::{info, warn};
+
+use crate::backup::{copy_dir_files, point_attachments_at, repoint_images};
+use crate::db::{get_app_data_dir, get_attachments_dir, get_data_dir, get_images_dir, set_data_dir, DbPool};
+use crate::error::AppError;
+use crate::gemini::HttpClient;
+use crate::profiles::active_vault_moved;
+
+fn sidecar(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+pub fn remove_database_files(db_path: &Path) {
+    for path in [db_path.to_path_buf(), sidecar(db_path, "-wal"), sidecar(db_path, "-shm")] {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Directory currently holding the database and images.
+#[command]
+pub fn get_data_directory<R: Runtime>(app: AppHandle<R>) -> Result<String, AppError> {
+    Ok(get_data_dir(&app)?.to_string_lossy().into_owned())
+}
+
+/// Moves the database, images and attachments into `new_path` and keeps using them from there.
+/// The copy is integrity-checked before the app switches over; only then is the old
+/// copy removed. Moving to the app data dir forgets the custom location.
+#[command]
+pub fn move_data_directory<R: Runtime>(
+    app: AppHandle<R>,
+    pool: State<'_, DbPool>,
+    http: State<'_, HttpClient>,
+    new_path: String,
+) -> Result<String, AppError> {
+    let new_dir = PathBuf::from(new_path.trim());
+    if !new_dir.is_absolute() {
+        return Err(AppError::InvalidInput(format!("Not an absolute path: {}", new_path)));
+    }
+    fs::create_dir_all(&new_dir)?;
+    let new_dir = new_dir.canonicalize()?;
+
+    let old_dir = get_data_dir(&app)?.canonicalize()?;
+    let old_images = get_images_dir(&app)?;
+    let old_attachments = get_attachments_dir(&app)?;
+    if new_dir == old_dir {
+        return Err(AppError::InvalidInput("Your data is already in that directory".to_string()));
+    }
+    if new_dir.starts_with(old_images.canonicalize()?) {
+        return Err(AppError::InvalidInput("Can't move data into its own images directory".to_string()));
+    }
+
+    let new_db = new_dir.join("vaulty.db");
+    let new_images = new_dir.join("images");
+    let new_attachments = new_dir.join("attachments");
+    if new_db.exists() || new_images.exists() || new_attachments.exists() {
+        return Err(AppError::InvalidInput(format!(
+            "{} already contains a Vaulty vault",
+            new_dir.display()
+        )));
+    }
+
+    let old_db = pool.db_path();
+    let is_default = new_dir == get_app_data_dir(&app)?.canonicalize()?;
+
+    let result = pool.relocate(new_db.clone(), |db_path, key| {
+        let result = (|| {
+            // The pool is closed, so the file and its WAL are a consistent copy
+            fs::copy(db_path, &new_db)?;
+            if sidecar(db_path, "-wal").exists() {
+                fs::copy(sidecar(db_path, "-wal"), sidecar(&new_db, "-wal"))?;
+            }
+            copy_dir_files(&old_images, &new_images)?;
+            copy_dir_files(&old_attachments, &new_attachments)?;
+
+            let conn = Connection::open(&new_db)?;
+            if let Some(key) = key {
+                conn.pragma_update(None, "key", key)?;
+            }
+            let check: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+            if check != "ok" {
+                return Err(AppError::Db(format!("Copied database failed its integrity check: {}", check)));
+            }
+            let (copied, original) = (fs::read_dir(&new_images)?.count(), fs::read_dir(&old_images)?.count());
+            if copied != original {
+                return Err(AppError::Io(format!("Copied {} of {} image files", copied, original)));
+            }
+            let (copied, original) =
+                (fs::read_dir(&new_attachments)?.count(), fs::read_dir(&old_attachments)?.count());
+            if copied != original {
+                return Err(AppError::Io(format!("Copied {} of {} attachments", copied, original)));
+            }
+            repoint_images(&conn, &new_images)?;
+            point_attachments_at(&conn, &new_attachments, false)?;
+            drop(conn);
+
+            set_data_dir(&app, (!is_default).then_some(new_dir.as_path()))
+        })();
+
+        if result.is_err() {
+            remove_database_files(&new_db);
+            let _ = fs::remove_dir_all(&new_images);
+            let _ = fs::remove_dir_all(&new_attachments);
+        }
+        result
+    });
+    http.reset();
+    result?;
+
+    if let Err(e) = active_vault_moved(&app, &old_dir, &new_dir) {
+        warn!("Failed to update the vault registry: {}", e);
+    }
+
+    // The vault now lives in new_dir; leftovers here are only wasted space
+    remove_database_files(&old_db);
+    if let Err(e) = fs::remove_dir_all(&old_images) {
+        warn!("Failed to remove old images dir {:?}: {}", old_images, e);
+    }
+    if let Err(e) = fs::remove_dir_all(&old_attachments) {
+        warn!("Failed to remove old attachments dir {:?}: {}", old_attachments, e);
+    }
+
+    info!("Moved data from {:?} to {:?}", old_dir, new_dir);
+    Ok(new_dir.to_string_lossy().into_owned())
+}
@@ -0,0 +1,393 @@
+use rusqlite::{params, params_from_iter, OptionalExtension, Transaction};
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::{command, State};
+use tracing::info;
+
+use crate::db::{get_connection, DbPool};
+use crate::error::AppError;
+use crate::models::{Exercise, EXERCISE_COLUMNS};
+use crate::sort::SortBy;
+
+/// Trims, collapses inner whitespace and lowercases each tag, then drops empty tags
+/// and repeats. Order is kept, so the exercise type stays the first tag.
+pub fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = normalize_tag(&tag);
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+/// One tag as `normalize_tags` stores it; empty if it was only whitespace.
+fn normalize_tag(tag: &str) -> String {
+    tag.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Ids of exercises tagged with any of `count` placeholder names, via the tag index.
+fn tagged_with(count: usize) -> String {
+    format!(
+        "SELECT exercise_tags.exercise_id FROM exercise_tags
+         JOIN tags ON tags.id = exercise_tags.tag_id
+         WHERE tags.name IN ({})",
+        vec!["?"; count].join(", ")
+    )
+}
+
+/// Returns exercises carrying any of `tags`, or all of them when `match_all` is set.
+/// Tags compare ASCII case-insensitively, like SQLite's `lower()`. An empty list
+/// matches nothing in "any" mode and everything in "all" mode.
+#[command]
+pub fn filter_by_tags(
+    pool: State<'_, DbPool>,
+    tags: Vec<String>,
+    match_all: Option<bool>,
+) -> Result<Vec<Exercise>, AppError> {
+    let conn = get_connection(&pool)?;
+    let match_all = match_all.unwrap_or(false);
+
+    let mut wanted: Vec<String> = tags.iter().map(|t| t.trim().to_ascii_lowercase()).collect();
+    wanted.sort();
+    wanted.dedup();
+
+    if wanted.is_empty() && !match_all {
+        return Ok(Vec::new());
+    }
+
+    // tags.name is NOCASE, so every casing of a tag shares one row
+    let tag_condition = if wanted.is_empty() {
+        "1".to_string()
+    } else if match_all {
+        format!(
+            "id IN ({} GROUP BY exercise_tags.exercise_id HAVING COUNT(*) = {})",
+            tagged_with(wanted.len()),
+            wanted.len()
+        )
+    } else {
+        format!("id IN ({})", tagged_with(wanted.len()))
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM exercises WHERE deleted_at IS NULL AND {} ORDER BY {}",
+        EXERCISE_COLUMNS,
+        tag_condition,
+        SortBy::default().order_by()
+    ))?;
+    let exercises = stmt
+        .query_map(params_from_iter(&wanted), Exercise::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(exercises)
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    /// Exercises outside the trash with the tag.
+    pub count: i64,
+}
+
+/// Every tag on an exercise outside the trash with how many use it, most used
+/// first and then alphabetically. Tags differing only in case are counted as one,
+/// under the spelling first stored. Reads only the tag index, not the exercises.
+#[command]
+pub fn get_all_tags(pool: State<'_, DbPool>) -> Result<Vec<TagCount>, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT tags.name, COUNT(*) AS uses
+         FROM tags
+         JOIN exercise_tags ON exercise_tags.tag_id = tags.id
+         JOIN exercises ON exercises.id = exercise_tags.exercise_id
+         WHERE exercises.deleted_at IS NULL
+         GROUP BY tags.id
+         ORDER BY uses DESC, tags.name",
+    )?;
+    let tags = stmt
+        .query_map([], |row| {
+            Ok(TagCount {
+                tag: row.get(0)?,
+                count: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(tags)
+}
+
+/// Replaces every tag matching one of `sources` (ASCII case-insensitively) with
+/// `target` on all exercises, trashed ones included, and returns the ids of the
+/// exercises that changed. `target` is stored as given, so callers normalize it
+/// first. It is kept once per exercise,
+/// at the position of the first tag it replaced, so a leading exercise-type tag
+/// stays first.
+fn replace_tags(tx: &Transaction, sources: &[&str], target: &str) -> Result<Vec<String>, AppError> {
+    let sources: Vec<String> = sources.iter().map(|s| s.to_ascii_lowercase()).collect();
+
+    let tagged: Vec<(String, String)> = tx
+        .prepare(&format!(
+            "SELECT id, tags FROM exercises WHERE id IN ({})",
+            tagged_with(sources.len())
+        ))?
+        .query_map(params_from_iter(&sources), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+
+    let mut changed = Vec::new();
+    for (id, tags_str) in tagged {
+        let tags: Vec<String> = serde_json::from_str(&tags_str).unwrap_or_default();
+        let mut replaced: Vec<String> = Vec::with_capacity(tags.len());
+        for tag in tags {
+            let lower = tag.to_ascii_lowercase();
+            if sources.contains(&lower) || tag.eq_ignore_ascii_case(target) {
+                if !replaced.iter().any(|t| t == target) {
+                    replaced.push(target.to_string());
+                }
+            } else {
+                replaced.push(tag);
+            }
+        }
+
+        let replaced_str = serde_json::to_string(&replaced)?;
+        if replaced_str != tags_str {
+            tx.execute("UPDATE exercises SET tags = ?2 WHERE id = ?1", params![id, replaced_str])?;
+            changed.push(id);
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Renames a tag on every exercise and returns how many exercises changed. `old`
+/// matches case-insensitively; `new` is normalized like saved tags, so renaming to
+/// "Linear Algebra" stores "linear algebra". An exercise that already carries
+/// `new` keeps one copy.
+#[command]
+pub fn rename_tag(pool: State<'_, DbPool>, old: String, new: String) -> Result<usize, AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+    let touched = rename(&tx, &old, &new)?;
+    tx.commit()?;
+
+    info!("Renamed {} to {} on {} exercises", old.trim(), normalize_tag(&new), touched);
+    Ok(touched)
+}
+
+fn rename(tx: &Transaction, old: &str, new: &str) -> Result<usize, AppError> {
+    let (old, new) = (old.trim(), normalize_tag(new));
+    if old.is_empty() || new.is_empty() {
+        return Err(AppError::InvalidInput("Tag names must not be empty".to_string()));
+    }
+    Ok(replace_tags(tx, &[old], &new)?.len())
+}
+
+/// Folds synonym tags into `target` and returns the ids of the exercises that
+/// changed. `target` may itself be one of `sources`, and is normalized like
+/// `rename_tag`'s new name.
+#[command]
+pub fn merge_tags(pool: State<'_, DbPool>, sources: Vec<String>, target: String) -> Result<Vec<String>, AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+    let changed = merge(&tx, &sources, &target)?;
+    tx.commit()?;
+
+    info!("Merged {} into {} on {} exercises", sources.join(", "), normalize_tag(&target), changed.len());
+    Ok(changed)
+}
+
+fn merge(tx: &Transaction, sources: &[String], target: &str) -> Result<Vec<String>, AppError> {
+    let target = normalize_tag(target);
+    let sources: Vec<&str> = sources.iter().map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
+    if target.is_empty() || sources.is_empty() {
+        return Err(AppError::InvalidInput("Tag names must not be empty".to_string()));
+    }
+    replace_tags(tx, &sources, &target)
+}
+
+/// Adds and removes tags on each of `ids` in one transaction and returns the
+/// resulting tag lists by id; unknown ids are left out. Added tags are normalized
+/// and appended, so the exercise type stays first. Removal matches
+/// case-insensitively. A tag in both lists ends up added.
+#[command]
+pub fn bulk_edit_tags(
+    pool: State<'_, DbPool>,
+    ids: Vec<String>,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<HashMap<String, Vec<String>>, AppError> {
+    let add = normalize_tags(add);
+    let remove = normalize_tags(remove);
+
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    let mut updated = HashMap::with_capacity(ids.len());
+    let mut changed = 0;
+    for id in ids {
+        let tags_str: Option<String> = tx
+            .query_row("SELECT tags FROM exercises WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?;
+        let Some(tags_str) = tags_str else { continue };
+
+        let mut tags: Vec<String> = serde_json::from_str(&tags_str).unwrap_or_default();
+        tags.retain(|tag| !remove.contains(&tag.to_lowercase()));
+        for tag in &add {
+            if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.clone());
+            }
+        }
+
+        let new_str = serde_json::to_string(&tags)?;
+        if new_str != tags_str {
+            tx.execute("UPDATE exercises SET tags = ?2 WHERE id = ?1", params![id, new_str])?;
+            changed += 1;
+        }
+        updated.insert(id, tags);
+    }
+
+    tx.commit()?;
+    info!("Bulk tag edit changed {} of {} exercises", changed, updated.len());
+    Ok(updated)
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteTagReport {
+    /// Exercises the tag was removed from.
+    pub changed: usize,
+    /// Of those, the ones left with no tags at all.
+    pub untagged: usize,
+}
+
+/// Removes a tag (ASCII case-insensitively) from every exercise, trashed ones included.
+#[command]
+pub fn delete_tag(pool: State<'_, DbPool>, tag: String) -> Result<DeleteTagReport, AppError> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(AppError::InvalidInput("Tag name must not be empty".to_string()));
+    }
+
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    let tagged: Vec<(String, String)> = tx
+        .prepare(&format!("SELECT id, tags FROM exercises WHERE id IN ({})", tagged_with(1)))?
+        .query_map(params![tag], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+
+    let mut report = DeleteTagReport { changed: 0, untagged: 0 };
+    for (id, tags_str) in tagged {
+        let mut tags: Vec<String> = serde_json::from_str(&tags_str).unwrap_or_default();
+        tags.retain(|t| !t.eq_ignore_ascii_case(tag));
+
+        // An empty list is stored as "[]", never NULL
+        tx.execute(
+            "UPDATE exercises SET tags = ?2 WHERE id = ?1",
+            params![id, serde_json::to_string(&tags)?],
+        )?;
+        report.changed += 1;
+        if tags.is_empty() {
+            report.untagged += 1;
+        }
+    }
+
+    tx.commit()?;
+    info!("Deleted {} from {} exercises", tag, report.changed);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_connection;
+    use rusqlite::Connection;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn normalize_tags_trims_folds_case_and_dedupes() {
+        let normalized = normalize_tags(tags(&[
+            "  Homework ",
+            "Linear   Algebra",
+            "",
+            "   ",
+            "HOMEWORK",
+            "eigenvalues",
+            "linear algebra",
+            "Eigenvalues\t",
+        ]));
+        assert_eq!(normalized, tags(&["homework", "linear algebra", "eigenvalues"]));
+    }
+
+    #[test]
+    fn normalize_tags_keeps_first_occurrence_order() {
+        let normalized = normalize_tags(tags(&["Programming", "sorting", "Graphs", "SORTING", "programming"]));
+        assert_eq!(normalized, tags(&["programming", "sorting", "graphs"]));
+    }
+
+    fn stored_tags(conn: &Connection, id: &str) -> Vec<String> {
+        let json: String = conn
+            .query_row("SELECT tags FROM exercises WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    /// Loads the exercise and saves it back the way `save_exercise` does.
+    fn resave(conn: &Connection, id: &str) {
+        let mut exercise = conn
+            .query_row(
+                &format!("SELECT {} FROM exercises WHERE id = ?1", EXERCISE_COLUMNS),
+                [id],
+                Exercise::from_row,
+            )
+            .unwrap();
+        exercise.tags = normalize_tags(exercise.tags);
+        exercise.upsert(conn).unwrap();
+    }
+
+    #[test]
+    fn renaming_to_a_mixed_case_name_survives_a_save() {
+        let mut conn = test_connection();
+        conn.execute(
+            "INSERT INTO exercises (id, name, tags, course, week, created_at)
+             VALUES ('e', 'Ex 1', '[\"proof\", \"x\"]', 'Algebra', 1, 0)",
+            [],
+        )
+        .unwrap();
+
+        let tx = conn.transaction().unwrap();
+        assert_eq!(rename(&tx, "X", " Linear  Algebra ").unwrap(), 1);
+        tx.commit().unwrap();
+        assert_eq!(stored_tags(&conn, "e"), tags(&["proof", "linear algebra"]));
+
+        resave(&conn, "e");
+        assert_eq!(stored_tags(&conn, "e"), tags(&["proof", "linear algebra"]));
+        let name: String = conn
+            .query_row("SELECT name FROM tags WHERE name = 'linear algebra'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(name, "linear algebra");
+    }
+
+    #[test]
+    fn merging_into_a_mixed_case_target_normalizes_it() {
+        let mut conn = test_connection();
+        conn.execute(
+            "INSERT INTO exercises (id, name, tags, course, week, created_at)
+             VALUES ('e', 'Ex 1', '[\"graphs\", \"bfs\", \"dfs\"]', 'Algorithms', 1, 0)",
+            [],
+        )
+        .unwrap();
+
+        let tx = conn.transaction().unwrap();
+        let changed = merge(&tx, &tags(&["bfs", "dfs"]), "Graph Search").unwrap();
+        tx.commit().unwrap();
+        assert_eq!(changed, tags(&["e"]));
+        assert_eq!(stored_tags(&conn, "e"), tags(&["graphs", "graph search"]));
+
+        resave(&conn, "e");
+        assert_eq!(stored_tags(&conn, "e"), tags(&["graphs", "graph search"]));
+    }
+}
@@ -0,0 +1,158 @@
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use tauri::{command, State};
+use tracing::info;
+
+use crate::db::{get_connection, DbPool};
+use crate::error::AppError;
+use crate::settings::Settings;
+
+/// A stretch of time spent on an exercise. At most one is open at a time.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimerSession {
+    pub id: i64,
+    pub exercise_id: String,
+    /// Unix milliseconds.
+    pub started_at: i64,
+    /// Unset while the timer runs.
+    pub ended_at: Option<i64>,
+}
+
+const SESSION_COLUMNS: &str = "id, exercise_id, started_at, ended_at";
+
+impl TimerSession {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(TimerSession {
+            id: row.get(0)?,
+            exercise_id: row.get(1)?,
+            started_at: row.get(2)?,
+            ended_at: row.get(3)?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoppedTimer {
+    /// Length of the session just stopped.
+    pub seconds: i64,
+    /// The exercise's total including it.
+    pub total_seconds: i64,
+}
+
+fn running_session(conn: &Connection) -> Result<Option<TimerSession>, AppError> {
+    let session = conn
+        .query_row(
+            &format!("SELECT {} FROM timer_sessions WHERE ended_at IS NULL", SESSION_COLUMNS),
+            [],
+            TimerSession::from_row,
+        )
+        .optional()?;
+    Ok(session)
+}
+
+/// Ends every open session at `now`, or `max_ms` after it started if that is
+/// sooner, and adds the time to its exercise. Returns how many were closed.
+fn close_open_sessions(conn: &Connection, now: i64, max_ms: Option<i64>) -> Result<usize, AppError> {
+    // MAX keeps a clock that went backwards from making a session negative
+    let end = "MAX(started_at, MIN(?1, COALESCE(started_at + ?2, ?1)))";
+    conn.execute(
+        &format!(
+            "UPDATE exercises SET total_seconds = total_seconds + (
+                 SELECT SUM(({end} - started_at) / 1000) FROM timer_sessions
+                 WHERE exercise_id = exercises.id AND ended_at IS NULL
+             )
+             WHERE id IN (SELECT exercise_id FROM timer_sessions WHERE ended_at IS NULL)",
+            end = end
+        ),
+        params![now, max_ms],
+    )?;
+    let closed = conn.execute(
+        &format!("UPDATE timer_sessions SET ended_at = {} WHERE ended_at IS NULL", end),
+        params![now, max_ms],
+    )?;
+    Ok(closed)
+}
+
+/// Closes sessions left open when the app last quit without stopping its timer,
+/// most likely in a crash. Their length is capped at the `max_session_minutes`
+/// setting, since the real end is unknown.
+pub fn close_dangling_sessions(conn: &Connection) -> Result<(), AppError> {
+    let max_ms = i64::from(Settings::load(conn)?.max_session_minutes) * 60 * 1000;
+    let closed = close_open_sessions(conn, chrono::Utc::now().timestamp_millis(), Some(max_ms))?;
+    if closed > 0 {
+        info!("Closed {} timer session(s) left open", closed);
+    }
+    Ok(())
+}
+
+/// Starts timing an exercise, first stopping the timer of any other exercise.
+/// Returns the running session, which is the existing one if this exercise's
+/// timer is already running.
+#[command]
+pub fn start_timer(pool: State<'_, DbPool>, exercise_id: String) -> Result<TimerSession, AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    let exists: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM exercises WHERE id = ?1 AND deleted_at IS NULL)",
+        params![exercise_id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::NotFound(format!("exercise {}", exercise_id)));
+    }
+
+    if let Some(running) = running_session(&tx)? {
+        if running.exercise_id == exercise_id {
+            return Ok(running);
+        }
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    close_open_sessions(&tx, now, None)?;
+    let session = tx.query_row(
+        &format!(
+            "INSERT INTO timer_sessions (exercise_id, started_at) VALUES (?1, ?2) RETURNING {}",
+            SESSION_COLUMNS
+        ),
+        params![exercise_id, now],
+        TimerSession::from_row,
+    )?;
+    tx.commit()?;
+
+    Ok(session)
+}
+
+/// Stops the exercise's running timer and adds the session to its total.
+#[command]
+pub fn stop_timer(pool: State<'_, DbPool>, exercise_id: String) -> Result<StoppedTimer, AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    let running = running_session(&tx)?
+        .filter(|session| session.exercise_id == exercise_id)
+        .ok_or_else(|| AppError::NotFound(format!("running timer for exercise {}", exercise_id)))?;
+
+    let now = chrono::Utc::now().timestamp_millis();
+    close_open_sessions(&tx, now, None)?;
+    let total_seconds: i64 = tx.query_row(
+        "SELECT total_seconds FROM exercises WHERE id = ?1",
+        params![exercise_id],
+        |row| row.get(0),
+    )?;
+    tx.commit()?;
+
+    Ok(StoppedTimer {
+        seconds: (now - running.started_at).max(0) / 1000,
+        total_seconds,
+    })
+}
+
+/// The session being timed, if any.
+#[command]
+pub fn get_running_timer(pool: State<'_, DbPool>) -> Result<Option<TimerSession>, AppError> {
+    let conn = get_connection(&pool)?;
+    running_session(&conn)
+}
@@ -0,0 +1,138 @@
+use rusqlite::{params, Connection};
+use std::fs;
+use std::path::Path;
+use tauri::{command, State};
+use tracing::info;
+
+use crate::db::{get_connection, DbPool};
+use crate::error::AppError;
+use crate::images::thumbnail_path;
+use crate::models::{Exercise, EXERCISE_COLUMNS};
+
+/// Exercises in the trash, most recently deleted first.
+#[command]
+pub fn list_trash(pool: State<'_, DbPool>) -> Result<Vec<Exercise>, AppError> {
+    let conn = get_connection(&pool)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM exercises WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+        EXERCISE_COLUMNS
+    ))?;
+    let exercises = stmt
+        .query_map([], Exercise::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(exercises)
+}
+
+/// Takes an exercise back out of the trash, at its old position.
+#[command]
+pub fn restore_exercise(pool: State<'_, DbPool>, id: String) -> Result<(), AppError> {
+    let conn = get_connection(&pool)?;
+
+    let updated = conn.execute(
+        "UPDATE exercises SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+        params![id],
+    )?;
+    if updated == 0 {
+        return Err(AppError::NotFound(format!("trashed exercise {}", id)));
+    }
+
+    Ok(())
+}
+
+/// Trashed exercises are purged for good at startup once they have been in the
+/// trash this long.
+const RETENTION_DAYS: i64 = 30;
+
+/// Exercises trashed before `?1`, and the trashed parts of those, which go with
+/// their parent.
+const PURGED_IDS: &str = "SELECT id FROM exercises WHERE deleted_at < ?1
+     UNION
+     SELECT id FROM exercises
+     WHERE deleted_at IS NOT NULL AND parent_id IN (SELECT id FROM exercises WHERE deleted_at < ?1)";
+
+/// Permanently deletes the exercises trashed before `cutoff` (Unix milliseconds)
+/// and their trashed parts, along with their attachments and the image files no remaining exercise refers
+/// to. Apart from solution images replaced by `save_solution` and images detached
+/// with `remove_exercise_image`, this is the only place image files are removed.
+/// Returns the number of exercises purged.
+fn purge_trashed_before(conn: &mut Connection, cutoff: i64) -> Result<usize, AppError> {
+    let tx = conn.transaction()?;
+
+    let paths: Vec<String> = tx
+        .prepare(&format!(
+            "SELECT DISTINCT path FROM exercise_images WHERE exercise_id IN ({})",
+            PURGED_IDS
+        ))?
+        .query_map(params![cutoff], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    let attachments: Vec<String> = tx
+        .prepare(&format!("SELECT path FROM attachments WHERE exercise_id IN ({})", PURGED_IDS))?
+        .query_map(params![cutoff], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+
+    let purged = tx.execute(&format!("DELETE FROM exercises WHERE id IN ({})", PURGED_IDS), params![cutoff])?;
+    // Deletes from before the cutoff can't be brought back any more
+    tx.execute("DELETE FROM undo_operations WHERE deleted_at < ?1", params![cutoff])?;
+    tx.execute(
+        "DELETE FROM course_metadata WHERE course NOT IN (SELECT course FROM exercises)",
+        [],
+    )?;
+    tx.execute(
+        "DELETE FROM week_metadata WHERE NOT EXISTS (
+             SELECT 1 FROM exercises WHERE exercises.course = week_metadata.course AND exercises.week = week_metadata.week
+         )",
+        [],
+    )?;
+
+    // Page images are shared by every exercise cropped from the same page
+    let mut unreferenced = Vec::new();
+    for path in paths {
+        let still_used: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM exercise_images WHERE path = ?1)",
+            params![path],
+            |row| row.get(0),
+        )?;
+        if !still_used {
+            unreferenced.push(path);
+        }
+    }
+
+    tx.commit()?;
+
+    // Every attachment is its own copy, so nothing else can be using it
+    for path in attachments {
+        let _ = fs::remove_file(path);
+    }
+    for path in unreferenced {
+        if let Some(thumbnail) = thumbnail_path(Path::new(&path)) {
+            let _ = fs::remove_file(thumbnail);
+        }
+        let _ = fs::remove_file(path);
+    }
+
+    Ok(purged)
+}
+
+/// Permanently deletes everything in the trash, as `purge_trashed_before` does.
+/// Returns the number of exercises purged.
+#[command]
+pub fn purge_trash(pool: State<'_, DbPool>) -> Result<usize, AppError> {
+    let mut conn = get_connection(&pool)?;
+    let purged = purge_trashed_before(&mut conn, i64::MAX)?;
+
+    info!("Purged {} exercises", purged);
+    Ok(purged)
+}
+
+/// Permanently deletes exercises that have been in the trash for more than
+/// `RETENTION_DAYS`, for when the vault is opened.
+pub fn purge_expired(conn: &mut Connection) -> Result<(), AppError> {
+    let cutoff = chrono::Utc::now().timestamp_millis() - RETENTION_DAYS * 24 * 60 * 60 * 1000;
+    let purged = purge_trashed_before(conn, cutoff)?;
+    if purged > 0 {
+        info!("Purged {} exercises trashed over {} days ago", purged, RETENTION_DAYS);
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use rusqlite::{params, OptionalExtension, Transaction};
+use serde::Serialize;
+use tauri::{command, State};
+use tracing::info;
+
+use crate::db::{get_connection, DbPool};
+use crate::error::AppError;
+
+/// How many destructive operations can be undone.
+const UNDO_LIMIT: i64 = 5;
+
+/// Deletes only move exercises to the trash, so undoing one is a matter of taking
+/// the same exercises back out. Image files stay on disk until the trash is purged,
+/// which also clears this stack.
+pub fn record_operation(
+    tx: &Transaction,
+    kind: &str,
+    target: &str,
+    deleted_at: i64,
+    exercise_ids: &[String],
+) -> Result<(), AppError> {
+    if exercise_ids.is_empty() {
+        return Ok(());
+    }
+
+    tx.execute(
+        "INSERT INTO undo_operations (kind, target, deleted_at, exercise_ids) VALUES (?1, ?2, ?3, ?4)",
+        params![kind, target, deleted_at, serde_json::to_string(exercise_ids)?],
+    )?;
+    tx.execute(
+        "DELETE FROM undo_operations WHERE id NOT IN (
+            SELECT id FROM undo_operations ORDER BY id DESC LIMIT ?1
+        )",
+        params![UNDO_LIMIT],
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoneOperation {
+    /// The command that was undone, e.g. `delete_course`.
+    kind: String,
+    /// The exercise id or course name it was called with; for bulk deletes, how
+    /// many exercises were trashed.
+    target: String,
+    /// Exercises taken back out of the trash.
+    restored: usize,
+}
+
+/// Reverses the most recent delete, or returns `None` when there is nothing left
+/// to undo. Exercises restored from the trash since then are left alone.
+#[command]
+pub fn undo_last_operation(pool: State<'_, DbPool>) -> Result<Option<UndoneOperation>, AppError> {
+    let mut conn = get_connection(&pool)?;
+    let tx = conn.transaction()?;
+
+    let last = tx
+        .query_row(
+            "SELECT id, kind, target FROM undo_operations ORDER BY id DESC LIMIT 1",
+            [],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)),
+        )
+        .optional()?;
+    let Some((id, kind, target)) = last else {
+        return Ok(None);
+    };
+
+    let restored = tx.execute(
+        "UPDATE exercises SET deleted_at = NULL
+         WHERE id IN (SELECT value FROM json_each((SELECT exercise_ids FROM undo_operations WHERE id = ?1)))
+           AND deleted_at = (SELECT deleted_at FROM undo_operations WHERE id = ?1)",
+        params![id],
+    )?;
+    tx.execute("DELETE FROM undo_operations WHERE id = ?1", params![id])?;
+    tx.commit()?;
+
+    info!("Undid {} of {}, restored {} exercises", kind, target, restored);
+    Ok(Some(UndoneOperation { kind, target, restored }))
+}
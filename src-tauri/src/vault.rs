@@ -0,0 +1,126 @@
+use rusqlite::{params, DatabaseName};
+use serde::Serialize;
+use std::fs;
+use tauri::{command, State};
+use tracing::info;
+
+use crate::db::{get_connection, init_db, replace_database_file, DbPool};
+use crate::error::AppError;
+use crate::gemini::HttpClient;
+
+#[derive(Debug, Serialize)]
+pub struct VaultStatus {
+    pub encrypted: bool,
+    pub locked: bool,
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), AppError> {
+    if passphrase.is_empty() {
+        return Err(AppError::InvalidInput("Passphrase cannot be empty".to_string()));
+    }
+    Ok(())
+}
+
+#[command]
+pub fn get_vault_status(pool: State<'_, DbPool>) -> VaultStatus {
+    VaultStatus {
+        encrypted: pool.is_encrypted(),
+        locked: pool.is_locked(),
+    }
+}
+
+/// Encrypts a plaintext vault. The database is copied into a new encrypted file
+/// with `sqlcipher_export`, which then replaces the original.
+#[command]
+pub fn set_vault_passphrase(
+    pool: State<'_, DbPool>,
+    http: State<'_, HttpClient>,
+    passphrase: String,
+) -> Result<(), AppError> {
+    check_passphrase(&passphrase)?;
+    if pool.is_encrypted() {
+        return Err(AppError::InvalidInput(
+            "Vault is already encrypted; use change_passphrase instead".to_string(),
+        ));
+    }
+
+    let mut encrypted_path = pool.db_path().as_os_str().to_owned();
+    encrypted_path.push(".encrypting");
+    let encrypted_path = std::path::PathBuf::from(encrypted_path);
+    let _ = fs::remove_file(&encrypted_path);
+
+    {
+        let conn = get_connection(&pool)?;
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        conn.execute(
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            params![encrypted_path.to_string_lossy(), passphrase],
+        )?;
+        let exported = conn
+            .query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))
+            // sqlcipher_export doesn't carry the schema version over
+            .and_then(|_| conn.pragma_update(Some(DatabaseName::Attached("encrypted")), "user_version", version));
+        conn.execute("DETACH DATABASE encrypted", [])?;
+        if let Err(e) = exported {
+            let _ = fs::remove_file(&encrypted_path);
+            return Err(e.into());
+        }
+    }
+
+    let reopened = pool.reopen(Some(passphrase), |db_path| {
+        replace_database_file(db_path, &encrypted_path)
+    });
+    http.reset();
+    reopened?;
+
+    info!("Vault encrypted");
+    Ok(())
+}
+
+/// Opens an encrypted vault and brings its schema up to date.
+#[command]
+pub fn unlock_vault(
+    pool: State<'_, DbPool>,
+    http: State<'_, HttpClient>,
+    passphrase: String,
+) -> Result<(), AppError> {
+    if !pool.is_locked() {
+        return Ok(());
+    }
+
+    pool.verify_key(&passphrase)?;
+    pool.reopen(Some(passphrase), |_| Ok(()))?;
+    http.reset();
+    init_db(&pool)?;
+
+    info!("Vault unlocked");
+    Ok(())
+}
+
+/// Re-encrypts the vault under a new passphrase with SQLCipher's `rekey`.
+#[command]
+pub fn change_passphrase(
+    pool: State<'_, DbPool>,
+    http: State<'_, HttpClient>,
+    current_passphrase: String,
+    new_passphrase: String,
+) -> Result<(), AppError> {
+    check_passphrase(&new_passphrase)?;
+    if !pool.is_encrypted() {
+        return Err(AppError::InvalidInput(
+            "Vault is not encrypted; use set_vault_passphrase instead".to_string(),
+        ));
+    }
+    pool.verify_key(&current_passphrase)?;
+
+    {
+        let conn = get_connection(&pool)?;
+        conn.pragma_update(None, "rekey", &new_passphrase)?;
+    }
+    // Pooled connections still hold the old key
+    pool.reopen(Some(new_passphrase), |_| Ok(()))?;
+    http.reset();
+
+    info!("Passphrase changed");
+    Ok(())
+}
@@ -1,8 +1,11 @@
 export interface BoundingBox {
+  page: number; // 1-based page of the source PDF, 1 for single images
   y: number;
   height: number;
 }
 
+export type ExerciseStatus = "todo" | "in_progress" | "done";
+
 export interface Exercise {
   id: string;
   name: string;
@@ -13,10 +16,327 @@ export interface Exercise {
   notes?: string; // User notes/solution
   imageUri?: string; // Base64 cropped image
   pageImageUri?: string; // Full page context
-  boundingBox?: BoundingBox;
+  boundingBoxes?: BoundingBox[]; // One per page the exercise covers, consecutive and in order
   createdAt: number;
+  orderIndex?: number; // Position within its course/week
+  sourcePage?: number; // 1-based page of the source PDF
+  sourceFile?: string; // File name of the PDF or image it was extracted from
+  deletedAt?: number; // Set while the exercise is in the trash
+  updatedAt?: number; // Set by the backend on every change
+  status?: ExerciseStatus; // "todo" when missing
+  difficulty?: number; // 1-5 stars, unset until rated
+  isFavorite?: boolean;
+  dueAt?: number; // Deadline, ms
+  review?: ReviewState; // Unset until the first review
+  totalSeconds?: number; // Tracked with the timer
+  solutionText?: string;
+  solutionImageUri?: string;
+  images?: ExerciseImage[]; // All images in display order; imageUri etc. are the first of each kind
+  parentId?: string; // Set on parts (a, b, c) of another exercise
+}
+
+export type ImageKind = "crop" | "page" | "solution";
+
+export interface ExerciseImage {
+  path: string;
+  kind: ImageKind;
+  position: number;
+}
+
+export interface ReviewState {
+  easeFactor: number;
+  intervalDays: number;
+  repetitions: number; // Successful reviews in a row
+  nextReviewAt: number; // ms
+}
+
+export interface TimerSession {
+  id: number;
+  exerciseId: string;
+  startedAt: number; // ms
+  endedAt?: number; // Unset while running
+}
+
+export interface StoppedTimer {
+  seconds: number; // This session
+  totalSeconds: number; // The exercise's total including it
+}
+
+export type AttemptOutcome = 'solved' | 'partial' | 'failed';
+
+export interface Attempt {
+  id: number;
+  exerciseId: string;
+  attemptedAt: number; // ms
+  outcome: AttemptOutcome;
+  minutesSpent?: number;
+  note?: string;
+}
+
+export type LinkRelation = 'related' | 'prerequisite' | 'duplicate-of';
+
+export interface LinkedExercise {
+  exercise: Exercise;
+  relation: LinkRelation;
+  direction: 'outgoing' | 'incoming'; // outgoing: the link starts at the exercise asked about
+}
+
+export interface Attachment {
+  id: string;
+  exerciseId: string;
+  fileName: string; // Name of the file that was attached
+  path: string; // The copy in the vault's attachments folder
+  mimeType: string;
+  sizeBytes: number;
+  createdAt: number; // ms
+}
+
+// Fields for updateExerciseFields; omitted ones are left alone, null clears
+export interface ExerciseUpdate {
+  name?: string;
+  tags?: string[];
+  notes?: string | null;
+  content?: string | null;
+  course?: string;
+  week?: number;
+  boundingBoxes?: BoundingBox[]; // [] clears
+  status?: ExerciseStatus;
+  difficulty?: number | null;
+  isFavorite?: boolean;
+  dueAt?: number | null;
+}
+
+export interface CreatedExercise {
+  id: string;
+  imagePath: string; // As stored in the exercise's imageUri
+}
+
+export interface ExercisePage {
+  items: Exercise[];
+  total: number; // Matching exercises across all pages
+}
+
+export interface Completion {
+  total: number;
+  done: number;
+  inProgress: number;
+  overdue: number; // Not done and past their due date
+  percentDone: number; // 0-100
+}
+
+export interface WeekStatistics extends Completion {
+  week: number;
+}
+
+export interface DifficultyDistribution {
+  rated: number[]; // Exercises rated 1 to 5 stars, in that order
+  unrated: number;
+}
+
+export interface AttemptStatistics {
+  total: number;
+  solved: number;
+  solveRate: number; // 0-100
+}
+
+export interface CourseStatistics extends Completion {
+  course: string;
+  difficulty: DifficultyDistribution;
+  attempts: AttemptStatistics;
+  weeks: WeekStatistics[];
+}
+
+export interface Statistics extends Completion {
+  courses: CourseStatistics[];
+}
+
+export interface DatabaseStats {
+  courseCount: number;
+  weekCount: number;
+  exerciseCount: number;
+  totalImageBytes: number; // Referenced images and their thumbnails, trash included
+  tagCount: number;
+  exercisesPerType: Record<string, number>; // By first tag
+}
+
+export interface CourseSummary {
+  name: string;
+  exerciseCount: number;
+  weekCount: number;
+  lastModified?: number; // ms
+  archived: boolean;
+}
+
+export interface TagCount {
+  tag: string;
+  count: number; // Exercises outside the trash using it
+}
+
+export interface CourseMetadata {
+  color?: string; // "#rrggbb" or "#rgb"
+  semester?: string;
+  archived: boolean;
+  description?: string;
+}
+
+export interface WeekMetadata {
+  title?: string;
+  startDate?: string; // YYYY-MM-DD
+  endDate?: string; // YYYY-MM-DD
+}
+
+export interface Week extends WeekMetadata {
+  week: number;
+  exerciseCount: number;
+}
+
+export interface Course extends CourseMetadata {
+  name: string;
+  exerciseCount: number;
+  weeks: Week[];
+}
+
+export interface SearchResult {
+  exercise: Exercise;
+  matchedFields: Array<'name' | 'content' | 'notes' | 'tags' | 'solution'>;
+  snippet: string; // Hits wrapped in **
+}
+
+export interface SearchPage {
+  items: SearchResult[];
+  total: number;
+}
+
+export interface DeleteTagReport {
+  changed: number;
+  untagged: number; // Exercises left with no tags
+}
+
+export interface UndoneOperation {
+  kind: 'delete_exercise' | 'delete_course' | 'bulk_delete_exercises';
+  target: string; // Exercise id, course name, or "<n> exercises" for bulk deletes
+  restored: number;
+}
+
+export interface CostEstimate {
+  pages: number;
+  estimatedImageBytes: number;
+  estimatedInputTokens: number; // Excludes output tokens; the billed amount can differ
+}
+
+export interface AnkiExportReport {
+  cards: number;
+  images: number;
+}
+
+export interface MergeCoursesReport {
+  moved: number;
+  mergedWeeks: number[]; // Weeks both courses had
+}
+
+export interface BulkDeleteResult {
+  id: string;
+  deleted: boolean;
+  reason?: string; // Set when deleted is false
+}
+
+export type GeminiModel = 'gemini-2.5-flash' | 'gemini-2.5-pro' | 'gemini-2.5-flash-lite';
+
+// Result of validateApiKey; network_error means the key couldn't be checked
+export type ApiKeyStatus = 'valid' | 'invalid_key' | 'quota_exceeded' | 'network_error';
+
+export type SaveFormat = 'png' | 'webp' | 'jpeg';
+
+// Stored in app_settings; see getSetting/setSetting
+export interface StoredSettings {
+  pdf_dpi: number; // 72-600, default 150
+  image_format: SaveFormat; // Default 'png'
+  jpeg_quality: number; // 1-100, default 85
+  max_session_minutes: number; // 1-1440, default 120; caps a timer left running when the app quit
+  analysis_chunk_pages: number; // 1-50, default 5; pages per Gemini request in analyzePages
+}
+
+export interface HistoryEntry {
+  id: number;
+  exerciseId: string;
+  name: string;
+  tags: string[];
+  notes?: string;
+  content: string;
+  reason: 'update' | 'delete';
+  recordedAt: number;
+}
+
+export interface BrokenImageRef {
+  exerciseId: string;
+  exerciseName: string;
+  path: string;
+  kind: ImageKind;
+  problem: 'missing' | 'corrupt';
+}
+
+export interface OrphanReport {
+  files: string[];
+  totalBytes: number;
+  deleted: boolean; // False for a dry run
+}
+
+export interface OptimizeReport {
+  beforeBytes: number;
+  afterBytes: number;
+}
+
+export interface StorageCleanupReport {
+  orphanedImagesDeleted: number;
+  bytesFreed: number;
+}
+
+export interface VaultStatus {
+  encrypted: boolean;
+  locked: boolean;
+}
+
+export interface VaultProfile {
+  name: string;
+  path: string;
+  active: boolean;
+  available: boolean; // false when its directory is missing
+}
+
+export type ImportMode = 'merge' | 'replace';
+
+export interface ImportSummary {
+  coursesAdded: number;
+  exercisesAdded: number;
+  exercisesUpdated: number;
+  conflictsSkipped: number; // Older duplicates left untouched during a merge
 }
 
 export interface AppSettings {
   apiKey: string;
 }
+
+export type AppErrorKind =
+  | "db"
+  | "busy"
+  | "vaultLocked"
+  | "migration"
+  | "io"
+  | "gemini"
+  | "rateLimited"
+  | "missingApiKey"
+  | "cancelled"
+  | "keyring"
+  | "pdfConversion"
+  | "base64"
+  | "image"
+  | "json"
+  | "archive"
+  | "invalidInput"
+  | "notFound";
+
+// Shape of errors rejected by Tauri commands
+export interface AppError {
+  kind: AppErrorKind;
+  message: string;
+}